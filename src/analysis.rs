@@ -0,0 +1,135 @@
+//! Image analysis
+
+use image::{Rgb, RgbImage};
+
+use crate::error::*;
+
+/// Number of pixels using each palette entry
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorUsage {
+    /// Pixel counts indexed by palette index
+    pub counts: Vec<u64>,
+}
+
+impl ColorUsage {
+    /// The number of palette entries used by at least one pixel
+    pub fn num_used(&self) -> usize {
+        self.counts.iter().filter(|&&c| c > 0).count()
+    }
+
+    /// Palette indices no pixel uses
+    pub fn unused(&self) -> Vec<u8> {
+        self.counts.iter().enumerate()
+            .filter(|(_, &c)| c == 0)
+            .map(|(i, _)| i as u8)
+            .collect()
+    }
+}
+
+/// Pixel counts per palette index and per RGB channel value
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// Pixel counts indexed by palette index
+    pub counts: Vec<u64>,
+    /// Pixel counts indexed by red value
+    pub red: Vec<u64>,
+    /// Pixel counts indexed by green value
+    pub green: Vec<u64>,
+    /// Pixel counts indexed by blue value
+    pub blue: Vec<u64>,
+}
+
+impl Histogram {
+    /// Derives the RGB histograms from per-index `counts` and `palette`
+    pub fn new(counts: Vec<u64>, palette: &[Rgb<u8>]) -> Histogram {
+        let mut red = vec![0u64; 256];
+        let mut green = vec![0u64; 256];
+        let mut blue = vec![0u64; 256];
+        for (&count, color) in counts.iter().zip(palette) {
+            red[color[0] as usize] += count;
+            green[color[1] as usize] += count;
+            blue[color[2] as usize] += count;
+        }
+        Histogram { counts, red, green, blue }
+    }
+
+    /// Total number of pixels counted
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+/// Fidelity of an image against a reference
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Comparison {
+    /// Peak signal-to-noise ratio over all RGB samples, in dB (infinite when identical)
+    pub psnr: f64,
+    /// Mean structural similarity over 8x8 luma windows, 1.0 when identical
+    pub ssim: f64,
+    /// The largest difference of any channel of any pixel
+    pub max_difference: u8,
+}
+
+/// SSIM window size and step
+const SSIM_WINDOW: u32 = 8;
+const SSIM_STEP: u32 = 4;
+
+fn luma(img: &RgbImage) -> Vec<f64> {
+    img.pixels().map(|p| 0.299 * f64::from(p[0]) + 0.587 * f64::from(p[1]) + 0.114 * f64::from(p[2])).collect()
+}
+
+fn ssim(a: &[f64], b: &[f64], width: u32, height: u32) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let (window_width, window_height) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+    let starts = |size: u32, window: u32| (0..=size - window).step_by(SSIM_STEP as usize);
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y0 in starts(height, window_height) {
+        for x0 in starts(width, window_width) {
+            let indices = (y0..y0 + window_height)
+                .flat_map(|y| (x0..x0 + window_width).map(move |x| (y * width + x) as usize));
+            let n = f64::from(window_width * window_height);
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for i in indices {
+                sum_a += a[i];
+                sum_b += b[i];
+                sum_aa += a[i] * a[i];
+                sum_bb += b[i] * b[i];
+                sum_ab += a[i] * b[i];
+            }
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    total / f64::from(windows)
+}
+
+/// Compares `img` with `reference`, which must have the same size
+pub fn compare(img: &RgbImage, reference: &RgbImage) -> Result<Comparison> {
+    if img.dimensions() != reference.dimensions() {
+        return Err(other_err(format!("image size {}x{} differs from the reference's {}x{}",
+                                     img.width(), img.height(), reference.width(), reference.height())));
+    }
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return Err(other_err("empty images"));
+    }
+
+    let mut squared_error = 0u64;
+    let mut max_difference = 0u8;
+    for (a, b) in img.iter().zip(reference.iter()) {
+        let difference = a.max(b) - a.min(b);
+        squared_error += u64::from(difference) * u64::from(difference);
+        max_difference = max_difference.max(difference);
+    }
+    let mse = squared_error as f64 / img.len() as f64;
+    let psnr = if squared_error == 0 { f64::INFINITY } else { 10.0 * (255.0 * 255.0 / mse).log10() };
+
+    Ok(Comparison { psnr, ssim: ssim(&luma(img), &luma(reference), width, height), max_difference })
+}
@@ -0,0 +1,137 @@
+//! Animations assembled from several decoded images
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use color_quant::NeuQuant;
+use image::RgbImage;
+
+use crate::error::*;
+use crate::output::png::{ColorProfile, encoding_err};
+
+/// Animation container format
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AnimationFormat {
+    /// GIF, with a shared palette of at most 256 colors
+    Gif,
+    /// APNG, keeping every color exactly
+    Apng,
+}
+
+impl AnimationFormat {
+    /// Finds the format by the file extension of `path`: `.gif`, or `.png`/`.apng` for APNG
+    pub fn from_path(path: impl AsRef<Path>) -> Option<AnimationFormat> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "gif" => Some(AnimationFormat::Gif),
+            "png" | "apng" => Some(AnimationFormat::Apng),
+            _ => None,
+        }
+    }
+
+    /// Writes `frames`, which must all have the same size, as a looping animation
+    pub fn write<W: Write>(self, frames: &[RgbImage], delay_ms: u32, w: W) -> Result<()> {
+        match self {
+            AnimationFormat::Gif => write_gif(frames, delay_ms, w),
+            AnimationFormat::Apng => write_apng(frames, delay_ms, w),
+        }
+    }
+}
+
+/// Sampling factor for NeuQuant, 1 (best) to 30 (fastest)
+const QUANTIZER_SAMPLE_FACTOR: i32 = 10;
+
+/// A palette shared by all frames, with each frame as indices into it
+struct SharedPalette {
+    /// RGB triples
+    palette: Vec<u8>,
+    frames: Vec<Vec<u8>>,
+}
+
+/// Uses the exact colors when all frames together have at most 256, quantizing otherwise
+fn shared_palette(frames: &[RgbImage]) -> SharedPalette {
+    let mut colors: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut palette = Vec::new();
+    let exact = frames.iter().flat_map(|f| f.pixels()).all(|p| {
+        if colors.contains_key(&p.0) {
+            return true;
+        }
+        if colors.len() == 256 {
+            return false;
+        }
+        colors.insert(p.0, colors.len() as u8);
+        palette.extend_from_slice(&p.0);
+        true
+    });
+    if exact {
+        let frames = frames.iter()
+            .map(|f| f.pixels().map(|p| colors[&p.0]).collect())
+            .collect();
+        return SharedPalette { palette, frames };
+    }
+
+    let rgba: Vec<u8> = frames.iter()
+        .flat_map(|f| f.pixels())
+        .flat_map(|p| vec![p[0], p[1], p[2], 0xff])
+        .collect();
+    let quantizer = NeuQuant::new(QUANTIZER_SAMPLE_FACTOR, 256, &rgba);
+    let frames = frames.iter()
+        .map(|f| f.pixels().map(|p| quantizer.index_of(&[p[0], p[1], p[2], 0xff]) as u8).collect())
+        .collect();
+    SharedPalette { palette: quantizer.color_map_rgb(), frames }
+}
+
+fn check_frames(frames: &[RgbImage]) -> Result<(u16, u16)> {
+    let first = frames.first().ok_or_else(|| other_err("no frames"))?;
+    if let Some(f) = frames.iter().find(|f| f.dimensions() != first.dimensions()) {
+        return Err(other_err(format!("frame size {}x{} differs from the first frame's {}x{}",
+                                     f.width(), f.height(), first.width(), first.height())));
+    }
+    if first.width() > u32::from(u16::MAX) || first.height() > u32::from(u16::MAX) {
+        return Err(Error::LimitExceeded(format!("{}x{} frames", first.width(), first.height())));
+    }
+    Ok((first.width() as u16, first.height() as u16))
+}
+
+/// Writes `frames`, which must all have the same size, as a looping GIF with a shared palette
+///
+/// `delay_ms` is rounded to the GIF's 1/100 second resolution.
+pub fn write_gif<W: Write>(frames: &[RgbImage], delay_ms: u32, w: W) -> Result<()> {
+    let (width, height) = check_frames(frames)?;
+    let shared = shared_palette(frames);
+    let encoding_err = |e: gif::EncodingError| Error::EncodingError(e.to_string());
+
+    let mut encoder = gif::Encoder::new(w, width, height, &shared.palette).map_err(encoding_err)?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(encoding_err)?;
+    let delay = ((delay_ms + 5) / 10).min(u32::from(u16::MAX)) as u16;
+    for indices in &shared.frames {
+        let frame = gif::Frame {
+            width,
+            height,
+            delay,
+            buffer: Cow::Borrowed(indices),
+            ..gif::Frame::default()
+        };
+        encoder.write_frame(&frame).map_err(encoding_err)?;
+    }
+    Ok(())
+}
+
+/// Writes `frames`, which must all have the same size, as a looping 8-bit RGB APNG tagged as sRGB
+pub fn write_apng<W: Write>(frames: &[RgbImage], delay_ms: u32, w: W) -> Result<()> {
+    let (width, height) = check_frames(frames)?;
+
+    let mut encoder = ColorProfile::Srgb.png_encoder(w, u32::from(width), u32::from(height))?;
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0).map_err(encoding_err)?;
+    let delay = delay_ms.min(u32::from(u16::MAX)) as u16;
+    encoder.set_frame_delay(delay, 1000).map_err(encoding_err)?;
+    let mut writer = encoder.write_header().map_err(encoding_err)?;
+    for frame in frames {
+        writer.write_image_data(frame).map_err(encoding_err)?;
+    }
+    writer.finish().map_err(encoding_err)
+}
@@ -0,0 +1,125 @@
+//! Validated construction of `ImageInfo`
+
+use encoding_rs::SHIFT_JIS;
+
+use crate::error::*;
+use crate::{geometry, ColorMode, ImageInfo, TextEncoding};
+
+/// Maximum size of the machine code, in Shift_JIS bytes
+const MACHINE_CODE_SIZE: usize = 4;
+/// Maximum size of the user name, in Shift_JIS bytes
+const USER_NAME_SIZE: usize = 18;
+
+/// Builds an `ImageInfo`, deriving the stored width and height from the rectangle
+///
+/// # Examples
+/// ```
+/// use mag_image_decoder::ImageInfoBuilder;
+///
+/// let info = ImageInfoBuilder::new(16).machine_code("PC98").size(640, 400).build().unwrap();
+/// assert_eq!((info.end_x, info.end_y), (639, 399));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ImageInfoBuilder {
+    machine_code: String,
+    user_name: String,
+    memo: String,
+    num_colors: u16,
+    /// `None` after `size` with a zero dimension
+    rect: Option<(u16, u16, u16, u16)>,
+    is_200_line_mode: bool,
+}
+
+fn sjis_len(field: &str, s: &str) -> Result<usize> {
+    let (bytes, _, had_errors) = SHIFT_JIS.encode(s);
+    if had_errors {
+        return Err(other_err(format!("{} is not representable in Shift_JIS", field)));
+    }
+    Ok(bytes.len())
+}
+
+impl ImageInfoBuilder {
+    /// Starts a 1x1 image with `num_colors` (16 or 256) and empty text fields
+    pub fn new(num_colors: u16) -> ImageInfoBuilder {
+        ImageInfoBuilder {
+            machine_code: String::new(),
+            user_name: String::new(),
+            memo: String::new(),
+            num_colors,
+            rect: Some((0, 0, 0, 0)),
+            is_200_line_mode: false,
+        }
+    }
+
+    /// Sets the machine code, up to 4 bytes
+    pub fn machine_code(mut self, machine_code: impl Into<String>) -> ImageInfoBuilder {
+        self.machine_code = machine_code.into();
+        self
+    }
+
+    /// Sets the user name, up to 18 Shift_JIS bytes besides trailing padding spaces
+    pub fn user_name(mut self, user_name: impl Into<String>) -> ImageInfoBuilder {
+        self.user_name = user_name.into();
+        self
+    }
+
+    /// Sets the memo, which must not contain 0x1A
+    pub fn memo(mut self, memo: impl Into<String>) -> ImageInfoBuilder {
+        self.memo = memo.into();
+        self
+    }
+
+    /// Sets the inclusive display rectangle
+    pub fn rect(mut self, x: u16, y: u16, end_x: u16, end_y: u16) -> ImageInfoBuilder {
+        self.rect = Some((x, y, end_x, end_y));
+        self
+    }
+
+    /// Sets a `width` x `height` display rectangle at the origin
+    pub fn size(mut self, width: u16, height: u16) -> ImageInfoBuilder {
+        self.rect = width.checked_sub(1).zip(height.checked_sub(1)).map(|(end_x, end_y)| (0, 0, end_x, end_y));
+        self
+    }
+
+    /// Sets 200-line mode
+    pub fn is_200_line_mode(mut self, is_200_line_mode: bool) -> ImageInfoBuilder {
+        self.is_200_line_mode = is_200_line_mode;
+        self
+    }
+
+    /// Validates the fields and builds the `ImageInfo`
+    pub fn build(self) -> Result<ImageInfo> {
+        let color_mode = match self.num_colors {
+            16 => ColorMode::Palette16,
+            256 => ColorMode::Palette256,
+            n => return Err(other_err(format!("unsupported number of colors: {}", n))),
+        };
+        if sjis_len("machine code", &self.machine_code)? > MACHINE_CODE_SIZE {
+            return Err(other_err(format!("machine code exceeds {} bytes", MACHINE_CODE_SIZE)));
+        }
+        if sjis_len("user name", self.user_name.trim_end_matches(' '))? > USER_NAME_SIZE {
+            return Err(other_err(format!("user name exceeds {} bytes", USER_NAME_SIZE)));
+        }
+        sjis_len("memo", &self.memo)?;
+        if self.memo.contains('\u{1a}') {
+            return Err(other_err("memo must not contain 0x1A"));
+        }
+        let (x, y, end_x, end_y) = self.rect.ok_or_else(|| other_err("width and height must be positive"))?;
+        let (width, height) = geometry(color_mode, x, y, end_x, end_y)?;
+
+        Ok(ImageInfo {
+            machine_code: self.machine_code,
+            user_name: self.user_name,
+            memo: self.memo,
+            x,
+            y,
+            end_x,
+            end_y,
+            width,
+            height,
+            num_colors: self.num_colors,
+            is_200_line_mode: self.is_200_line_mode,
+            text_encoding: TextEncoding::ShiftJis,
+        })
+    }
+}
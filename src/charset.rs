@@ -0,0 +1,65 @@
+//! Text encodings of the user name and memo
+
+use std::fmt;
+
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS, UTF_8};
+
+/// Encoding the user name and memo were decoded with
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// Shift_JIS, which the format specifies (ASCII is a subset)
+    #[default]
+    ShiftJis,
+    /// EUC-JP, used by some Unix-side tools
+    EucJp,
+    /// UTF-8, used by some modern tools
+    Utf8,
+}
+
+/// Candidates for `detect`, in order of preference
+const CANDIDATES: &[TextEncoding] = &[TextEncoding::ShiftJis, TextEncoding::Utf8, TextEncoding::EucJp];
+
+impl TextEncoding {
+    /// The `encoding_rs` encoding
+    pub fn encoding(self) -> &'static Encoding {
+        match self {
+            TextEncoding::ShiftJis => SHIFT_JIS,
+            TextEncoding::EucJp => EUC_JP,
+            TextEncoding::Utf8 => UTF_8,
+        }
+    }
+
+    /// The first encoding that decodes the fixed-width `user_name` and the `memo` without errors,
+    /// or Shift_JIS if none does
+    ///
+    /// A multibyte character cut off at the end of the user name is ignored, as when it is decoded.
+    pub fn detect(user_name: &[u8], memo: &[u8]) -> TextEncoding {
+        CANDIDATES.iter().cloned()
+            .find(|candidate| {
+                let encoding = candidate.encoding();
+                !decode_fixed_width(encoding, user_name).1
+                    && encoding.decode_without_bom_handling_and_without_replacement(memo).is_some()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Decodes a fixed-width, space-padded field, dropping a multibyte character cut off by the width
+///
+/// Returns the text, padding included, and whether any other bytes were invalid.
+pub(crate) fn decode_fixed_width(encoding: &'static Encoding, bytes: &[u8]) -> (String, bool) {
+    let padding = bytes.iter().rev().take_while(|&&b| b == b' ').count();
+    let bytes = &bytes[..bytes.len() - padding];
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut text = String::with_capacity(decoder.max_utf8_buffer_length(bytes.len()).unwrap_or(0) + padding);
+    // Not the last input, so an incomplete sequence at the end stays pending instead of becoming U+FFFD
+    let (_, _, had_errors) = decoder.decode_to_string(bytes, &mut text, false);
+    text.extend(std::iter::repeat_n(' ', padding));
+    (text, had_errors)
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.encoding().name())
+    }
+}
@@ -0,0 +1,152 @@
+//! Subcommands of magdecode
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+use mag_image_decoder::{DecodeOptions, Decoder};
+
+pub mod animate;
+pub mod archive;
+pub mod catalog;
+pub mod check;
+pub mod clipboard;
+pub mod collision;
+pub mod color_usage;
+pub mod compare;
+pub mod config;
+pub mod doctor;
+pub mod encode;
+pub mod find;
+pub mod gallery;
+pub mod html;
+pub mod identify;
+pub mod info;
+pub mod lang;
+pub mod remap;
+pub mod report;
+#[cfg(not(target_os = "wasi"))]
+pub mod serve;
+pub mod similar;
+pub mod slideshow;
+pub mod source;
+pub mod visualize;
+
+/// `serve` without sockets to listen on
+#[cfg(target_os = "wasi")]
+pub mod serve {
+    use std::path::Path;
+
+    /// Fails: WASI has no sockets to listen on
+    pub fn run(_dir: &Path, _bind: &str, _port: u16) -> Result<(), String> {
+        Err("serve is not available on WASI".to_owned())
+    }
+}
+
+/// Opens and parses the header of a MAG file
+pub fn open_decoder(path: &Path) -> Result<Decoder, String> {
+    open_decoder_with(path, DecodeOptions::default())
+}
+
+/// Opens and parses the header of a MAG file with `options`
+pub fn open_decoder_with(path: &Path, options: DecodeOptions) -> Result<Decoder, String> {
+    let reader = BufReader::new(File::open(path)
+        .map_err(|e| format!("'{}': {}", path.display(), e))?);
+    Decoder::with_options(reader, options).map_err(|e| format!("'{}': {}", path.display(), e))
+}
+
+fn is_mag_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mag"))
+}
+
+/// Collects `*.mag` files (case-insensitive) under `dir` recursively, sorted by path
+pub fn find_mag_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir).map_err(|e| format!("'{}': {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| format!("'{}': {}", dir.display(), e))?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if is_mag_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(target_os = "wasi")]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::wasi::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(any(unix, target_os = "wasi")))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Reads a list of paths, one per line or NUL-terminated, from `list` (`-` for stdin)
+///
+/// Paths are kept as raw bytes on Unix and WASI, so non-UTF-8 (e.g. Shift_JIS) names survive.
+pub fn read_file_list(list: &Path, nul_delimited: bool) -> Result<Vec<PathBuf>, String> {
+    let mut data = Vec::new();
+    let result = if list == Path::new("-") {
+        io::stdin().lock().read_to_end(&mut data)
+    } else {
+        File::open(list).and_then(|mut f| f.read_to_end(&mut data))
+    };
+    result.map_err(|e| format!("'{}': {}", list.display(), e))?;
+
+    let separator = if nul_delimited { b'\0' } else { b'\n' };
+    Ok(data.split(|&b| b == separator)
+        .map(|entry| if nul_delimited { entry } else { entry.strip_suffix(b"\r").unwrap_or(entry) })
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+/// Makes `path` absolute against the current directory, resolving `.` and `..` lexically
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let path = match env::current_dir() {
+        Ok(cwd) if !path.is_absolute() => cwd.join(path),
+        _ => path.to_owned(),
+    };
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c.as_os_str()),
+        }
+    }
+    out
+}
+
+/// The deepest directory containing all of `files`
+pub fn common_base(files: &[PathBuf]) -> PathBuf {
+    let mut parents = files.iter()
+        .map(|f| normalize_path(f).parent().map(Path::to_owned).unwrap_or_default());
+    let first = parents.next().unwrap_or_default();
+    parents.fold(first, |base, parent| {
+        base.components()
+            .zip(parent.components())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    })
+}
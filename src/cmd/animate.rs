@@ -0,0 +1,27 @@
+//! Animations from a sequence of MAG files
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use log::info;
+use mag_image_decoder::animation::AnimationFormat;
+
+use super::open_decoder;
+
+/// Decodes `inputs` in order and writes them as frames of an animated GIF or APNG to `output`
+pub fn run(inputs: &[PathBuf], output: &Path, delay_ms: u32) -> Result<(), String> {
+    let format = AnimationFormat::from_path(output)
+        .ok_or_else(|| format!("'{}': expected a .gif, .png or .apng output", output.display()))?;
+    let mut frames = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let decoder = open_decoder(input)?;
+        frames.push(decoder.decode().map_err(|e| format!("'{}': {}", input.display(), e))?);
+    }
+
+    info!("animate: {} frames -> '{}'", frames.len(), output.display());
+    let mut w = BufWriter::new(File::create(output).map_err(|e| format!("'{}': {}", output.display(), e))?);
+    format.write(&frames, delay_ms, &mut w)
+        .and_then(|_| w.flush().map_err(Into::into))
+        .map_err(|e| format!("'{}': {}", output.display(), e))
+}
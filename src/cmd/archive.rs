@@ -0,0 +1,121 @@
+//! MAG files inside LZH and ZIP archives
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use delharc::LhaDecodeReader;
+use encoding_rs::SHIFT_JIS;
+use zip::ZipArchive;
+
+use super::is_mag_file;
+
+/// Extended header holding the file name, in level 1 and later headers
+const LHA_EXT_FILENAME: u8 = 0x01;
+/// Extended header holding the directory, with 0xFF separators
+const LHA_EXT_PATH: u8 = 0x02;
+
+/// Archive format, by file extension
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ArchiveKind {
+    Lzh,
+    Zip,
+}
+
+impl ArchiveKind {
+    /// Finds the format by the file extension of `path`: `.lzh`/`.lha` or `.zip`
+    pub fn from_path(path: &Path) -> Option<ArchiveKind> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "lzh" | "lha" => Some(ArchiveKind::Lzh),
+            "zip" => Some(ArchiveKind::Zip),
+            _ => None,
+        }
+    }
+}
+
+/// A MAG file read from an archive
+pub struct Entry {
+    /// The path inside the archive, with `/` separators
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Archive names are UTF-8 only when the archiver says so; Japanese archives are usually Shift_JIS
+fn decode_name(raw: &[u8]) -> String {
+    match std::str::from_utf8(raw) {
+        Ok(name) => name.to_owned(),
+        Err(_) => SHIFT_JIS.decode(raw).0.into_owned(),
+    }
+}
+
+/// The raw path of an LHA entry, with `/` separators
+fn lha_raw_path(header: &delharc::LhaHeader) -> Vec<u8> {
+    let mut dir = Vec::new();
+    let mut name = None;
+    for extra in header.iter_extra() {
+        match extra {
+            [LHA_EXT_FILENAME, data @ ..] => name = Some(data.to_vec()),
+            [LHA_EXT_PATH, data @ ..] => {
+                dir = data.iter().map(|&b| if b == 0xff { b'/' } else { b }).collect();
+                if !dir.is_empty() && !dir.ends_with(b"/") {
+                    dir.push(b'/');
+                }
+            }
+            _ => (),
+        }
+    }
+    let name = name.unwrap_or_else(|| header.filename.iter().map(|&b| if b == b'\\' { b'/' } else { b }).collect());
+    dir.extend(name);
+    dir
+}
+
+fn read_lzh(path: &Path) -> Result<Vec<Entry>, String> {
+    let err = |e: std::io::Error| format!("'{}': {}", path.display(), e);
+    let file = BufReader::new(File::open(path).map_err(err)?);
+    let mut reader = LhaDecodeReader::new(file).map_err(|e| err(e.into()))?;
+    let mut entries = Vec::new();
+    loop {
+        let header = reader.header();
+        let name = decode_name(&lha_raw_path(header));
+        if !header.is_directory() && is_mag_file(Path::new(&name)) {
+            if !reader.is_decoder_supported() {
+                return Err(format!("'{}': {}: unsupported compression method", path.display(), name));
+            }
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).map_err(err)?;
+            reader.crc_check().map_err(|e| format!("'{}': {}: {}", path.display(), name, e))?;
+            entries.push(Entry { name, data });
+        }
+        if !reader.next_file().map_err(|e| err(e.into()))? {
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+fn read_zip(path: &Path) -> Result<Vec<Entry>, String> {
+    let file = BufReader::new(File::open(path).map_err(|e| format!("'{}': {}", path.display(), e))?);
+    let err = |e: zip::result::ZipError| format!("'{}': {}", path.display(), e);
+    let mut archive = ZipArchive::new(file).map_err(err)?;
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(err)?;
+        let name = decode_name(file.name_raw());
+        if file.is_dir() || !is_mag_file(Path::new(&name)) {
+            continue;
+        }
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).map_err(|e| format!("'{}': {}: {}", path.display(), name, e))?;
+        entries.push(Entry { name, data });
+    }
+    Ok(entries)
+}
+
+/// Reads the `*.mag` entries (case-insensitive) of an archive, in archive order
+pub fn read_entries(path: &Path, kind: ArchiveKind) -> Result<Vec<Entry>, String> {
+    match kind {
+        ArchiveKind::Lzh => read_lzh(path),
+        ArchiveKind::Zip => read_zip(path),
+    }
+}
@@ -0,0 +1,143 @@
+//! Metadata catalogs of MAG collections for archive ingestion
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use log::info;
+use mag_image_decoder::{Decoder, ImageInfo};
+use sha2::{Digest, Sha256};
+
+use super::find_mag_files;
+
+/// A cataloged file
+struct Record {
+    /// Path relative to the scanned directory, with `/` separators
+    path: String,
+    info: ImageInfo,
+    /// SHA-256 of the whole file, lowercase hex
+    sha256: String,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn record(dir: &Path, path: &Path) -> Result<Record, String> {
+    let data = fs::read(path).map_err(|e| format!("'{}': {}", path.display(), e))?;
+    let info = Decoder::new(&data[..]).map_err(|e| format!("'{}': {}", path.display(), e))?.into_info();
+    Ok(Record {
+        path: path.strip_prefix(dir).unwrap_or(path).to_string_lossy().replace('\\', "/"),
+        info,
+        sha256: hex(&Sha256::digest(&data)),
+    })
+}
+
+/// Quotes a CSV field when needed (RFC 4180)
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn write_csv<W: Write>(records: &[Record], mut w: W) -> std::io::Result<()> {
+    writeln!(w, "path,width,height,colors,machine,author,memo,sha256")?;
+    for record in records {
+        let info = &record.info;
+        writeln!(w, "{},{},{},{},{},{},{},{}",
+                 csv_field(&record.path),
+                 info.width,
+                 info.height,
+                 info.num_colors,
+                 csv_field(info.machine_code.trim()),
+                 csv_field(info.user_name.trim()),
+                 csv_field(info.memo.trim()),
+                 record.sha256)?;
+    }
+    w.flush()
+}
+
+#[cfg(feature = "sqlite")]
+fn write_sqlite(records: &[Record], path: &Path) -> Result<(), String> {
+    use rusqlite::{params, Connection};
+
+    let sqlite = |e: rusqlite::Error| format!("'{}': {}", path.display(), e);
+    let mut conn = Connection::open(path).map_err(sqlite)?;
+    let tx = conn.transaction().map_err(sqlite)?;
+    tx.execute_batch("
+        CREATE TABLE IF NOT EXISTS images (
+            path TEXT PRIMARY KEY,
+            width INTEGER NOT NULL,
+            height INTEGER NOT NULL,
+            colors INTEGER NOT NULL,
+            machine TEXT NOT NULL,
+            author TEXT NOT NULL,
+            memo TEXT NOT NULL,
+            is_200_line_mode INTEGER NOT NULL,
+            sha256 TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS images_machine ON images (machine);
+        CREATE INDEX IF NOT EXISTS images_author ON images (author);
+        CREATE INDEX IF NOT EXISTS images_sha256 ON images (sha256);
+    ").map_err(sqlite)?;
+    {
+        let mut insert = tx.prepare("INSERT OR REPLACE INTO images VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")
+            .map_err(sqlite)?;
+        for record in records {
+            let info = &record.info;
+            insert.execute(params![
+                record.path,
+                info.width,
+                info.height,
+                info.num_colors,
+                info.machine_code.trim(),
+                info.user_name.trim(),
+                info.memo.trim(),
+                info.is_200_line_mode,
+                record.sha256,
+            ]).map_err(sqlite)?;
+        }
+    }
+    tx.commit().map_err(sqlite)
+}
+
+const NO_SQLITE: &str = "--sqlite requires magdecode built with the `sqlite` feature";
+
+#[cfg(not(feature = "sqlite"))]
+fn write_sqlite(_records: &[Record], _path: &Path) -> Result<(), String> {
+    Err(NO_SQLITE.to_owned())
+}
+
+/// Catalogs all MAG files under `dir` into a CSV file and/or an SQLite database
+///
+/// Rows in an existing database are replaced by path, so re-running updates it.
+pub fn run(dir: &Path, csv: Option<&Path>, sqlite: Option<&Path>) -> Result<(), String> {
+    if csv.is_none() && sqlite.is_none() {
+        return Err("specify an output with --csv and/or --sqlite".to_owned());
+    }
+    if cfg!(not(feature = "sqlite")) && sqlite.is_some() {
+        return Err(NO_SQLITE.to_owned());
+    }
+
+    let mut records = Vec::new();
+    for path in find_mag_files(dir)? {
+        match record(dir, &path) {
+            Ok(record) => records.push(record),
+            Err(e) => eprintln!("Warning: skipped {}", e),
+        }
+    }
+
+    if let Some(csv) = csv {
+        File::create(csv)
+            .and_then(|file| write_csv(&records, BufWriter::new(file)))
+            .map_err(|e| format!("'{}': {}", csv.display(), e))?;
+        info!("{} files cataloged to '{}'", records.len(), csv.display());
+    }
+    if let Some(sqlite) = sqlite {
+        write_sqlite(&records, sqlite)?;
+        info!("{} files cataloged to '{}'", records.len(), sqlite.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! Integrity check without output, for `check`
+
+use mag_image_decoder::{DecodeOptions, Decoder};
+
+use super::source::Item;
+
+fn check(item: &Item, options: DecodeOptions) -> Result<Decoder, String> {
+    let (reader, _) = item.open()?;
+    let decoder = Decoder::with_options(reader, options).map_err(|e| e.to_string())?;
+    decoder.decode_indexed().map_err(|e| e.to_string())?;
+    Ok(decoder)
+}
+
+/// Fully decodes each item, printing `ok` or the error, and the warnings; fails if any item does
+pub fn run(items: &[Item], options: DecodeOptions) -> Result<(), String> {
+    let mut failures = 0;
+    for item in items {
+        match check(item, options.clone()) {
+            Ok(decoder) => {
+                println!("{}: ok", item.name);
+                for warning in decoder.warnings() {
+                    println!("  warning: {}", warning);
+                }
+            }
+            Err(e) => {
+                println!("{}: {}", item.name, e);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        Err(format!("{} of {} files failed the check", failures, items.len()))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+//! Decoded images on the system clipboard, for `--clipboard`
+
+use image::RgbImage;
+
+/// Places `img` on the clipboard
+///
+/// On Linux, the clipboard is owned by the process that set it, so this blocks
+/// until another application replaces the contents.
+#[cfg(all(feature = "clipboard", not(target_os = "wasi")))]
+pub fn copy(img: &RgbImage) -> Result<(), String> {
+    use std::borrow::Cow;
+
+    use arboard::{Clipboard, ImageData};
+
+    let err = |e: arboard::Error| format!("clipboard: {}", e);
+    let bytes: Vec<u8> = img.pixels().flat_map(|p| [p[0], p[1], p[2], 0xff]).collect();
+    let image = ImageData { width: img.width() as usize, height: img.height() as usize, bytes: Cow::Owned(bytes) };
+    let mut clipboard = Clipboard::new().map_err(err)?;
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+        clipboard.set().wait().image(image).map_err(err)
+    }
+    #[cfg(not(target_os = "linux"))]
+    clipboard.set_image(image).map_err(err)
+}
+
+/// Fails: this build has no clipboard support
+#[cfg(any(not(feature = "clipboard"), target_os = "wasi"))]
+pub fn copy(_img: &RgbImage) -> Result<(), String> {
+    Err(if cfg!(target_os = "wasi") {
+        "--clipboard is not available on WASI".to_owned()
+    } else {
+        "--clipboard requires building with the `clipboard` feature".to_owned()
+    })
+}
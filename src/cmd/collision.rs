@@ -0,0 +1,89 @@
+//! Batch inputs mapping to the same output file
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use log::warn;
+
+use super::normalize_path;
+
+/// What to do when several inputs would be written to the same output path
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Collision {
+    /// Fail before converting anything
+    Error,
+    /// Write the later inputs to `NAME-1.EXT`, `NAME-2.EXT`, ...
+    Suffix,
+    /// Let the last input win, with a warning
+    Overwrite,
+}
+
+impl FromStr for Collision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Collision::Error),
+            "suffix" => Ok(Collision::Suffix),
+            "overwrite" => Ok(Collision::Overwrite),
+            _ => Err(format!("unsupported collision policy '{}' (expected one of: error, suffix, overwrite)", s)),
+        }
+    }
+}
+
+/// `path` with `-n` appended to the file stem
+fn suffixed(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+/// Applies `policy` to the output paths of a batch, `None` standing for stdout
+///
+/// `names` are the inputs, for messages. Paths are compared after resolving them
+/// against the current directory.
+pub fn resolve(names: &[&str], outputs: Vec<Option<PathBuf>>, policy: Collision)
+               -> Result<Vec<Option<PathBuf>>, String> {
+    // Resolved output path -> the input writing it
+    let mut writers: HashMap<PathBuf, &str> = HashMap::new();
+    let mut resolved = Vec::with_capacity(outputs.len());
+    for (&name, output) in names.iter().zip(outputs) {
+        let output = match output {
+            Some(output) => output,
+            None => {
+                resolved.push(None);
+                continue;
+            }
+        };
+        let key = normalize_path(&output);
+        let other = match writers.get(&key) {
+            Some(&other) => other,
+            None => {
+                writers.insert(key, name);
+                resolved.push(Some(output));
+                continue;
+            }
+        };
+        match policy {
+            Collision::Error => return Err(format!(
+                "'{}' and '{}' would both be written to '{}' (see --on-collision)", other, name, output.display())),
+            Collision::Overwrite => {
+                warn!("'{}' overwrites the output of '{}': '{}'", name, other, output.display());
+                resolved.push(Some(output));
+            }
+            Collision::Suffix => {
+                let renamed = (1..).map(|n| suffixed(&output, n))
+                    .find(|p| !writers.contains_key(&normalize_path(p)))
+                    .expect("some suffix is free");
+                warn!("'{}' would overwrite the output of '{}', writing '{}' instead", name, other, renamed.display());
+                writers.insert(normalize_path(&renamed), name);
+                resolved.push(Some(renamed));
+            }
+        }
+    }
+    Ok(resolved)
+}
@@ -0,0 +1,17 @@
+//! Palette usage report
+
+use mag_image_decoder::Decoder;
+
+/// Formats the per-index pixel counts and the unused palette entries of `decoder`
+pub fn report(name: &str, decoder: &Decoder) -> Result<String, String> {
+    let usage = decoder.color_usage().map_err(|e| format!("'{}': {}", name, e))?;
+    let palette = decoder.palette();
+    let mut out = format!("{}: {} of {} palette entries used\n", name, usage.num_used(), usage.counts.len());
+    out.push_str("index   pixels  color\n");
+    for (index, (count, rgb)) in usage.counts.iter().zip(palette.iter()).enumerate() {
+        out.push_str(&format!("{:5} {:8}  #{:02x}{:02x}{:02x}\n", index, count, rgb[0], rgb[1], rgb[2]));
+    }
+    let unused: Vec<_> = usage.unused().iter().map(|i| i.to_string()).collect();
+    out.push_str(&format!("unused: {}\n", if unused.is_empty() { "none".to_owned() } else { unused.join(", ") }));
+    Ok(out)
+}
@@ -0,0 +1,18 @@
+//! Fidelity of a decoded MAG file against a reference image
+
+use std::path::Path;
+
+use mag_image_decoder::analysis::compare;
+
+use super::open_decoder;
+
+/// Decodes `file` and prints its PSNR, SSIM and maximum difference against `reference`
+pub fn run(file: &Path, reference: &Path) -> Result<(), String> {
+    let img = open_decoder(file)?.decode().map_err(|e| format!("'{}': {}", file.display(), e))?;
+    let reference_img = image::open(reference).map_err(|e| format!("'{}': {}", reference.display(), e))?.to_rgb();
+    let comparison = compare(&img, &reference_img).map_err(|e| format!("'{}': {}", reference.display(), e))?;
+    println!("PSNR: {:.2} dB", comparison.psnr);
+    println!("SSIM: {:.4}", comparison.ssim);
+    println!("max difference: {}", comparison.max_difference);
+    Ok(())
+}
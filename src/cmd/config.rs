@@ -0,0 +1,86 @@
+//! Default options of `decode` from `magdecode.toml`
+//!
+//! The first of `./magdecode.toml` and `$XDG_CONFIG_HOME/magdecode/magdecode.toml`
+//! (`~/.config/magdecode/magdecode.toml` when unset) that exists is read. Keys are
+//! the long option names, e.g.
+//!
+//! ```toml
+//! outdir = "out"
+//! format = "qoi"
+//! jobs = 4
+//! name-template = "{stem}-decoded.{ext}"
+//! preserve-times = true
+//! ```
+//!
+//! A key only applies when its option, or the `--no-` form of a flag, is not on the command
+//! line, and is skipped when the command line has an option that it conflicts with.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use toml::{Table, Value};
+
+const FILE_NAME: &str = "magdecode.toml";
+
+/// Long options of `decode` that may be set in the config file, with the names of their arguments
+const KEYS: &[(&str, &str)] = &[
+    ("log-format", "LOG_FORMAT"), ("outdir", "DIR"), ("format", "FORMAT"), ("jobs", "JOBS"),
+    ("name-template", "TEMPLATE"), ("icc-profile", "PROFILE"), ("palette-file", "PALETTE"), ("upscale", "ALGORITHM"),
+    ("indexed", "indexed"), ("crt", "crt"), ("scanlines", "BRIGHTNESS"), ("resize", "SIZE"), ("resize-mode", "MODE"),
+    ("filter", "FILTER"), ("rotate", "DEGREES"), ("flip-horizontal", "flip_horizontal"),
+    ("flip-vertical", "flip_vertical"), ("on-collision", "POLICY"), ("preserve-times", "preserve_times"),
+    ("lenient", "lenient"), ("detect-charset", "detect_charset"), ("max-dimensions", "WxH"), ("max-memory", "BYTES"),
+    ("timeout", "SECONDS"), ("timing", "timing"), ("recursive", "recursive"),
+];
+
+fn candidates() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(FILE_NAME)];
+    let config_home = env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(dir) = config_home {
+        paths.push(dir.join("magdecode").join(FILE_NAME));
+    }
+    paths
+}
+
+fn to_args(table: &Table, skip: &dyn Fn(&str) -> bool) -> Result<Vec<OsString>, String> {
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let name = key.replace('_', "-");
+        let arg = match KEYS.iter().find(|(long, _)| *long == name) {
+            Some((_, arg)) => arg,
+            None => {
+                let keys: Vec<_> = KEYS.iter().map(|(long, _)| *long).collect();
+                return Err(format!("unknown key '{}' (expected one of: {})", key, keys.join(", ")));
+            }
+        };
+        if skip(arg) {
+            continue;
+        }
+        let flag = OsString::from(format!("--{}", name));
+        match value {
+            Value::Boolean(true) => args.push(flag),
+            Value::Boolean(false) => (),
+            Value::String(s) => args.extend(vec![flag, s.into()]),
+            Value::Integer(n) => args.extend(vec![flag, n.to_string().into()]),
+            Value::Float(x) => args.extend(vec![flag, x.to_string().into()]),
+            _ => return Err(format!("'{}': expected a string, number or boolean", key)),
+        }
+    }
+    Ok(args)
+}
+
+/// Reads the config file, if any, as command line arguments to add to the user's
+///
+/// Keys whose argument `skip` returns true for (given the clap argument name) are left out.
+pub fn default_args(skip: &dyn Fn(&str) -> bool) -> Result<Vec<OsString>, String> {
+    let path = match candidates().into_iter().find(|p| p.is_file()) {
+        Some(path) => path,
+        None => return Ok(Vec::new()),
+    };
+    let text = fs::read_to_string(&path).map_err(|e| format!("'{}': {}", path.display(), e))?;
+    let table: Table = text.parse().map_err(|e| format!("'{}': {}", path.display(), e))?;
+    to_args(&table, skip).map_err(|e| format!("'{}': {}", path.display(), e))
+}
@@ -0,0 +1,303 @@
+//! Step-by-step structure diagnosis, for `doctor`
+//!
+//! Each part of the file is checked in order, with its offset, so a failure says
+//! which part is broken and what might repair it rather than only what the decoder
+//! gave up on.
+
+use std::fmt::Write as _;
+use std::io::Read;
+
+use super::source::Item;
+
+const MAGIC_NUMBER: &[u8; 8] = b"MAKI02  ";
+const MEMO_OFFSET: usize = 31;
+const MEMO_END: u8 = 0x1a;
+const HEADER_SIZE: usize = 32;
+/// Copy vectors (x in units of half a pixel unit, y in lines) for flag nibbles 1 to 15
+const COPY_VECTORS: [(usize, usize); 16] = [
+    (0, 0), (1, 0), (2, 0), (4, 0), (0, 1), (1, 1), (0, 2), (1, 2),
+    (2, 2), (0, 4), (1, 4), (2, 4), (0, 8), (1, 8), (2, 8), (0, 16),
+];
+
+/// The result of one check
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+#[derive(Default)]
+struct Report {
+    lines: String,
+    problems: usize,
+    failed: bool,
+}
+
+impl Report {
+    fn add(&mut self, status: Status, what: String, fix: Option<&str>) {
+        let label = match status {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        let _ = writeln!(self.lines, "  {:<5} {}", label, what);
+        if let Some(fix) = fix {
+            let _ = writeln!(self.lines, "        fix: {}", fix);
+        }
+        if status != Status::Ok {
+            self.problems += 1;
+        }
+        if status == Status::Fail {
+            self.failed = true;
+        }
+    }
+
+    fn ok(&mut self, what: String) {
+        self.add(Status::Ok, what, None);
+    }
+
+    fn warn(&mut self, what: String, fix: &str) {
+        self.add(Status::Warn, what, Some(fix));
+    }
+
+    fn fail(&mut self, what: String, fix: &str) {
+        self.add(Status::Fail, what, Some(fix));
+    }
+}
+
+fn u16_at(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn u32_at(buf: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]]) as usize
+}
+
+/// A section as declared in the header, in absolute offsets
+struct Section {
+    name: &'static str,
+    start: usize,
+    size: usize,
+}
+
+impl Section {
+    fn end(&self) -> usize {
+        self.start + self.size
+    }
+}
+
+/// Checks everything up to the sections, returning what the stream check needs
+fn check_structure(buf: &[u8], report: &mut Report) -> Option<(usize, usize, usize, [Section; 3])> {
+    if buf.starts_with(MAGIC_NUMBER) {
+        report.ok(format!("magic number \"MAKI02  \" at 0x0 ({} bytes in total)", buf.len()));
+    } else if buf.starts_with(b"MAKI01") {
+        report.fail("magic number is MAKI01, the older MAKI format, not MAG".to_owned(),
+                    "convert the file to MAG with a MAKI-aware tool; MAKI01 is not supported");
+        return None;
+    } else if buf.len() >= 8 && buf[..8].eq_ignore_ascii_case(MAGIC_NUMBER) {
+        report.fail(format!("magic number is {:?}, not \"MAKI02  \"", String::from_utf8_lossy(&buf[..8])),
+                    "the case was changed, probably by a text conversion; restore \"MAKI02  \" at 0x0");
+        return None;
+    } else {
+        let found = String::from_utf8_lossy(&buf[..buf.len().min(8)]).into_owned();
+        report.fail(format!("magic number \"MAKI02  \" missing at 0x0 (found {:?})", found),
+                    "this is not a MAG file, or its start is missing");
+        return None;
+    }
+
+    if buf.len() < MEMO_OFFSET {
+        report.fail(format!("file ends at 0x{:x}, inside the machine code and user name (0x8..0x1f)", buf.len()),
+                    "the file is truncated; recover the rest of it");
+        return None;
+    }
+    report.ok("machine code and user name at 0x8..0x1f".to_owned());
+
+    let header = match buf[MEMO_OFFSET..].iter().position(|&b| b == MEMO_END) {
+        Some(memo_len) => {
+            report.ok(format!("memo terminator 0x1a at 0x{:x} ({}-byte memo)", MEMO_OFFSET + memo_len, memo_len));
+            MEMO_OFFSET + memo_len + 1
+        }
+        None => {
+            report.fail(format!("no memo terminator 0x1a after 0x{:x}, so the header cannot be found", MEMO_OFFSET),
+                        "a text conversion may have dropped 0x1a or the file is truncated; \
+                         insert 0x1a at the end of the memo");
+            return None;
+        }
+    };
+
+    if buf.len() < header + HEADER_SIZE {
+        report.fail(format!("header at 0x{:x} needs {} bytes, but the file ends at 0x{:x}",
+                            header, HEADER_SIZE, buf.len()),
+                    "the file is truncated; recover the rest of it");
+        return None;
+    }
+    if buf[header] != 0 {
+        report.fail(format!("header at 0x{:x} starts with 0x{:02x}, not 0x00", header, buf[header]),
+                    "the memo may contain a stray 0x1a, or bytes were inserted before the header");
+        return None;
+    }
+    let screen_mode = buf[header + 3];
+    let num_colors = if screen_mode & 0x80 != 0 { 256 } else { 16 };
+    let pixel_unit = if num_colors == 256 { 4 } else { 8 };
+    let (x, y, end_x, end_y) = (u16_at(buf, header + 4), u16_at(buf, header + 6),
+                                u16_at(buf, header + 8), u16_at(buf, header + 10));
+    if end_x < x || end_y < y {
+        report.fail(format!("header at 0x{:x}: rectangle ({}, {})-({}, {}) ends before it starts",
+                            header, x, y, end_x, end_y),
+                    "the header is damaged; the rectangle fields are at header + 4..12");
+        return None;
+    }
+    let width = (usize::from(end_x) / pixel_unit - usize::from(x) / pixel_unit + 1) * pixel_unit;
+    let height = usize::from(end_y) - usize::from(y) + 1;
+    if width > usize::from(u16::MAX) {
+        report.fail(format!("header at 0x{:x}: width {} exceeds 65535", header, width),
+                    "the header is damaged; the rectangle fields are at header + 4..12");
+        return None;
+    }
+    report.ok(format!("header at 0x{:x}: {} colors, {}x{} at ({}, {}){}", header, num_colors, width, height, x, y,
+                      if screen_mode & 1 != 0 { ", 200-line mode" } else { "" }));
+    if screen_mode & 0x7e != 0 {
+        report.warn(format!("screen mode 0x{:02x} has bits other than 256 colors and 200-line mode set", screen_mode),
+                    "usually harmless: other savers use them for machine-specific modes");
+    }
+
+    let palette = header + HEADER_SIZE;
+    let palette_end = palette + num_colors * 3;
+    if buf.len() < palette_end {
+        report.fail(format!("palette at 0x{:x}..0x{:x}: file ends at 0x{:x} ({} of {} bytes)",
+                            palette, palette_end, buf.len(), buf.len() - palette, num_colors * 3),
+                    "decode with --lenient to fill missing entries with black, or --palette-file");
+        return None;
+    }
+    report.ok(format!("palette at 0x{:x}..0x{:x}", palette, palette_end));
+
+    let flag_a_len = (width / pixel_unit * height).div_ceil(8);
+    let sections = [
+        Section { name: "flag A", start: header + u32_at(buf, header + 12), size: flag_a_len },
+        Section { name: "flag B", start: header + u32_at(buf, header + 16), size: u32_at(buf, header + 20) },
+        Section { name: "pixel", start: header + u32_at(buf, header + 24), size: u32_at(buf, header + 28) },
+    ];
+    let mut expected = palette_end;
+    for section in &sections {
+        let what = format!("{} section at 0x{:x}..0x{:x}", section.name, section.start, section.end());
+        if section.start < palette_end {
+            report.fail(format!("{} starts inside the header or palette", what),
+                        "the section offsets (header + 12..32) are damaged");
+        } else if section.end() > buf.len() {
+            report.fail(format!("{} runs {} bytes past the end of the file", what, section.end() - buf.len()),
+                        "the file is truncated (recover the rest of it), or the declared size is too \
+                         large (--lenient reads what is there)");
+        } else if section.start != expected {
+            report.warn(format!("{} does not follow the previous section at 0x{:x}", what, expected),
+                        "decode with --lenient to read each section from its own offset");
+        } else {
+            report.ok(what);
+        }
+        expected = section.end();
+    }
+    if sections[1].start < sections[0].start {
+        report.warn("flag B is stored before flag A".to_owned(),
+                    "decode with --lenient, which does not derive the flag A size from the flag B offset");
+    }
+    if report.failed {
+        return None;
+    }
+    let end = sections.iter().map(Section::end).max().unwrap_or(palette_end);
+    if buf.len() > end {
+        report.ok(format!("{} bytes after the last section, from 0x{:x} (see --extract-appended)",
+                          buf.len() - end, end));
+    }
+    Some((width, height, pixel_unit, sections))
+}
+
+/// Walks the flag streams as the decoder does, checking that they and the pixel section are long enough
+fn check_streams(buf: &[u8], width: usize, height: usize, pixel_unit: usize, sections: &[Section; 3],
+                 report: &mut Report) {
+    let [flag_a, flag_b, pixel] = sections;
+    let flag_a_data = &buf[flag_a.start..flag_a.end()];
+    let flag_b_data = &buf[flag_b.start..flag_b.end()];
+    let copy_pixels = pixel_unit / 2;
+    let units = width / pixel_unit;
+    let mut line_flags = vec![0u8; units];
+    let (mut bit, mut flag_b_used, mut pixel_used) = (0, 0, 0);
+    for y in 0..height {
+        for flag in line_flags.iter_mut() {
+            if flag_a_data[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                match flag_b_data.get(flag_b_used) {
+                    Some(&b) => *flag ^= b,
+                    None => {
+                        report.fail(format!("flag B data runs out at line {} of {} (0x{:x}, {} bytes used)",
+                                            y, height, flag_b.end(), flag_b_used),
+                                    "the flag B section is too short, or the flag A data is damaged");
+                        return;
+                    }
+                }
+                flag_b_used += 1;
+            }
+            bit += 1;
+        }
+        for (i, &flag) in line_flags.iter().enumerate() {
+            for (half, nibble) in [flag >> 4, flag & 0xf].iter().enumerate() {
+                let x = (i * 2 + half) * copy_pixels;
+                if *nibble == 0 {
+                    pixel_used += 2;
+                } else {
+                    let (dx, dy) = COPY_VECTORS[*nibble as usize];
+                    if dx * copy_pixels > x || dy > y {
+                        report.fail(format!("copy vector {} at ({}, {}) points outside the image", nibble, x, y),
+                                    "the flag data is damaged at this line");
+                        return;
+                    }
+                }
+            }
+        }
+        if pixel_used > pixel.size {
+            report.fail(format!("pixel data runs out at line {} of {} (0x{:x}, after all {} bytes)",
+                                y, height, pixel.end(), pixel.size),
+                        "the pixel section is too short, or the flag data before this line is damaged");
+            return;
+        }
+    }
+    report.ok(format!("flag streams: {} of {} flag B bytes used", flag_b_used, flag_b.size));
+    report.ok(format!("pixel stream: {} of {} bytes used", pixel_used, pixel.size));
+    if flag_b_used < flag_b.size || pixel_used < pixel.size {
+        report.warn("sections are longer than the image needs".to_owned(),
+                    "usually harmless: some savers pad their sections");
+    }
+}
+
+fn diagnose(item: &Item) -> Result<Report, String> {
+    let (mut reader, _) = item.open()?;
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).map_err(|e| format!("{}: {}", item.name, e))?;
+    let mut report = Report::default();
+    if let Some((width, height, pixel_unit, sections)) = check_structure(&buf, &mut report) {
+        check_streams(&buf, width, height, pixel_unit, &sections, &mut report);
+    }
+    Ok(report)
+}
+
+/// Diagnoses each item, printing every check with its offset and suggested fixes;
+/// fails if any item has a failed check
+pub fn run(items: &[Item]) -> Result<(), String> {
+    let mut failures = 0;
+    for item in items {
+        let report = diagnose(item)?;
+        println!("{}:", item.name);
+        print!("{}", report.lines);
+        println!("  {}", match (report.failed, report.problems) {
+            (true, _) => "cannot be decoded",
+            (false, 0) => "no problems found",
+            (false, _) => "decodable, with warnings",
+        });
+        if report.failed {
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        Err(format!("{} of {} files cannot be decoded", failures, items.len()))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,19 @@
+//! Encodes indexed PNG files back to MAG
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use log::info;
+use mag_image_decoder::Encoder;
+use mag_image_decoder::indexed_png::read_indexed_png;
+
+/// Reads the indexed PNG `input` and writes the MAG file `output`
+pub fn run(input: &Path, output: &Path) -> Result<(), String> {
+    let reader = BufReader::new(File::open(input).map_err(|e| format!("'{}': {}", input.display(), e))?);
+    let (info, image) = read_indexed_png(reader).map_err(|e| format!("'{}': {}", input.display(), e))?;
+    info!("encode: '{}' -> '{}' ({:?})", input.display(), output.display(), info);
+    let w = BufWriter::new(File::create(output).map_err(|e| format!("'{}': {}", output.display(), e))?);
+    Encoder::new(w).encode(&info, &image)
+        .map_err(|e| format!("'{}': {}", output.display(), e))
+}
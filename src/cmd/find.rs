@@ -0,0 +1,44 @@
+//! Searches MAG headers by metadata, without decoding the images
+
+use std::path::Path;
+
+use log::info;
+use mag_image_decoder::ImageInfo;
+
+use super::{find_mag_files, open_decoder};
+
+/// Conditions a file must all meet to be printed
+#[derive(Debug, Default)]
+pub struct Filter {
+    /// Substring of the user name
+    pub author: Option<String>,
+    /// Machine code, compared case-insensitively
+    pub machine: Option<String>,
+    /// Substring of the memo
+    pub memo_contains: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, info: &ImageInfo) -> bool {
+        self.author.as_ref().is_none_or(|author| info.user_name().contains(author.as_str()))
+            && self.machine.as_ref().is_none_or(|machine| info.machine_code().trim().eq_ignore_ascii_case(machine))
+            && self.memo_contains.as_ref().is_none_or(|memo| info.memo().contains(memo.as_str()))
+    }
+}
+
+/// Prints the paths of MAG files under `dir` whose headers match `filter`
+pub fn run(dir: &Path, filter: &Filter) -> Result<(), String> {
+    let mut matched = 0;
+    for path in find_mag_files(dir)? {
+        match open_decoder(&path) {
+            Ok(decoder) if filter.matches(decoder.info()) => {
+                println!("{}", path.display());
+                matched += 1;
+            }
+            Ok(_) => (),
+            Err(e) => eprintln!("Warning: skipped {}", e),
+        }
+    }
+    info!("{} files matched", matched);
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! Static HTML gallery generator
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use image::{FilterType, imageops, RgbImage};
+use log::info;
+use mag_image_decoder::ImageInfo;
+use mag_image_decoder::output::OutputFormat;
+
+use super::html::{self, Card};
+use super::{find_mag_files, open_decoder};
+
+/// Thumbnails fit within this size, in pixels
+pub const THUMBNAIL_SIZE: u32 = 160;
+
+struct Entry {
+    name: String,
+    source: String,
+    info: ImageInfo,
+}
+
+/// Flattens a relative path into a file name unique within the gallery
+///
+/// `a/b.MAG` and `a_b.MAG` flatten alike, so later ones get `-1`, `-2`, ... as with
+/// `--on-collision suffix`. `used` holds the names taken so far, lowercased for
+/// case-insensitive file systems.
+fn entry_name(relative: &Path, used: &mut HashSet<String>) -> String {
+    let flat: String = relative.with_extension("").to_string_lossy()
+        .chars()
+        .map(|c| if c == '/' || c == '\\' { '_' } else { c })
+        .collect();
+    let name = std::iter::once(flat.clone())
+        .chain((1..).map(|n| format!("{}-{}", flat, n)))
+        .find(|name| !used.contains(&name.to_lowercase()))
+        .expect("some suffix is free");
+    used.insert(name.to_lowercase());
+    name
+}
+
+/// Shrinks `img` to fit within `THUMBNAIL_SIZE`
+pub fn thumbnail(img: RgbImage) -> RgbImage {
+    let scale = f64::from(THUMBNAIL_SIZE) / f64::from(img.width().max(img.height()));
+    if scale < 1.0 {
+        let width = ((f64::from(img.width()) * scale).round() as u32).max(1);
+        let height = ((f64::from(img.height()) * scale).round() as u32).max(1);
+        imageops::resize(&img, width, height, FilterType::Triangle)
+    } else {
+        img
+    }
+}
+
+fn convert(path: &Path, name: &str, out_dir: &Path) -> Result<ImageInfo, String> {
+    let decoder = open_decoder(path)?;
+    let img = decoder.decode().map_err(|e| format!("'{}': {}", path.display(), e))?;
+    let image_path = out_dir.join("images").join(format!("{}.png", name));
+    OutputFormat::Png.save(&img, &image_path)
+        .map_err(|e| format!("'{}': {}", image_path.display(), e))?;
+
+    let thumb = thumbnail(img);
+    let thumb_path = out_dir.join("thumbs").join(format!("{}.png", name));
+    OutputFormat::Png.save(&thumb, &thumb_path)
+        .map_err(|e| format!("'{}': {}", thumb_path.display(), e))?;
+
+    Ok(decoder.into_info())
+}
+
+fn render_index(title: &str, entries: &[Entry]) -> String {
+    let cards = entries.iter().map(|entry| Card { key: &entry.name, source: &entry.source, info: &entry.info });
+    html::render_index(title, "", cards, |url| (format!("images/{}.png", url), format!("thumbs/{}.png", url)))
+}
+
+/// Converts all MAG files under `dir` and writes `index.html` with thumbnails into `out_dir`
+pub fn run(dir: &Path, out_dir: &Path) -> Result<(), String> {
+    for sub in &["images", "thumbs"] {
+        let path = out_dir.join(sub);
+        fs::create_dir_all(&path).map_err(|e| format!("'{}': {}", path.display(), e))?;
+    }
+
+    let mut entries = Vec::new();
+    let mut used = HashSet::new();
+    for path in find_mag_files(dir)? {
+        let relative = path.strip_prefix(dir).unwrap_or(&path);
+        let name = entry_name(relative, &mut used);
+        info!("gallery: '{}' -> '{}'", path.display(), name);
+        match convert(&path, &name, out_dir) {
+            Ok(info) => entries.push(Entry { name, source: relative.display().to_string(), info }),
+            Err(e) => eprintln!("Warning: skipped {}", e),
+        }
+    }
+
+    let index_path = out_dir.join("index.html");
+    let title = dir.file_name().map_or_else(|| dir.display().to_string(), |s| s.to_string_lossy().into_owned());
+    fs::write(&index_path, render_index(&title, &entries))
+        .map_err(|e| format!("'{}': {}", index_path.display(), e))?;
+    info!("{} images written to '{}'", entries.len(), out_dir.display());
+    Ok(())
+}
@@ -0,0 +1,90 @@
+//! HTML index pages shared by `gallery` and `serve`
+
+use mag_image_decoder::ImageInfo;
+
+use super::gallery::THUMBNAIL_SIZE;
+
+/// One image on an index page
+pub struct Card<'a> {
+    /// The key the links are built from
+    pub key: &'a str,
+    /// The name shown under the thumbnail
+    pub source: &'a str,
+    pub info: &'a ImageInfo,
+}
+
+pub fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn escape_url(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Renders a page of thumbnails with their metadata
+///
+/// `intro` is inserted as is after the heading. `links` maps the URL-escaped key of a
+/// card to the addresses of its image and its thumbnail.
+pub fn render_index<'a, L>(title: &str, intro: &str, cards: impl IntoIterator<Item = Card<'a>>, links: L) -> String
+    where L: Fn(&str) -> (String, String) {
+    let mut html = format!(r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; background: #222; color: #ddd; }}
+a {{ color: #9cf; }}
+.entry {{ display: inline-block; vertical-align: top; width: {width}px; margin: 8px; }}
+.entry img {{ image-rendering: pixelated; }}
+.meta {{ font-size: small; }}
+.memo {{ white-space: pre-wrap; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{intro}"#, title = escape_html(title), width = THUMBNAIL_SIZE + 40, intro = intro);
+
+    for card in cards {
+        let info = card.info;
+        let (image, thumb) = links(&escape_url(card.key));
+        html.push_str(&format!(r#"<div class="entry">
+<a href="{image}"><img src="{thumb}" alt="{source}" loading="lazy"></a>
+<div class="meta">
+<div>{source}</div>
+<div>{width}x{height}, {colors} colors, {machine}</div>
+<div>{user}</div>
+<div class="memo">{memo}</div>
+</div>
+</div>
+"#,
+            image = image,
+            thumb = thumb,
+            source = escape_html(card.source),
+            width = info.width,
+            height = info.height,
+            colors = info.num_colors,
+            machine = escape_html(info.machine_code.trim()),
+            user = escape_html(info.user_name.trim()),
+            memo = escape_html(info.memo.trim())));
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
@@ -0,0 +1,56 @@
+//! One-line summaries of MAG headers, like ImageMagick's `identify`
+
+use std::fmt::Display;
+
+use mag_image_decoder::{Decoder, ImageInfo, TrailerBlock, TrailerKind};
+
+use super::source::Item;
+
+/// The first non-empty line of the memo, which is conventionally the title
+fn title(memo: &str) -> &str {
+    memo.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("")
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// One line describing the file called `name`
+pub fn summary(name: &dyn Display, info: &ImageInfo, trailer: &[TrailerBlock]) -> String {
+    let mut line = format!("{} MAG {}x{} {}c {}", name, info.width, info.height,
+                           info.num_colors, info.machine_code.trim());
+    if info.is_200_line_mode {
+        line.push_str(" 200-line");
+    }
+    line.push(' ');
+    line.push_str(&quote(title(&info.memo)));
+    for block in trailer.iter().filter(|b| b.kind != TrailerKind::Padding) {
+        line.push_str(&format!(" +{}({} bytes)", block.kind.name(), block.data.len()));
+    }
+    line
+}
+
+/// Prints one line per input item (a file or an archive entry), without decoding
+pub fn list(items: &[Item]) -> Result<(), String> {
+    let mut failures = 0;
+    for item in items {
+        let result = item.open().and_then(|(reader, _)| {
+            Decoder::new(reader).map_err(|e| format!("'{}': {}", item.name, e))
+        });
+        match result {
+            Ok(decoder) => {
+                let trailer = decoder.trailer().unwrap_or_default();
+                println!("{}", summary(&item.name, decoder.info(), &trailer));
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        Err(format!("{} of {} files could not be listed", failures, items.len()))
+    } else {
+        Ok(())
+    }
+}
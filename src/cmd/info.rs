@@ -0,0 +1,62 @@
+//! Full header dump, for `info`
+
+use mag_image_decoder::{DecodeOptions, Decoder, ImageInfo, RetroImage, TrailerBlock};
+
+use super::color_usage;
+use super::source::Item;
+
+fn details(name: &str, format: &str, info: &ImageInfo, trailer: &[TrailerBlock]) -> String {
+    let mut out = format!("{}\n", name);
+    out.push_str(&format!("  format: {}\n", format));
+    out.push_str(&format!("  size: {}x{}{}\n", info.width, info.height,
+                          if info.is_200_line_mode { " (200-line mode, shown doubled)" } else { "" }));
+    out.push_str(&format!("  colors: {}\n", info.num_colors));
+    out.push_str(&format!("  position: ({}, {})-({}, {})\n", info.x, info.y, info.end_x, info.end_y));
+    out.push_str(&format!("  machine: {}\n", info.machine_code.trim_end()));
+    out.push_str(&format!("  user: {}\n", info.user_name.trim_end()));
+    out.push_str(&format!("  text encoding: {}\n", info.text_encoding));
+    for (i, line) in info.memo.lines().enumerate() {
+        out.push_str(&format!("  {} {}\n", if i == 0 { "memo:" } else { "     " }, line));
+    }
+    for block in trailer {
+        out.push_str(&format!("  appended: {} at {} ({} bytes)\n", block.kind.name(), block.offset, block.data.len()));
+    }
+    out
+}
+
+/// Prints the header of each item, with its warnings and, if `color_usage`, the palette usage
+pub fn run(items: &[Item], options: DecodeOptions, color_usage: bool) -> Result<(), String> {
+    let mut failures = 0;
+    for item in items {
+        let result = item.open().and_then(|(reader, _)| {
+            Decoder::with_options(reader, options.clone()).map_err(|e| format!("'{}': {}", item.name, e))
+        });
+        let decoder = match result {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                failures += 1;
+                continue;
+            }
+        };
+        let trailer = decoder.trailer().unwrap_or_default();
+        print!("{}", details(&item.name, decoder.format_name(), decoder.info(), &trailer));
+        for warning in decoder.warnings() {
+            println!("  warning: {}", warning);
+        }
+        if color_usage {
+            match color_usage::report(&item.name, &decoder) {
+                Ok(report) => print!("{}", report),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    failures += 1;
+                }
+            }
+        }
+    }
+    if failures > 0 {
+        Err(format!("{} of {} files could not be read", failures, items.len()))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+//! Message language of the CLI, for `--lang`
+//!
+//! Help and error messages are written in English and translated on output, a
+//! phrase at a time, so text without a translation stays English.
+
+use std::env;
+use std::ffi::OsString;
+use std::str::FromStr;
+
+/// A message language
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    Ja,
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Lang::En),
+            "ja" => Ok(Lang::Ja),
+            _ => Err(format!("unknown language '{}' (expected en or ja)", s)),
+        }
+    }
+}
+
+/// The language of a POSIX locale name such as `ja_JP.UTF-8`
+fn from_locale(locale: &str) -> Lang {
+    if locale.starts_with("ja") { Lang::Ja } else { Lang::En }
+}
+
+impl Lang {
+    /// The language from `--lang`, or else the first set of `LC_ALL`, `LC_MESSAGES` and `LANG`
+    ///
+    /// Fails when Japanese is asked for with `--lang` in a build without the `ja` feature;
+    /// a Japanese locale then falls back to English.
+    pub fn detect(args: &[OsString]) -> Result<Lang, String> {
+        let mut args = args.iter().skip(1).map(|a| a.to_str().unwrap_or("")).take_while(|&a| a != "--");
+        let mut requested = None;
+        while let Some(arg) = args.next() {
+            if arg == "--lang" {
+                requested = args.next();
+            } else if let Some(value) = arg.strip_prefix("--lang=") {
+                requested = Some(value);
+            }
+        }
+        match requested.map(str::parse).transpose()? {
+            Some(Lang::Ja) if !cfg!(feature = "ja") =>
+                Err("--lang ja requires building with the `ja` feature".to_owned()),
+            Some(lang) => Ok(lang),
+            None if !cfg!(feature = "ja") => Ok(Lang::En),
+            None => Ok(["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+                .filter_map(|name| env::var(name).ok())
+                .find(|locale| !locale.is_empty())
+                .map_or(Lang::En, |locale| from_locale(&locale))),
+        }
+    }
+
+    /// Translates the phrases of `text` that have a translation
+    pub fn translate(self, text: &str) -> String {
+        match self {
+            Lang::En => text.to_owned(),
+            #[cfg(feature = "ja")]
+            Lang::Ja => ja::translate(text),
+            #[cfg(not(feature = "ja"))]
+            Lang::Ja => text.to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "ja")]
+mod ja;
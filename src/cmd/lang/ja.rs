@@ -0,0 +1,200 @@
+//! Japanese messages
+
+/// English phrases and their translations; longer phrases are replaced first
+const PHRASES: &[(&str, &str)] = &[
+    // clap
+    ("USAGE:", "使い方:"),
+    ("FLAGS:", "フラグ:"),
+    ("OPTIONS:", "オプション:"),
+    ("ARGS:", "引数:"),
+    ("SUBCOMMANDS:", "サブコマンド:"),
+    ("Prints help information", "ヘルプを表示します"),
+    ("Prints version information", "バージョンを表示します"),
+    ("Prints this message or the help of the given subcommand(s)", "このヘルプ、または指定したサブコマンドのヘルプを表示します"),
+    ("[default: ", "[既定値: "),
+    ("[possible values: ", "[指定可能な値: "),
+    ("error: ", "エラー: "),
+    ("Found argument ", "引数 "),
+    (" which wasn't expected, or isn't valid in this context", " は想定外か、ここでは使えません"),
+    ("For more information try ", "詳しくは次を実行してください: "),
+    ("Invalid value for ", "値が不正です: "),
+    ("The following required arguments were not provided:", "次の必須引数が指定されていません:"),
+    ("The argument ", "引数 "),
+    (" cannot be used with ", " は次と同時に指定できません: "),
+    (" requires a value but none was supplied", " には値が必要です"),
+    (" was provided more than once, but cannot be used multiple times", " は一度しか指定できません"),
+    ("isn't a valid value for", "は次の値として不正です:"),
+
+    // magdecode
+    ("MAG image decoder", "MAG 画像デコーダ"),
+    ("Without a subcommand, the arguments are those of `decode`.", "サブコマンドを省略すると、引数は `decode` のものとして扱われます。"),
+    ("Verbose mode (-v, -vv, -vvv, etc.), RUST_LOG overrides per module",
+     "詳細表示 (-v, -vv, -vvv など)。モジュールごとの指定は RUST_LOG が優先されます"),
+    ("Log format on stderr: text, or jsonl (adds one JSON record per processed file)",
+     "標準エラー出力のログ形式: text、または jsonl (処理したファイルごとに JSON レコードを 1 行追加)"),
+    ("Language of help and error messages: en, or ja (`ja` feature); defaults to the locale",
+     "ヘルプとエラーメッセージの言語: en、または ja (`ja` フィーチャ)。既定値はロケールに従います"),
+
+    // input and parsing
+    ("Also process the files listed in LIST, one per line ('-' reads the list from stdin)",
+     "LIST に 1 行ずつ列挙されたファイルも処理します ('-' で標準入力から読み込み)"),
+    ("With --files-from, entries are NUL-terminated (as from `find -print0`)",
+     "--files-from のエントリを NUL 区切りとして読みます (`find -print0` の出力など)"),
+    ("Process directories given as FILE recursively", "FILE に指定したディレクトリを再帰的に処理します"),
+    ("Files to process; .lzh and .zip archives are searched for MAG entries ('-' reads stdin)",
+     "処理するファイル。.lzh と .zip アーカイブは中の MAG ファイルを探します ('-' で標準入力)"),
+    ("Recover from damaged files where possible, with warnings", "壊れたファイルも可能な範囲で警告付きで復元します"),
+    ("Try UTF-8 and EUC-JP for user names and memos that are not valid Shift_JIS",
+     "Shift_JIS として不正なユーザー名とメモを UTF-8 と EUC-JP でも試します"),
+    ("Reject images larger than WxH pixels", "WxH ピクセルより大きい画像を拒否します"),
+    ("Reject files needing more than BYTES of memory to decode", "デコードに BYTES を超えるメモリが必要なファイルを拒否します"),
+    ("Abort decoding a file after SECONDS of wall-clock time", "1 ファイルのデコードが SECONDS 秒を超えたら中断します"),
+
+    // decode
+    ("Converts MAG files to PNG or another format (the default subcommand)",
+     "MAG ファイルを PNG などの形式に変換します (既定のサブコマンド)"),
+    ("Specify the output directory", "出力ディレクトリを指定します"),
+    ("Output image format: png, qoi, ppm, farbfeld, tga, tiff, aseprite, ico (avif when built with the `avif` feature)",
+     "出力形式: png, qoi, ppm, farbfeld, tga, tiff, aseprite, ico (`avif` フィーチャ付きのビルドでは avif も)"),
+    ("Name output files after TEMPLATE, where {stem} is the input file name without its extension, {name} the \
+      whole input file name and {ext} the extension of the output format",
+     "出力ファイル名を TEMPLATE で決めます。{stem} は拡張子を除いた入力ファイル名、{name} は入力ファイル名全体、\
+      {ext} は出力形式の拡張子に置き換わります"),
+    ("Convert up to JOBS files at a time", "最大 JOBS 個のファイルを同時に変換します"),
+    ("Embed the ICC profile file PROFILE in PNG output instead of tagging it as sRGB",
+     "PNG を sRGB とする代わりに ICC プロファイル PROFILE を埋め込みます"),
+    ("Decode with the palette from PALETTE instead of the embedded one: a MAG file, JASC-PAL, GIMP palette or \
+      Adobe Color Table; entries past its end keep the embedded colors",
+     "埋め込みのパレットの代わりに PALETTE (MAG ファイル、JASC-PAL、GIMP パレット、Adobe Color Table) の\
+      パレットでデコードします。足りない分は埋め込みの色のままです"),
+    ("Upscale with a pixel-art-aware algorithm: scale2x, scale3x, hq2x",
+     "ドット絵向けのアルゴリズムで拡大します: scale2x, scale3x, hq2x"),
+    ("Write an indexed PNG keeping the original palette indices and metadata (see `encode`), a color-mapped TGA \
+      with `--format tga`, a palette TIFF with `--format tiff`, or an indexed sprite with `--format aseprite`",
+     "元のパレット番号とメタデータを保ったインデックスカラー PNG (`encode` 参照) を出力します。\
+      `--format tga` ではカラーマップ TGA、`--format tiff` ではパレット TIFF、`--format aseprite` では\
+      インデックスカラーのスプライトになります"),
+    ("Apply a CRT effect (scanlines, phosphor blur, aperture grille) after decoding",
+     "デコード後に CRT 風の効果 (走査線、蛍光体のにじみ、アパーチャグリル) をかけます"),
+    ("Double 200-line images with scanlines of BRIGHTNESS (0 for black to 1) instead of repeated rows",
+     "200 ライン画像の行を繰り返す代わりに、明るさ BRIGHTNESS (0 で黒、1 まで) の走査線を挟みます"),
+    ("Resize the output to WxH (after --upscale and --crt)", "出力を WxH に拡大縮小します (--upscale と --crt の後)"),
+    ("How --resize treats the aspect ratio: fit (within WxH) or fill (cover WxH, cropping the center)",
+     "--resize の縦横比の扱い: fit (WxH に収める) または fill (WxH を覆い、中央を切り出す)"),
+    ("Resampling filter for --resize: nearest, triangle, catmull-rom, gaussian, lanczos3",
+     "--resize のリサンプリングフィルタ: nearest, triangle, catmull-rom, gaussian, lanczos3"),
+    ("Rotate the output clockwise by DEGREES (0, 90, 180 or 270)", "出力を時計回りに DEGREES 度 (0, 90, 180, 270) 回転します"),
+    ("Mirror the output left to right (after rotating)", "出力を左右反転します (回転の後)"),
+    ("Mirror the output top to bottom (after rotating)", "出力を上下反転します (回転の後)"),
+    ("Place the decoded image on the system clipboard instead of writing a file (`clipboard` feature)",
+     "ファイルに書く代わりにクリップボードに画像を置きます (`clipboard` フィーチャ)"),
+    ("When inputs map to the same output file: error (before converting anything), suffix (NAME-1.EXT, ...), or \
+      overwrite",
+     "出力ファイル名が重なるとき: error (変換前にエラー)、suffix (NAME-1.EXT, ...)、または overwrite (上書き)"),
+    ("Copy the modification time of each input file onto its output", "入力ファイルの更新日時を出力にコピーします"),
+    ("Also write unrecognized data appended after the image to <output>.appended",
+     "画像の後ろに付加された不明なデータも <output>.appended に書き出します"),
+    ("Deprecated: use `scan`, which this runs on the inputs instead of converting them",
+     "非推奨: `scan` を使ってください。変換する代わりに入力に対して `scan` を実行します"),
+    ("Print decode/encode time, sizes and throughput per file and in total to stderr",
+     "ファイルごとと合計のデコード・エンコード時間、サイズ、処理速度を標準エラー出力に表示します"),
+    ("Turn off --recursive (e.g. set in magdecode.toml)", "--recursive を無効にします (magdecode.toml での指定など)"),
+    ("Turn off --lenient (e.g. set in magdecode.toml)", "--lenient を無効にします (magdecode.toml での指定など)"),
+    ("Turn off --detect-charset (e.g. set in magdecode.toml)", "--detect-charset を無効にします (magdecode.toml での指定など)"),
+    ("Turn off --indexed (e.g. set in magdecode.toml)", "--indexed を無効にします (magdecode.toml での指定など)"),
+    ("Turn off --crt (e.g. set in magdecode.toml)", "--crt を無効にします (magdecode.toml での指定など)"),
+    ("Turn off --flip-horizontal (e.g. set in magdecode.toml)", "--flip-horizontal を無効にします (magdecode.toml での指定など)"),
+    ("Turn off --flip-vertical (e.g. set in magdecode.toml)", "--flip-vertical を無効にします (magdecode.toml での指定など)"),
+    ("Turn off --preserve-times (e.g. set in magdecode.toml)", "--preserve-times を無効にします (magdecode.toml での指定など)"),
+    ("Turn off --timing (e.g. set in magdecode.toml)", "--timing を無効にします (magdecode.toml での指定など)"),
+
+    // other subcommands
+    ("Prints the full header, warnings and appended data of each file", "各ファイルのヘッダー全体、警告、付加データを表示します"),
+    ("Also print per-palette-entry pixel counts and unused entries", "パレットごとのピクセル数と未使用のパレットも表示します"),
+    ("Prints a one-line summary of each MAG file in the given files, directories and archives, without decoding",
+     "指定したファイル、ディレクトリ、アーカイブ内の各 MAG ファイルの概要をデコードせずに 1 行で表示します"),
+    ("Files, directories (searched recursively) and .lzh/.zip archives",
+     "ファイル、ディレクトリ (再帰的に検索)、.lzh/.zip アーカイブ"),
+    ("Decodes each file without writing output, reporting errors and warnings",
+     "各ファイルを出力せずにデコードし、エラーと警告を報告します"),
+    ("Diagnoses undecodable files: checks each part in order and reports offsets and suggested fixes",
+     "デコードできないファイルを診断します: 各部分を順に調べ、オフセットと修復方法を示します"),
+    ("Generates a static HTML gallery with thumbnails and metadata", "サムネイルとメタデータ付きの静的な HTML ギャラリーを生成します"),
+    ("The directory to scan for MAG files (recursively)", "MAG ファイルを (再帰的に) 探すディレクトリ"),
+    ("The output directory of the site", "サイトの出力ディレクトリ"),
+    ("Encodes an indexed PNG written with `--indexed` back to MAG",
+     "`--indexed` で書き出したインデックスカラー PNG を MAG に戻します"),
+    ("Input indexed PNG file", "入力するインデックスカラー PNG ファイル"),
+    ("Input MAG file", "入力 MAG ファイル"),
+    ("Output MAG file", "出力 MAG ファイル"),
+    ("Reports PSNR, SSIM and the maximum pixel difference of a decoded MAG file against a reference image",
+     "デコードした MAG ファイルと参照画像の PSNR、SSIM、最大画素差を報告します"),
+    ("MAG file to decode", "デコードする MAG ファイル"),
+    ("Reference image (PNG, BMP, ...) of the same size as the decoded output",
+     "デコード結果と同じサイズの参照画像 (PNG, BMP, ...)"),
+    ("Renders a false-color map of literal (red) and copied units, shaded from cyan (near) to blue (far source)",
+     "直値 (赤) とコピー (コピー元が近いほど水色、遠いほど青) の単位を色分けした画像を描きます"),
+    ("MAG file to analyze", "解析する MAG ファイル"),
+    ("Output image, in the format given by its extension", "出力画像 (形式は拡張子で決まります)"),
+    ("Rewrites palette indices and entries, producing a new MAG file", "パレット番号とパレットを書き換えた MAG ファイルを作ります"),
+    ("Index mapping, e.g. 3:5,5:3", "番号の対応 (例: 3:5,5:3)"),
+    ("Rewrite pixel indices only, keeping the palette", "パレットはそのままで、ピクセルの番号だけを書き換えます"),
+    ("Rewrite palette entries only, keeping the pixel indices", "ピクセルの番号はそのままで、パレットだけを書き換えます"),
+    ("Assembles MAG files into an animation, one frame per file: GIF (shared palette) or APNG",
+     "MAG ファイルを 1 ファイル 1 フレームのアニメーションにします: GIF (共通パレット) または APNG"),
+    ("Frames, in order; all must have the same size", "フレーム (順番どおり、すべて同じサイズ)"),
+    ("Output file; .gif for GIF, .png or .apng for APNG", "出力ファイル (.gif で GIF、.png か .apng で APNG)"),
+    ("Time each frame is shown, in milliseconds", "各フレームの表示時間 (ミリ秒)"),
+    ("Writes a slideshow of the images as a Y4M video stream, e.g. for `ffmpeg -i - out.mp4`",
+     "画像のスライドショーを Y4M 動画として書き出します (`ffmpeg -i - out.mp4` など向け)"),
+    ("Write the stream to OUTPUT instead of stdout", "標準出力の代わりに OUTPUT に書き出します"),
+    ("Frame size; images are fitted within it on black, keeping their aspect ratio",
+     "フレームサイズ (画像は縦横比を保って黒地の中に収めます)"),
+    ("Frames per second", "毎秒のフレーム数"),
+    ("How long each image is shown, in seconds", "各画像の表示時間 (秒)"),
+    ("Resampling filter for fitting: nearest, triangle, catmull-rom, gaussian, lanczos3",
+     "収めるときのリサンプリングフィルタ: nearest, triangle, catmull-rom, gaussian, lanczos3"),
+    ("Writes a catalog of metadata and SHA-256 hashes of the MAG files in a directory",
+     "ディレクトリ内の MAG ファイルのメタデータと SHA-256 ハッシュの一覧を書き出します"),
+    ("Write one CSV row per file to this path", "このパスに 1 ファイル 1 行の CSV を書き出します"),
+    ("Insert or update one row per file in this SQLite database (`sqlite` feature)",
+     "この SQLite データベースに 1 ファイル 1 行を追加・更新します (`sqlite` フィーチャ)"),
+    ("Prints groups of visually similar MAG files in a directory, by perceptual hash",
+     "ディレクトリ内の見た目の似た MAG ファイルを知覚ハッシュでグループにして表示します"),
+    ("Maximum Hamming distance (0-64) between hashes of similar images", "似た画像とみなすハッシュのハミング距離の上限 (0-64)"),
+    ("Prints the paths of MAG files in a directory whose header metadata matches",
+     "ヘッダーのメタデータが一致する MAG ファイルのパスを表示します"),
+    ("Only files whose user name contains AUTHOR", "ユーザー名に AUTHOR を含むファイルだけ"),
+    ("Only files with this machine code, e.g. PC98 (case-insensitive)", "この機種コード (例: PC98、大文字小文字を区別しない) のファイルだけ"),
+    ("Only files whose memo contains TEXT", "メモに TEXT を含むファイルだけ"),
+    ("Serves a read-only web UI and JSON API for the MAG files in a directory",
+     "ディレクトリ内の MAG ファイルを閲覧する Web UI と JSON API を提供します"),
+    ("The address to listen on (use 0.0.0.0 to allow other hosts)", "待ち受けるアドレス (他のホストからも受けるには 0.0.0.0)"),
+    ("The port to listen on", "待ち受けるポート"),
+
+    // errors
+    ("Error: ", "エラー: "),
+    ("No input file specified.", "入力ファイルが指定されていません。"),
+    ("--files-from - and '-' input both read stdin", "--files-from - と入力 '-' の両方が標準入力を読もうとしています"),
+    ("--clipboard takes a single input", "--clipboard に指定できる入力は 1 つだけです"),
+    ("--extract-appended cannot be used with stdin input", "--extract-appended は標準入力からの入力には使えません"),
+    ("expected WxH", "WxH の形式で指定してください"),
+    ("Invalid format: ", "形式が不正です: "),
+    ("Invalid geometry: ", "画像の範囲が不正です: "),
+    ("Degenerate image: ", "画素データのない画像です: "),
+    ("Limit exceeded: ", "制限を超えました: "),
+    ("Encoding error: ", "エンコードエラー: "),
+    ("Magic number mismatch", "マジックナンバーが MAG ではありません"),
+    ("header truncated", "ヘッダーが途中で切れています"),
+    ("pixel data exhausted at line ", "画素データが足りません: 行 "),
+    ("flag B data exhausted at line ", "フラグ B のデータが足りません: 行 "),
+    ("No such file or directory", "ファイルまたはディレクトリがありません"),
+    ("Permission denied", "アクセスが拒否されました"),
+];
+
+/// Replaces every phrase of `PHRASES` in `text`
+pub fn translate(text: &str) -> String {
+    let mut phrases: Vec<&(&str, &str)> = PHRASES.iter().collect();
+    phrases.sort_by_key(|(en, _)| std::cmp::Reverse(en.len()));
+    phrases.iter().fold(text.to_owned(), |text, (en, ja)| text.replace(en, ja))
+}
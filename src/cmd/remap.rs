@@ -0,0 +1,22 @@
+//! Rewrites palette indices of a MAG file
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use log::info;
+use mag_image_decoder::Encoder;
+use mag_image_decoder::remap::{PaletteMap, RemapTarget};
+
+use super::open_decoder;
+
+/// Decodes `input`, applies `map` and encodes the result to `output`
+pub fn run(input: &Path, output: &Path, map: &PaletteMap, target: RemapTarget) -> Result<(), String> {
+    let decoder = open_decoder(input)?;
+    let image = decoder.decode_indexed().map_err(|e| format!("'{}': {}", input.display(), e))?;
+    let image = map.apply(&image, target).map_err(|e| e.to_string())?;
+    info!("remap: '{}' -> '{}' ({:?})", input.display(), output.display(), target);
+    let w = BufWriter::new(File::create(output).map_err(|e| format!("'{}': {}", output.display(), e))?);
+    Encoder::new(w).encode(decoder.info(), &image)
+        .map_err(|e| format!("'{}': {}", output.display(), e))
+}
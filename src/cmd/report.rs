@@ -0,0 +1,133 @@
+//! Per-file reports and the JSON Lines log format
+
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::ops::AddAssign;
+use std::time::Duration;
+
+use log::Record;
+use mag_image_decoder::{ImageInfo, Warning};
+use serde_json::{json, Value};
+
+/// Format of log output on stderr
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line, plus one record per processed file
+    Jsonl,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "jsonl" => Ok(LogFormat::Jsonl),
+            _ => Err(format!("unsupported log format '{}' (expected one of: text, jsonl)", s)),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Text => "text",
+            LogFormat::Jsonl => "jsonl",
+        })
+    }
+}
+
+/// What processing one input produced, filled in as far as processing got
+#[derive(Default)]
+pub struct Processed {
+    pub info: Option<ImageInfo>,
+    pub warnings: Vec<Warning>,
+    pub output: Option<PathBuf>,
+    pub timing: Timing,
+}
+
+/// Time spent and bytes processed, for one file or in aggregate
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Timing {
+    /// Header parsing, decoding and post-processing
+    pub decode: Duration,
+    /// Encoding to the output format and writing it out
+    pub encode: Duration,
+    /// Input size; 0 for stdin
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+impl AddAssign for Timing {
+    fn add_assign(&mut self, other: Timing) {
+        self.decode += other.decode;
+        self.encode += other.encode;
+        self.input_bytes += other.input_bytes;
+        self.output_bytes += other.output_bytes;
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total = (self.decode + self.encode).as_secs_f64();
+        write!(f, "decode {:.2} ms, encode {:.2} ms, {} -> {} bytes", millis(self.decode), millis(self.encode),
+               self.input_bytes, self.output_bytes)?;
+        if total > 0.0 && self.input_bytes > 0 {
+            write!(f, ", {:.2} MB/s", self.input_bytes as f64 / total / 1_000_000.0)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn info_json(info: &ImageInfo) -> Value {
+    json!({
+        "machine_code": info.machine_code,
+        "user_name": info.user_name,
+        "memo": info.memo,
+        "x": info.x,
+        "y": info.y,
+        "end_x": info.end_x,
+        "end_y": info.end_y,
+        "width": info.width,
+        "height": info.height,
+        "num_colors": info.num_colors,
+        "is_200_line_mode": info.is_200_line_mode,
+        "text_encoding": info.text_encoding.to_string(),
+    })
+}
+
+/// Prints the record of one processed file to stderr
+pub fn print_file_record(input: &str, processed: &Processed, result: &Result<(), String>, elapsed: Duration) {
+    let record = json!({
+        "type": "file",
+        "path": input,
+        "status": if result.is_ok() { "ok" } else { "error" },
+        "error": result.as_ref().err(),
+        "output": processed.output.as_ref().map(|p| p.to_string_lossy()),
+        "info": processed.info.as_ref().map(info_json),
+        "warnings": processed.warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>(),
+        "elapsed_ms": millis(elapsed),
+    });
+    let stderr = io::stderr();
+    let _ = writeln!(stderr.lock(), "{}", record);
+}
+
+/// env_logger format writing log records as JSON lines
+pub fn format_log_record(buf: &mut env_logger::fmt::Formatter, record: &Record) -> io::Result<()> {
+    let line = json!({
+        "type": "log",
+        "time": buf.timestamp_millis().to_string(),
+        "level": record.level().to_string(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    });
+    writeln!(buf, "{}", line)
+}
@@ -0,0 +1,144 @@
+//! Read-only HTTP server for browsing a MAG archive
+
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{info, warn};
+use mag_image_decoder::{DecodeOptions, ImageInfo};
+use mag_image_decoder::output::OutputFormat;
+use serde_json::json;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use super::gallery::thumbnail;
+use super::html::{self, Card};
+use super::{find_mag_files, open_decoder_with};
+use super::report::info_json;
+
+/// Larger images are refused, so a single request cannot exhaust the server's memory
+const MAX_DIMENSION: u32 = 4096;
+/// Decodes taking longer than this are aborted
+const DECODE_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Entry {
+    path: PathBuf,
+    info: ImageInfo,
+}
+
+type Entries = BTreeMap<String, Entry>;
+
+fn decode_options() -> DecodeOptions {
+    DecodeOptions::new().max_dimensions(MAX_DIMENSION, MAX_DIMENSION).timeout(DECODE_TIMEOUT)
+}
+
+fn unescape_url(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+fn scan(dir: &Path) -> Result<Entries, String> {
+    let mut entries = BTreeMap::new();
+    for path in find_mag_files(dir)? {
+        let key = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+        match open_decoder_with(&path, decode_options()) {
+            Ok(decoder) => {
+                entries.insert(key, Entry { path, info: decoder.into_info() });
+            }
+            Err(e) => warn!("skipped {}", e),
+        }
+    }
+    Ok(entries)
+}
+
+fn render_index(title: &str, entries: &Entries) -> String {
+    let cards = entries.iter().map(|(key, entry)| Card { key, source: key, info: &entry.info });
+    html::render_index(title, "<p><a href=\"/api/files\">/api/files</a> (JSON)</p>\n", cards,
+                       |url| (format!("/image/{}", url), format!("/thumb/{}", url)))
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+fn respond(request: Request, status: u16, content_type: &str, body: Vec<u8>) {
+    let response = Response::from_data(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", content_type));
+    if let Err(e) = request.respond(response) {
+        warn!("failed to respond: {}", e);
+    }
+}
+
+fn respond_error(request: Request, status: u16, message: &str) {
+    respond(request, status, "text/plain; charset=utf-8", format!("{}\n", message).into_bytes());
+}
+
+fn png(entry: &Entry, thumb: bool) -> Result<Vec<u8>, String> {
+    let decoder = open_decoder_with(&entry.path, decode_options())?;
+    let img = decoder.decode().map_err(|e| format!("'{}': {}", entry.path.display(), e))?;
+    let img = if thumb { thumbnail(img) } else { img };
+    let mut buf = Cursor::new(Vec::new());
+    OutputFormat::Png.write(&img, &mut buf).map_err(|e| e.to_string())?;
+    Ok(buf.into_inner())
+}
+
+fn handle(request: Request, title: &str, entries: &Entries) {
+    if *request.method() != Method::Get && *request.method() != Method::Head {
+        return respond_error(request, 405, "method not allowed");
+    }
+    let url = request.url().split('?').next().unwrap_or("").to_owned();
+    info!("{} {}", request.method(), url);
+
+    if url == "/" {
+        return respond(request, 200, "text/html; charset=utf-8", render_index(title, entries).into_bytes());
+    }
+    if url == "/api/files" {
+        let files: Vec<_> = entries.iter()
+            .map(|(key, entry)| json!({ "path": key, "info": info_json(&entry.info) }))
+            .collect();
+        return respond(request, 200, "application/json", json!(files).to_string().into_bytes());
+    }
+
+    let (thumb, key) = if let Some(key) = url.strip_prefix("/image/") {
+        (false, key)
+    } else if let Some(key) = url.strip_prefix("/thumb/") {
+        (true, key)
+    } else {
+        return respond_error(request, 404, "not found");
+    };
+    match unescape_url(key).and_then(|key| entries.get(&key)) {
+        Some(entry) => match png(entry, thumb) {
+            Ok(body) => respond(request, 200, "image/png", body),
+            Err(e) => {
+                warn!("{}", e);
+                respond_error(request, 500, &e)
+            }
+        },
+        None => respond_error(request, 404, "not found"),
+    }
+}
+
+/// Serves the MAG files under `dir`, as scanned at startup, on `bind:port` until killed
+pub fn run(dir: &Path, bind: &str, port: u16) -> Result<(), String> {
+    let entries = scan(dir)?;
+    let title = dir.file_name().map_or_else(|| dir.display().to_string(), |s| s.to_string_lossy().into_owned());
+    let server = Server::http((bind, port)).map_err(|e| format!("{}:{}: {}", bind, port, e))?;
+    eprintln!("Serving {} images from '{}' on http://{}:{}/", entries.len(), dir.display(), bind, port);
+    for request in server.incoming_requests() {
+        handle(request, &title, &entries);
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Groups visually similar images by perceptual hash
+
+use std::path::{Path, PathBuf};
+
+use log::info;
+use mag_image_decoder::hash::{distance, phash};
+
+use super::{find_mag_files, open_decoder};
+
+fn hash_file(path: &Path) -> Result<u64, String> {
+    let decoder = open_decoder(path)?;
+    let img = decoder.decode().map_err(|e| format!("'{}': {}", path.display(), e))?;
+    Ok(phash(&img))
+}
+
+/// Disjoint sets with path halving
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Groups files whose hashes are within `threshold` bits of some other member, transitively
+fn clusters(hashes: &[(PathBuf, u64)], threshold: u32) -> Vec<Vec<usize>> {
+    let mut parents: Vec<usize> = (0..hashes.len()).collect();
+    for i in 0..hashes.len() {
+        for j in i + 1..hashes.len() {
+            if distance(hashes[i].1, hashes[j].1) <= threshold {
+                let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+                parents[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = vec![Vec::new(); hashes.len()];
+    for i in 0..hashes.len() {
+        let root = find(&mut parents, i);
+        groups[root].push(i);
+    }
+    groups.into_iter().filter(|g| g.len() > 1).collect()
+}
+
+/// Prints clusters of similar MAG files under `dir`, separated by blank lines
+pub fn run(dir: &Path, threshold: u32) -> Result<(), String> {
+    let mut hashes = Vec::new();
+    for path in find_mag_files(dir)? {
+        match hash_file(&path) {
+            Ok(hash) => hashes.push((path, hash)),
+            Err(e) => eprintln!("Warning: skipped {}", e),
+        }
+    }
+    info!("{} images hashed", hashes.len());
+
+    for (n, group) in clusters(&hashes, threshold).iter().enumerate() {
+        if n > 0 {
+            println!();
+        }
+        let (first, first_hash) = &hashes[group[0]];
+        println!("{}", first.display());
+        for &i in &group[1..] {
+            let (path, hash) = &hashes[i];
+            println!("{} (distance {})", path.display(), distance(*first_hash, *hash));
+        }
+    }
+    Ok(())
+}
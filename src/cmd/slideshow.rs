@@ -0,0 +1,68 @@
+//! Slideshow videos as a Y4M stream, for piping into ffmpeg
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use image::{GenericImage, RgbImage};
+use log::info;
+use mag_image_decoder::{DecodeOptions, Decoder};
+use mag_image_decoder::output::y4m::Y4mWriter;
+use mag_image_decoder::resize::{Resize, ResizeFilter, ResizeMode};
+
+use super::source::Item;
+
+/// Frame geometry and timing
+pub struct Slideshow {
+    /// The frame size; images are fitted within it, keeping their aspect ratio, on black
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    /// How long each image is shown, in seconds
+    pub duration: f64,
+    pub filter: ResizeFilter,
+}
+
+impl Slideshow {
+    /// `img` scaled to fit the frame and centered on black
+    fn frame(&self, img: &RgbImage) -> RgbImage {
+        let fitted = Resize { width: self.width, height: self.height, mode: ResizeMode::Fit, filter: self.filter }
+            .apply(img);
+        let mut frame = RgbImage::new(self.width, self.height);
+        frame.copy_from(&fitted, (self.width - fitted.width()) / 2, (self.height - fitted.height()) / 2);
+        frame
+    }
+}
+
+fn write_all<W: Write>(items: &[Item], slideshow: &Slideshow, w: W) -> Result<(), String> {
+    let mut writer = Y4mWriter::new(w, slideshow.width, slideshow.height, slideshow.fps).map_err(|e| e.to_string())?;
+    let frames_per_image = ((slideshow.duration * f64::from(slideshow.fps)).round() as u32).max(1);
+    for item in items {
+        let result = item.open().and_then(|(reader, _)| {
+            Decoder::with_options(reader, DecodeOptions::default())
+                .and_then(|decoder| decoder.decode())
+                .map_err(|e| format!("'{}': {}", item.name, e))
+        });
+        match result {
+            Ok(img) => {
+                info!("slideshow: '{}', {} frames", item.name, frames_per_image);
+                writer.write_frames(&slideshow.frame(&img), frames_per_image)
+                    .map_err(|e| format!("failed to write: {}", e))?;
+            }
+            Err(e) => eprintln!("Warning: skipped {}", e),
+        }
+    }
+    writer.into_inner().map_err(|e| format!("failed to write: {}", e))?;
+    Ok(())
+}
+
+/// Writes `items` as a Y4M slideshow to `output`, or stdout; files that cannot be decoded are skipped
+pub fn run(items: &[Item], slideshow: &Slideshow, output: Option<&Path>) -> Result<(), String> {
+    match output {
+        Some(path) => {
+            let file = File::create(path).map_err(|e| format!("'{}': {}", path.display(), e))?;
+            write_all(items, slideshow, BufWriter::new(file)).map_err(|e| format!("'{}': {}", path.display(), e))
+        }
+        None => write_all(items, slideshow, BufWriter::new(io::stdout().lock())),
+    }
+}
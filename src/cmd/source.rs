@@ -0,0 +1,152 @@
+//! Inputs as named MAG byte streams, from files, directories and archives
+//!
+//! Each input argument is opened by the first of `OPENERS` that recognizes it,
+//! as a `Container` listing its MAG files as `Item`s. The conversion pipeline
+//! only sees items, so a new kind of container (a disk image, say) is one more
+//! `Container` implementation and opener.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+
+use super::archive::{self, ArchiveKind};
+use super::find_mag_files;
+
+/// Path standing for stdin (and stdout)
+pub const STDIO_PATH: &str = "-";
+
+enum Data {
+    File(PathBuf),
+    Bytes(Vec<u8>),
+    Stdin,
+}
+
+/// A MAG file found in an input
+pub struct Item {
+    /// Where the item came from, for messages: the file path, or `archive:entry`
+    pub name: String,
+    /// The path the output is named after: the file path, or the entry's path in a
+    /// directory named after the archive (`-` for stdin)
+    pub path: PathBuf,
+    /// The file on disk the item was read from, if any
+    pub file: Option<PathBuf>,
+    data: Data,
+}
+
+impl Item {
+    /// A MAG file on disk
+    pub fn file(path: PathBuf) -> Item {
+        Item { name: path.display().to_string(), path: path.clone(), file: Some(path.clone()), data: Data::File(path) }
+    }
+
+    /// Whether the item is read from stdin
+    pub fn is_stdin(&self) -> bool {
+        matches!(self.data, Data::Stdin)
+    }
+
+    /// Opens the content, with its size when known in advance
+    pub fn open(&self) -> Result<(Box<dyn Read + '_>, Option<u64>), String> {
+        match &self.data {
+            Data::File(path) => {
+                let err = |e: io::Error| format!("'{}': {}", path.display(), e);
+                let file = File::open(path).map_err(err)?;
+                let size = file.metadata().map_err(err)?.len();
+                Ok((Box::new(BufReader::new(file)), Some(size)))
+            }
+            Data::Bytes(data) => Ok((Box::new(Cursor::new(data)), Some(data.len() as u64))),
+            Data::Stdin => Ok((Box::new(io::stdin().lock()), None)),
+        }
+    }
+}
+
+/// Something holding MAG files
+pub trait Container {
+    /// Lists the MAG files, in order
+    fn items(&self) -> Result<Vec<Item>, String>;
+}
+
+/// A single file, taken to be MAG whatever its extension
+struct SingleFile(PathBuf);
+
+impl Container for SingleFile {
+    fn items(&self) -> Result<Vec<Item>, String> {
+        Ok(vec![Item::file(self.0.clone())])
+    }
+}
+
+/// The `*.mag` files under a directory, recursively
+struct Directory(PathBuf);
+
+impl Container for Directory {
+    fn items(&self) -> Result<Vec<Item>, String> {
+        Ok(find_mag_files(&self.0)?.into_iter().map(Item::file).collect())
+    }
+}
+
+/// Stdin, as one MAG file
+struct Stdin;
+
+impl Container for Stdin {
+    fn items(&self) -> Result<Vec<Item>, String> {
+        Ok(vec![Item { name: "<stdin>".to_owned(), path: PathBuf::from(STDIO_PATH), file: None, data: Data::Stdin }])
+    }
+}
+
+/// The `*.mag` entries of an LZH or ZIP archive
+struct Archive(PathBuf, ArchiveKind);
+
+/// Joins the entry name to `dir`, dropping components that would leave it
+fn entry_path(dir: &Path, name: &str) -> PathBuf {
+    let mut path = dir.to_owned();
+    for component in Path::new(name).components() {
+        if let Component::Normal(c) = component {
+            path.push(c);
+        }
+    }
+    path
+}
+
+impl Container for Archive {
+    fn items(&self) -> Result<Vec<Item>, String> {
+        let Archive(path, kind) = self;
+        let dir = path.with_extension("");
+        Ok(archive::read_entries(path, *kind)?.into_iter()
+            .map(|entry| Item {
+                name: format!("{}:{}", path.display(), entry.name),
+                path: entry_path(&dir, &entry.name),
+                file: Some(path.clone()),
+                data: Data::Bytes(entry.data),
+            })
+            .collect())
+    }
+}
+
+/// Options affecting how inputs are opened
+pub struct SourceOptions {
+    /// Accept directories
+    pub recursive: bool,
+}
+
+/// Opens `path` as a container, or returns `None` if it is not of this kind
+type Opener = fn(&Path, &SourceOptions) -> Option<Result<Box<dyn Container>, String>>;
+
+/// Tried in order; the last accepts any path
+const OPENERS: &[Opener] = &[
+    |path, _| (path == Path::new(STDIO_PATH)).then(|| Ok(Box::new(Stdin) as Box<dyn Container>)),
+    |path, options| path.is_dir().then(|| {
+        if !options.recursive {
+            return Err(format!("'{}' is a directory (use --recursive)", path.display()));
+        }
+        Ok(Box::new(Directory(path.to_owned())) as Box<dyn Container>)
+    }),
+    |path, _| ArchiveKind::from_path(path).filter(|_| fs::metadata(path).is_ok_and(|m| m.is_file()))
+        .map(|kind| Ok(Box::new(Archive(path.to_owned(), kind)) as Box<dyn Container>)),
+    |path, _| Some(Ok(Box::new(SingleFile(path.to_owned())))),
+];
+
+/// Opens an input argument
+pub fn open(path: &Path, options: &SourceOptions) -> Result<Box<dyn Container>, String> {
+    OPENERS.iter()
+        .find_map(|opener| opener(path, options))
+        .expect("the last opener accepts any path")
+}
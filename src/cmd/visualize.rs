@@ -0,0 +1,30 @@
+//! Compression-structure heatmap, for `visualize`
+
+use std::path::Path;
+
+use image::{imageops, FilterType};
+use mag_image_decoder::output::OutputFormat;
+use mag_image_decoder::trace::{heatmap, Action};
+
+use super::open_decoder;
+
+/// Renders which parts of `file` are literals and copies to `output`, in the format
+/// given by its extension, and prints the share of literal units
+pub fn run(file: &Path, output: &Path) -> Result<(), String> {
+    let format = OutputFormat::from_path(output)
+        .ok_or_else(|| format!("'{}': unknown output format", output.display()))?;
+    let decoder = open_decoder(file)?;
+    let units = decoder.trace().map_err(|e| format!("'{}': {}", file.display(), e))?;
+    let info = decoder.info();
+    let (width, height) = (u32::from(info.width), u32::from(info.height));
+    let mut img = heatmap(&units, width, height);
+    if info.is_200_line_mode {
+        img = imageops::resize(&img, width, height * 2, FilterType::Nearest);
+    }
+    format.save(&img, output).map_err(|e| format!("'{}': failed to save: {}", output.display(), e))?;
+
+    let literals = units.iter().filter(|u| u.action == Action::Literal).count();
+    println!("{}: {} units, {:.1}% literal", file.display(), units.len(),
+             literals as f64 * 100.0 / units.len().max(1) as f64);
+    Ok(())
+}
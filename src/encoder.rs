@@ -0,0 +1,187 @@
+//! MAG encoder
+
+use std::io::Write;
+
+use byteorder::{LittleEndian as LE, WriteBytesExt};
+use encoding_rs::SHIFT_JIS;
+
+use crate::{COPY_VECTORS, HEADER_SIZE, ImageInfo, IndexedImage, MAGIC_NUMBER};
+use crate::error::*;
+
+/// The order in which copy vectors are tried; vertical copies first, since they are the most common
+const SEARCH_ORDER: [u8; 15] = [4, 1, 5, 6, 2, 7, 8, 3, 9, 10, 11, 12, 13, 14, 15];
+const USER_NAME_SIZE: usize = 18;
+
+/// MAG encoder
+pub struct Encoder<W: Write> {
+    w: W,
+}
+
+/// Encodes `s` as Shift_JIS, truncated to `max_len` bytes without splitting a character
+fn encode_text(s: &str, max_len: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    for c in s.chars() {
+        let mut tmp = [0u8; 4];
+        let (bytes, _, _) = SHIFT_JIS.encode(c.encode_utf8(&mut tmp));
+        if out.len() + bytes.len() > max_len {
+            break;
+        }
+        out.extend_from_slice(&bytes);
+    }
+    out
+}
+
+impl<W: Write> Encoder<W> {
+    /// Creates a new `Encoder` writing to `w`.
+    pub fn new(w: W) -> Encoder<W> {
+        Encoder { w }
+    }
+
+    /// Encodes `image` with the metadata in `info`.
+    ///
+    /// `info.num_colors` selects 16- or 256-color mode; `info.width` and `info.height` must match `image`,
+    /// and `info.end_x`/`info.end_y` must lie in the last pixel unit and row of the image.
+    pub fn encode(mut self, info: &ImageInfo, image: &IndexedImage) -> Result<()> {
+        let pixel_unit = match info.num_colors {
+            16 => 8,
+            256 => 4,
+            n => return Err(other_err(format!("unsupported number of colors: {}", n))),
+        };
+        if u32::from(info.width) != image.width || u32::from(info.height) != image.height {
+            return Err(other_err(format!("image size {}x{} does not match info {}x{}",
+                                         image.width, image.height, info.width, info.height)));
+        }
+        if image.width == 0 || image.height == 0 || !image.width.is_multiple_of(pixel_unit) {
+            return Err(other_err(format!("width must be a positive multiple of {}", pixel_unit)));
+        }
+        if image.pixels.len() != (image.width * image.height) as usize {
+            return Err(other_err("pixel count does not match the image size"));
+        }
+        if image.palette.len() > info.num_colors as usize {
+            return Err(other_err(format!("palette has more than {} entries", info.num_colors)));
+        }
+        if let Some(&index) = image.pixels.iter().find(|&&i| u16::from(i) >= info.num_colors) {
+            return Err(other_err(format!("palette index {} out of range", index)));
+        }
+        if info.memo.contains('\u{1a}') {
+            return Err(other_err("memo must not contain 0x1A"));
+        }
+
+        let (flag_a, flag_b, pixels) = encode_streams(image, pixel_unit as usize);
+
+        let mut palette = Vec::with_capacity(info.num_colors as usize * 3);
+        for i in 0..info.num_colors as usize {
+            let rgb = image.palette.get(i).map_or([0, 0, 0], |c| c.0);
+            palette.extend_from_slice(&[rgb[1], rgb[0], rgb[2]]);
+        }
+
+        let flag_a_offset = HEADER_SIZE + palette.len() as u32;
+        let flag_b_offset = flag_a_offset + flag_a.len() as u32;
+        let pixel_offset = flag_b_offset + flag_b.len() as u32;
+        let storage = info.storage_rect();
+        let storage_end_x = u32::from(storage.x) + image.width - 1;
+        if storage_end_x > u32::from(u16::MAX) || u32::from(info.y) + image.height - 1 > u32::from(u16::MAX) {
+            return Err(other_err("image exceeds the 16-bit coordinate space"));
+        }
+        let display = info.display_rect();
+        if info.end_x < info.x || u32::from(info.end_x) > storage_end_x
+            || u32::from(info.end_x) + pixel_unit <= storage_end_x
+            || u32::from(display.height) != image.height || info.end_y < info.y {
+            return Err(Error::InvalidGeometry {
+                reason: format!("does not match the {}x{} image", image.width, image.height),
+                x: info.x,
+                y: info.y,
+                end_x: info.end_x,
+                end_y: info.end_y,
+            });
+        }
+
+        let w = &mut self.w;
+        w.write_all(MAGIC_NUMBER)?;
+        let mut machine_code = encode_text(&info.machine_code, 4);
+        machine_code.resize(4, b' ');
+        w.write_all(&machine_code)?;
+        let mut user_name = encode_text(&info.user_name, USER_NAME_SIZE);
+        user_name.resize(USER_NAME_SIZE, b' ');
+        w.write_all(&user_name)?;
+        w.write_all(b" ")?;
+        w.write_all(&encode_text(&info.memo, usize::MAX))?;
+        w.write_u8(0x1a)?;
+
+        let screen_mode = if info.num_colors == 256 { 0x80 } else { 0 }
+            | if info.is_200_line_mode { 1 } else { 0 };
+        w.write_all(&[0, 0, 0, screen_mode])?;
+        w.write_u16::<LE>(info.x)?;
+        w.write_u16::<LE>(info.y)?;
+        w.write_u16::<LE>(info.end_x)?;
+        w.write_u16::<LE>(info.end_y)?;
+        w.write_u32::<LE>(flag_a_offset)?;
+        w.write_u32::<LE>(flag_b_offset)?;
+        w.write_u32::<LE>(flag_b.len() as u32)?;
+        w.write_u32::<LE>(pixel_offset)?;
+        w.write_u32::<LE>(pixels.len() as u32)?;
+        w.write_all(&palette)?;
+        w.write_all(&flag_a)?;
+        w.write_all(&flag_b)?;
+        w.write_all(&pixels)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+/// Builds the flag A, flag B and pixel streams
+pub(crate) fn encode_streams(image: &IndexedImage, pixel_unit: usize) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let width = image.width as usize;
+    let copy_pixels = pixel_unit / 2;
+    let num_x_units = width / pixel_unit;
+    let row = |y: usize| &image.pixels[y * width..(y + 1) * width];
+
+    let mut flag_a_bits = Vec::with_capacity(num_x_units * image.height as usize);
+    let mut flag_b = Vec::new();
+    let mut pixels = Vec::new();
+    let mut prev_flags = vec![0u8; num_x_units];
+
+    for y in 0..image.height as usize {
+        let current = row(y);
+        for (unit, prev_flag) in prev_flags.iter_mut().enumerate() {
+            let mut flag = 0;
+            for half in 0..2 {
+                let x = unit * pixel_unit + half * copy_pixels;
+                let target = &current[x..x + copy_pixels];
+                let prev_nibble = if half == 0 { *prev_flag >> 4 } else { *prev_flag & 0xf };
+                let matches = |n: u8| {
+                    let (dx, dy) = COPY_VECTORS[n as usize];
+                    match x.checked_sub(dx * copy_pixels) {
+                        Some(src_x) if dy <= y => &row(y - dy)[src_x..src_x + copy_pixels] == target,
+                        _ => false,
+                    }
+                };
+                let nibble = if prev_nibble != 0 && matches(prev_nibble) {
+                    prev_nibble
+                } else {
+                    SEARCH_ORDER.iter().cloned().find(|&n| matches(n)).unwrap_or(0)
+                };
+                if nibble == 0 {
+                    if copy_pixels == 4 {
+                        pixels.push(target[0] << 4 | target[1]);
+                        pixels.push(target[2] << 4 | target[3]);
+                    } else {
+                        pixels.extend_from_slice(target);
+                    }
+                }
+                flag = flag << 4 | nibble;
+            }
+            let diff = flag ^ *prev_flag;
+            flag_a_bits.push(diff != 0);
+            if diff != 0 {
+                flag_b.push(diff);
+            }
+            *prev_flag = flag;
+        }
+    }
+
+    let flag_a = flag_a_bits.chunks(8)
+        .map(|bits| bits.iter().enumerate().fold(0u8, |b, (i, &bit)| b | (bit as u8) << (7 - i)))
+        .collect();
+    (flag_a, flag_b, pixels)
+}
@@ -1,4 +1,7 @@
+#![allow(non_local_definitions)]
+
 use std::io;
+use std::time::Duration;
 use failure::Fail;
 
 /// Library error
@@ -8,6 +11,32 @@ pub enum Error {
     #[fail(display = "Invalid format: {}", _0)]
     InvalidFormat(String),
 
+    /// Inconsistent or out-of-range image geometry in the header
+    #[fail(display = "Invalid geometry: {} (x: {}, y: {}, end_x: {}, end_y: {})", reason, x, y, end_x, end_y)]
+    InvalidGeometry {
+        reason: String,
+        x: u16,
+        y: u16,
+        end_x: u16,
+        end_y: u16,
+    },
+
+    /// The header describes an image without pixel data, such as one with an empty flag A or pixel section
+    #[fail(display = "Degenerate image: {}", _0)]
+    DegenerateImage(String),
+
+    /// A limit set in `DecodeOptions` was exceeded
+    #[fail(display = "Limit exceeded: {}", _0)]
+    LimitExceeded(String),
+
+    /// Decoding took longer than the timeout set in `DecodeOptions`
+    #[fail(display = "Timed out after {:?}", _0)]
+    TimedOut(Duration),
+
+    /// Error while encoding an output image
+    #[fail(display = "Encoding error: {}", _0)]
+    EncodingError(String),
+
     /// Other error
     #[fail(display = "Error: {}", _0)]
     OtherError(String),
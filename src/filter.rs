@@ -0,0 +1,47 @@
+//! Post-processing filters applied to decoded images
+
+use image::{ImageBuffer, Rgb, RgbImage};
+
+/// CRT emulation: horizontal phosphor blur, scanlines and an aperture grille mask
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrtFilter {
+    /// Weight of each horizontal neighbour, 0.0 (sharp) to 0.5
+    pub blur: f32,
+    /// Brightness of odd rows, 0.0 (black) to 1.0 (no scanlines)
+    pub scanline_brightness: f32,
+    /// Brightness of the masked subpixel channels, 0.0 to 1.0 (no mask)
+    pub grille_brightness: f32,
+}
+
+impl Default for CrtFilter {
+    fn default() -> Self {
+        CrtFilter {
+            blur: 0.15,
+            scanline_brightness: 0.6,
+            grille_brightness: 0.8,
+        }
+    }
+}
+
+impl CrtFilter {
+    /// Applies the filter, returning a new image of the same size
+    pub fn apply(&self, img: &RgbImage) -> RgbImage {
+        let (width, height) = img.dimensions();
+        let blur = self.blur.clamp(0.0, 0.5);
+        ImageBuffer::from_fn(width, height, |x, y| {
+            let left = img[(x.saturating_sub(1), y)];
+            let center = img[(x, y)];
+            let right = img[((x + 1).min(width - 1), y)];
+            let row = if y % 2 == 1 { self.scanline_brightness } else { 1.0 };
+
+            let mut out = [0u8; 3];
+            for (c, v) in out.iter_mut().enumerate() {
+                let blurred = f32::from(center[c]) * (1.0 - 2.0 * blur)
+                    + (f32::from(left[c]) + f32::from(right[c])) * blur;
+                let grille = if x % 3 == c as u32 { 1.0 } else { self.grille_brightness };
+                *v = (blurred * row * grille).round().clamp(0.0, 255.0) as u8;
+            }
+            Rgb(out)
+        })
+    }
+}
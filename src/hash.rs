@@ -0,0 +1,77 @@
+//! Perceptual hashes for finding near-duplicate images
+//!
+//! Unlike a file hash, these survive re-saves, small palette tweaks and resizing:
+//! similar images have hashes a small Hamming distance apart.
+//!
+//! # Examples
+//! ```no_run
+//! use std::fs::File;
+//! use mag_image_decoder::Decoder;
+//! use mag_image_decoder::hash::{distance, phash};
+//!
+//! let decode = |path| Decoder::new(File::open(path).unwrap()).unwrap().decode().unwrap();
+//! let a = phash(&decode("A.MAG"));
+//! let b = phash(&decode("B.MAG"));
+//! if distance(a, b) <= 10 {
+//!     println!("A.MAG and B.MAG look alike");
+//! }
+//! ```
+
+use image::{FilterType, GrayImage, imageops, RgbImage};
+
+/// Side of the grayscale image the DCT is computed on
+const PHASH_SIZE: u32 = 32;
+/// Side of the low-frequency block kept from the DCT
+const PHASH_LOW: usize = 8;
+
+fn grayscale(img: &RgbImage, width: u32, height: u32) -> GrayImage {
+    imageops::resize(&imageops::grayscale(img), width, height, FilterType::Triangle)
+}
+
+/// Difference hash: whether each pixel of a 9x8 thumbnail is brighter than its right neighbor
+pub fn dhash(img: &RgbImage) -> u64 {
+    let small = grayscale(img, 9, 8);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+            hash = hash << 1 | bit as u64;
+        }
+    }
+    hash
+}
+
+/// DCT-based hash: whether each low-frequency coefficient of a 32x32 thumbnail is above the median
+pub fn phash(img: &RgbImage) -> u64 {
+    let n = PHASH_SIZE as usize;
+    let small = grayscale(img, PHASH_SIZE, PHASH_SIZE);
+    let pixels: Vec<f64> = small.pixels().map(|p| f64::from(p[0])).collect();
+
+    // cos_table[u * n + x] = cos((2x + 1) u pi / 2n), for the low frequencies only
+    let cos_table: Vec<f64> = (0..PHASH_LOW)
+        .flat_map(|u| (0..n).map(move |x| ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * n) as f64).cos()))
+        .collect();
+    let mut rows = vec![0.0; n * PHASH_LOW];
+    for y in 0..n {
+        for u in 0..PHASH_LOW {
+            rows[y * PHASH_LOW + u] = (0..n).map(|x| pixels[y * n + x] * cos_table[u * n + x]).sum();
+        }
+    }
+    let mut coefficients = Vec::with_capacity(PHASH_LOW * PHASH_LOW);
+    for v in 0..PHASH_LOW {
+        for u in 0..PHASH_LOW {
+            coefficients.push((0..n).map(|y| rows[y * PHASH_LOW + u] * cos_table[v * n + y]).sum::<f64>());
+        }
+    }
+
+    // The DC term only reflects overall brightness
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = sorted[sorted.len() / 2];
+    coefficients.iter().fold(0u64, |hash, &c| hash << 1 | (c > median) as u64)
+}
+
+/// Number of differing bits between two hashes
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
@@ -0,0 +1,37 @@
+//! Palette-indexed images
+
+use image::{ImageBuffer, Rgb, RgbImage};
+
+/// An image of palette indices, as stored in the MAG file
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedImage {
+    /// The width, in pixels
+    pub width: u32,
+    /// The height, in pixels (200-line images are not doubled)
+    pub height: u32,
+    /// Palette indices in row-major order
+    pub pixels: Vec<u8>,
+    /// The palette
+    pub palette: Vec<Rgb<u8>>,
+}
+
+impl IndexedImage {
+    /// Gets the palette index at (x, y)
+    pub fn index(&self, x: u32, y: u32) -> u8 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Repeats every row, as `Decoder::decode` does for 200-line images
+    pub fn double_rows(&self) -> IndexedImage {
+        let pixels = self.pixels.chunks(self.width as usize)
+            .flat_map(|row| row.iter().chain(row))
+            .cloned()
+            .collect();
+        IndexedImage { width: self.width, height: self.height * 2, pixels, palette: self.palette.clone() }
+    }
+
+    /// Converts to RGB through the palette
+    pub fn to_rgb(&self) -> RgbImage {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| self.palette[self.index(x, y) as usize])
+    }
+}
@@ -0,0 +1,129 @@
+//! Lossless MAG ↔ indexed PNG conversion
+//!
+//! `write_indexed_png` stores the palette indices unchanged in an 8-bit indexed PNG,
+//! with the palette in `PLTE` and the metadata in `iTXt` chunks (keywords `MAG:*`).
+//! `read_indexed_png` restores the `ImageInfo` and `IndexedImage`, so that
+//! MAG → indexed PNG → MAG reproduces the original indices, palette and metadata exactly.
+//! Only the compressed streams may differ from the original file.
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::{BufReader, BufWriter};
+//! use mag_image_decoder::{Decoder, Encoder};
+//! use mag_image_decoder::indexed_png::{read_indexed_png, write_indexed_png};
+//!
+//! let decoder = Decoder::new(BufReader::new(File::open("SAMPLE.MAG").unwrap())).unwrap();
+//! let image = decoder.decode_indexed().unwrap();
+//! write_indexed_png(decoder.info(), &image, BufWriter::new(File::create("SAMPLE.png").unwrap())).unwrap();
+//!
+//! let (info, image) = read_indexed_png(BufReader::new(File::open("SAMPLE.png").unwrap())).unwrap();
+//! Encoder::new(BufWriter::new(File::create("RESTORED.MAG").unwrap())).encode(&info, &image).unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use image::Rgb;
+use png::{BitDepth, ColorType, Transformations};
+
+use crate::{ImageInfo, ImageInfoBuilder, IndexedImage};
+use crate::error::*;
+use crate::output::png::{ColorProfile, encoding_err};
+
+const KEY_MACHINE_CODE: &str = "MAG:machine_code";
+const KEY_USER_NAME: &str = "MAG:user_name";
+const KEY_MEMO: &str = "MAG:memo";
+const KEY_X: &str = "MAG:x";
+const KEY_Y: &str = "MAG:y";
+const KEY_END_X: &str = "MAG:end_x";
+const KEY_END_Y: &str = "MAG:end_y";
+const KEY_NUM_COLORS: &str = "MAG:num_colors";
+const KEY_200_LINE_MODE: &str = "MAG:200_line_mode";
+
+/// Writes `image` as an 8-bit indexed PNG tagged as sRGB, with `info` in text chunks
+pub fn write_indexed_png<W: Write>(info: &ImageInfo, image: &IndexedImage, w: W) -> Result<()> {
+    let mut encoder = ColorProfile::Srgb.png_encoder(w, image.width, image.height)?;
+    encoder.set_color(ColorType::Indexed);
+    encoder.set_depth(BitDepth::Eight);
+    let palette: Vec<u8> = image.palette.iter().flat_map(|c| c.0.iter().cloned()).collect();
+    encoder.set_palette(palette);
+    let texts = [
+        (KEY_MACHINE_CODE, info.machine_code.clone()),
+        (KEY_USER_NAME, info.user_name.clone()),
+        (KEY_MEMO, info.memo.clone()),
+        (KEY_X, info.x.to_string()),
+        (KEY_Y, info.y.to_string()),
+        (KEY_END_X, info.end_x.to_string()),
+        (KEY_END_Y, info.end_y.to_string()),
+        (KEY_NUM_COLORS, info.num_colors.to_string()),
+        (KEY_200_LINE_MODE, info.is_200_line_mode.to_string()),
+    ];
+    for (key, text) in texts.iter() {
+        encoder.add_itxt_chunk(key.to_string(), text.clone()).map_err(encoding_err)?;
+    }
+
+    let mut writer = encoder.write_header().map_err(encoding_err)?;
+    writer.write_image_data(&image.pixels).map_err(encoding_err)?;
+    writer.finish().map_err(encoding_err)
+}
+
+/// Reads an indexed PNG written by `write_indexed_png`
+pub fn read_indexed_png<R: Read>(r: R) -> Result<(ImageInfo, IndexedImage)> {
+    let decoding_err = |e: png::DecodingError| Error::InvalidFormat(e.to_string());
+
+    let mut decoder = png::Decoder::new(r);
+    decoder.set_transformations(Transformations::IDENTITY);
+    let mut reader = decoder.read_info().map_err(decoding_err)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf).map_err(decoding_err)?;
+    if frame.color_type != ColorType::Indexed {
+        return Err(Error::InvalidFormat(format!("not an indexed PNG: {:?}", frame.color_type)));
+    }
+
+    let width = frame.width;
+    let height = frame.height;
+    let pixels = unpack(&buf, width, height, frame.bit_depth as u8, frame.line_size);
+
+    let png_info = reader.info();
+    let palette: Vec<Rgb<u8>> = png_info.palette.as_ref()
+        .ok_or_else(|| Error::InvalidFormat("PLTE chunk missing".into()))?
+        .chunks(3)
+        .map(|c| Rgb([c[0], c[1], c[2]]))
+        .collect();
+    let mut texts = HashMap::new();
+    for chunk in &png_info.utf8_text {
+        texts.insert(chunk.keyword.clone(), chunk.get_text().map_err(decoding_err)?);
+    }
+    let text = |key: &str| texts.get(key).cloned().unwrap_or_default();
+    let number = |key: &str, default: u16| text(key).parse::<u16>().unwrap_or(default);
+
+    let num_colors = number(KEY_NUM_COLORS, if palette.len() <= 16 { 16 } else { 256 });
+    let x = number(KEY_X, 0);
+    let y = number(KEY_Y, 0);
+    let unit = if num_colors == 256 { 4 } else { 8 };
+    let end_x = number(KEY_END_X, (x / unit * unit).saturating_add(width as u16).saturating_sub(1));
+    let end_y = number(KEY_END_Y, y.saturating_add(height as u16).saturating_sub(1));
+    let info = ImageInfoBuilder::new(num_colors)
+        .machine_code(text(KEY_MACHINE_CODE))
+        .user_name(text(KEY_USER_NAME))
+        .memo(text(KEY_MEMO))
+        .rect(x, y, end_x, end_y)
+        .is_200_line_mode(text(KEY_200_LINE_MODE) == "true")
+        .build()?;
+    Ok((info, IndexedImage { width, height, pixels, palette }))
+}
+
+/// Expands rows of 1, 2, 4 or 8-bit indices to one byte per pixel
+fn unpack(buf: &[u8], width: u32, height: u32, bit_depth: u8, line_size: usize) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    let per_byte = 8 / bit_depth as u32;
+    let mask = ((1u16 << bit_depth) - 1) as u8;
+    for line in buf.chunks(line_size).take(height as usize) {
+        for x in 0..width {
+            let byte = line[(x / per_byte) as usize];
+            let shift = 8 - bit_depth as u32 * (x % per_byte + 1);
+            pixels.push((byte >> shift) & mask);
+        }
+    }
+    pixels
+}
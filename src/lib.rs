@@ -15,25 +15,94 @@
 //! let img = decoder.decode().unwrap();
 //! img.save("SAMPLE.png").unwrap();
 //! ```
+//!
+//! Converting a file in one call, with the output format chosen by extension:
+//! ```no_run
+//! use mag_image_decoder::DecodeOptions;
+//!
+//! mag_image_decoder::convert_file("SAMPLE.MAG", "SAMPLE.qoi", &DecodeOptions::default()).unwrap();
+//! ```
+//!
+//! Reading only the metadata:
+//! ```no_run
+//! let memo = mag_image_decoder::read_memo("SAMPLE.MAG").unwrap();
+//! println!("{}", memo);
+//! ```
+//!
+//! Telling MAG files apart from others by their first bytes:
+//! ```no_run
+//! let head = std::fs::read("UNKNOWN.BIN").unwrap();
+//! if let Some(kind) = mag_image_decoder::sniff(&head) {
+//!     println!("{}", kind.name());
+//! }
+//! ```
 
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::ops::Range;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use bit_vec::BitVec;
 use byteorder::{LittleEndian as LE, ReadBytesExt};
-use encoding_rs::*;
-use image::{FilterType, ImageBuffer, imageops, Rgb, RgbImage};
-use log::debug;
+use image::{GenericImage, Rgb, RgbImage};
+use log::{debug, warn};
 
-pub use crate::error::*;
+use crate::output::OutputFormat;
+use crate::trace::{Action, Unit};
 
+pub use crate::analysis::{ColorUsage, Histogram};
+pub use crate::builder::ImageInfoBuilder;
+pub use crate::charset::TextEncoding;
+pub use crate::encoder::Encoder;
+pub use crate::error::*;
+pub use crate::indexed::IndexedImage;
+pub use crate::mag_file::MagFile;
+pub use crate::options::{DecodeOptions, LineDoubling};
+pub use crate::palette::{ColorDistance, Palette};
+pub use crate::retro::RetroImage;
+pub use crate::sniff::{sniff, FormatKind};
+pub use crate::transform::{Rotation, Transform};
+pub use crate::trailer::{TrailerBlock, TrailerKind};
+pub use crate::warning::{TextField, Warning};
+
+pub mod analysis;
+pub mod builder;
+pub mod charset;
+pub mod animation;
+pub mod encoder;
 pub mod error;
+pub mod filter;
+pub mod hash;
+pub mod indexed;
+pub mod indexed_png;
+pub mod mag_file;
+pub mod options;
+pub mod output;
+pub mod palette;
+pub mod palette_file;
+pub mod remap;
+pub mod resize;
+pub mod retro;
+pub mod scale;
+pub mod sniff;
+#[cfg(feature = "dev-tools")]
+pub mod synth;
+pub mod trace;
+pub mod trailer;
+pub mod transform;
+pub mod warning;
 
 /// Represents metadata of an image.
+///
+/// Fields may be added in minor releases; build one with `ImageInfo::builder`.
 #[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub struct ImageInfo {
     /// The machine name (max 4 characters).
     /// e.g. PC98, PC88, ESEQ, X68K, MSX2
+    ///
+    /// Decoded as Shift_JIS; undecodable bytes become U+FFFD.
     pub machine_code: String,
     /// The author's name
     pub user_name: String,
@@ -43,7 +112,11 @@ pub struct ImageInfo {
     pub x: u16,
     /// The y position
     pub y: u16,
-    /// The width of the image, in pixels
+    /// The x position of the right edge (inclusive), as stored in the header
+    pub end_x: u16,
+    /// The y position of the bottom edge (inclusive), as stored in the header
+    pub end_y: u16,
+    /// The width of the image, in pixels (aligned to the pixel unit)
     pub width: u16,
     /// The height of the image, in pixels
     pub height: u16,
@@ -51,6 +124,111 @@ pub struct ImageInfo {
     pub num_colors: u16,
     /// The rectangular pixel aspect ratio flag
     pub is_200_line_mode: bool,
+    /// The encoding the user name and memo were decoded with
+    pub text_encoding: TextEncoding,
+}
+
+/// A rectangle in screen coordinates
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    /// The left edge
+    pub x: u16,
+    /// The top edge
+    pub y: u16,
+    /// The width, in pixels
+    pub width: u16,
+    /// The height, in pixels
+    pub height: u16,
+}
+
+impl ImageInfo {
+    /// Starts building an `ImageInfo` with `num_colors` (16 or 256)
+    pub fn builder(num_colors: u16) -> ImageInfoBuilder {
+        ImageInfoBuilder::new(num_colors)
+    }
+
+    /// The machine name
+    pub fn machine_code(&self) -> &str {
+        &self.machine_code
+    }
+
+    /// The author's name
+    pub fn user_name(&self) -> &str {
+        &self.user_name
+    }
+
+    /// The author's memo
+    pub fn memo(&self) -> &str {
+        &self.memo
+    }
+
+    /// The x position
+    pub fn x(&self) -> u16 {
+        self.x
+    }
+
+    /// The y position
+    pub fn y(&self) -> u16 {
+        self.y
+    }
+
+    /// The x position of the right edge (inclusive), as stored in the header
+    pub fn end_x(&self) -> u16 {
+        self.end_x
+    }
+
+    /// The y position of the bottom edge (inclusive), as stored in the header
+    pub fn end_y(&self) -> u16 {
+        self.end_y
+    }
+
+    /// The width of the image, in pixels (aligned to the pixel unit)
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// The height of the image, in pixels
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// The number of colors, 16 or 256
+    pub fn num_colors(&self) -> u16 {
+        self.num_colors
+    }
+
+    /// The rectangular pixel aspect ratio flag
+    pub fn is_200_line_mode(&self) -> bool {
+        self.is_200_line_mode
+    }
+
+    /// The encoding the user name and memo were decoded with
+    pub fn text_encoding(&self) -> TextEncoding {
+        self.text_encoding
+    }
+
+    /// The pixel unit width stored data is aligned to: 8 pixels in 16-color mode, 4 in 256-color mode
+    pub fn pixel_unit(&self) -> u16 {
+        if self.num_colors == 256 { 4 } else { 8 }
+    }
+
+    /// The rectangle covered by the stored pixels, aligned to the pixel unit.
+    /// This is what `Decoder::decode` returns (before 200-line doubling).
+    pub fn storage_rect(&self) -> Rect {
+        let unit = self.pixel_unit();
+        Rect { x: self.x / unit * unit, y: self.y, width: self.width, height: self.height }
+    }
+
+    /// The rectangle the original viewer displays, from the raw x/end_x values.
+    /// It lies within `storage_rect`; the stored pixels outside it are padding.
+    pub fn display_rect(&self) -> Rect {
+        Rect {
+            x: self.x,
+            y: self.y,
+            width: self.end_x.saturating_sub(self.x).saturating_add(1),
+            height: self.end_y.saturating_sub(self.y).saturating_add(1),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -61,33 +239,51 @@ pub struct Decoder {
     info: ImageInfo,
     header_offset: u32,
     color_mode: ColorMode,
+    palette: Palette,
+    warnings: Vec<Warning>,
+    timeout: Option<Duration>,
+    transform: Transform,
+    line_doubling: LineDoubling,
+    lenient: bool,
     buf: Vec<u8>,
 }
 
-// TODO: 最初に並べ替えておく
-struct Palette {
-    grb_colors: Vec<u8>,
+const MAGIC_NUMBER: &[u8; 8] = b"MAKI02  ";
+const HEADER_SIZE: u32 = 32;
+/// Copy vectors (x in 2-byte units, y in lines) for flag nibbles 1 to 15
+const COPY_VECTORS: [(usize, usize); 16] = [
+    (0, 0), (1, 0), (2, 0), (4, 0), (0, 1), (1, 1), (0, 2), (1, 2),
+    (2, 2), (0, 4), (1, 4), (2, 4), (0, 8), (1, 8), (2, 8), (0, 16),
+];
+/// Rows kept for copying; the farthest copy vector reaches 16 lines up
+const ROW_HISTORY: usize = 17;
+
+/// Section offsets and sizes as declared in the header, relative to it
+struct Layout {
+    flag_a_offset: u32,
+    flag_b_offset: u32,
+    flag_b_size: u32,
+    pixel_offset: u32,
+    pixel_size: u32,
 }
 
-impl Palette {
-    pub fn new(grb_colors: &[u8]) -> Palette {
-        Palette { grb_colors: grb_colors.to_owned() }
-    }
+/// Absolute (offset, size) of the data sections
+struct Sections {
+    flag_a: (u32, u32),
+    flag_b: (u32, u32),
+    pixel: (u32, u32),
+}
 
-    pub fn rgb(&self, index: u8) -> Rgb<u8> {
-        let index = index as usize * 3;
-        let g = self.grb_colors[index];
-        let r = self.grb_colors[index + 1];
-        let b = self.grb_colors[index + 2];
-        Rgb([r, g, b])
+impl Sections {
+    /// Offset just past the last section
+    fn end(&self) -> usize {
+        [self.flag_a, self.flag_b, self.pixel].iter()
+            .map(|&(start, size)| start as usize + size as usize)
+            .max()
+            .unwrap_or(0)
     }
 }
 
-const MAGIC_NUMBER: &[u8; 8] = b"MAKI02  ";
-const TEXT_ENCODING: &str = "Shift_JIS";
-const HEADER_SIZE: u32 = 32;
-
-
 fn range(start: u32, size: u32) -> Range<usize> {
     start as usize..(start + size) as usize
 }
@@ -99,6 +295,58 @@ fn pixel_unit(c: ColorMode) -> u16 {
     }
 }
 
+fn section<'a>(buf: &'a [u8], name: &str, start: u32, size: u32) -> Result<&'a [u8]> {
+    let start = start as usize;
+    start.checked_add(size as usize)
+        .and_then(|end| buf.get(start..end))
+        .ok_or_else(|| Error::InvalidFormat(format!(
+            "{} section out of bounds (offset {}, size {}, file size {})", name, start, size, buf.len())))
+}
+
+/// Computes (width, height) from the header rectangle, with width aligned to the pixel unit
+fn geometry(color_mode: ColorMode, x: u16, y: u16, end_x: u16, end_y: u16) -> Result<(u16, u16)> {
+    let invalid = |reason: &str| Error::InvalidGeometry { reason: reason.into(), x, y, end_x, end_y };
+    if end_x < x {
+        return Err(invalid("end_x < x"));
+    }
+    if end_y < y {
+        return Err(invalid("end_y < y"));
+    }
+    let pixel_unit = u32::from(pixel_unit(color_mode));
+    let width = (u32::from(end_x) / pixel_unit - u32::from(x) / pixel_unit + 1) * pixel_unit;
+    let height = u32::from(end_y) - u32::from(y) + 1;
+    if width > u32::from(u16::MAX) {
+        return Err(invalid("width exceeds 65535"));
+    }
+    if height > u32::from(u16::MAX) {
+        return Err(invalid("height exceeds 65535"));
+    }
+    Ok((width as u16, height as u16))
+}
+
+fn check_limits(options: &DecodeOptions, input_size: usize, width: u16, height: u16,
+                is_200_line_mode: bool) -> Result<()> {
+    let width = u32::from(width);
+    let height = u32::from(height);
+    let out_height = if is_200_line_mode { height * 2 } else { height };
+    if let Some(max_width) = options.max_width.filter(|&max| width > max) {
+        return Err(Error::LimitExceeded(format!("width {} exceeds {}", width, max_width)));
+    }
+    if let Some(max_height) = options.max_height.filter(|&max| out_height > max) {
+        return Err(Error::LimitExceeded(format!("height {} exceeds {}", out_height, max_height)));
+    }
+    if let Some(max_memory) = options.max_memory {
+        let image_size = width as usize * height as usize * 3;
+        let resized_size = if is_200_line_mode { image_size * 2 } else { 0 };
+        let required = input_size + image_size + resized_size;
+        if required > max_memory {
+            return Err(Error::LimitExceeded(format!(
+                "decoding requires {} bytes, exceeds {}", required, max_memory)));
+        }
+    }
+    Ok(())
+}
+
 fn nibble_high(b: u8) -> u8 {
     b >> 4
 }
@@ -109,27 +357,71 @@ fn nibble_low(b: u8) -> u8 {
 
 impl Decoder {
     /// Creates a new `Decoder` using the reader `reader`.
-    pub fn new<R: Read>(mut reader: R) -> Result<Decoder> {
-        let mut buf = Vec::new();
-        reader.read_to_end(&mut buf).unwrap();
+    pub fn new<R: Read>(reader: R) -> Result<Decoder> {
+        Decoder::with_options(reader, DecodeOptions::default())
+    }
 
-        let encoding = Encoding::for_label(TEXT_ENCODING.as_bytes())
-            .ok_or_else(|| other_err(format!("Unknown encoding; {}", TEXT_ENCODING)))?;
+    /// Creates a new `Decoder` using the reader `reader` and `options`.
+    pub fn with_options<R: Read>(mut reader: R, options: DecodeOptions) -> Result<Decoder> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("mag_header").entered();
 
-        if &buf[..8] != MAGIC_NUMBER {
+        let mut buf = Vec::new();
+        match options.max_memory {
+            Some(max_memory) => {
+                reader.take(max_memory as u64 + 1).read_to_end(&mut buf)?;
+                if buf.len() > max_memory {
+                    return Err(Error::LimitExceeded(format!("input exceeds {} bytes", max_memory)));
+                }
+            }
+            None => {
+                reader.read_to_end(&mut buf)?;
+            }
+        }
+
+        if !buf.starts_with(MAGIC_NUMBER) {
             return Err(Error::InvalidFormat("Magic number mismatch".into()));
         }
 
-        let machine_code = String::from_utf8(buf[8..12].to_owned()).unwrap();
-        let (user_name, _, _) = encoding.decode(&buf[range(12, 19)]);
+        if buf.len() < 31 {
+            return Err(Error::InvalidFormat("header truncated".into()));
+        }
+        let mut warnings = Vec::new();
+        let mut decode_text = |text_encoding: TextEncoding, bytes: &[u8], field: TextField| {
+            let (text, had_errors) = match field {
+                TextField::Memo => {
+                    let (text, _, had_errors) = text_encoding.encoding().decode(bytes);
+                    (text.into_owned(), had_errors)
+                }
+                // Fixed-width fields are often cut mid-character; the raw bytes stay in the buffer
+                TextField::MachineCode | TextField::UserName => charset::decode_fixed_width(text_encoding.encoding(), bytes),
+            };
+            if had_errors {
+                let warning = Warning::MalformedText { field, encoding: text_encoding };
+                warn!("{}", warning);
+                warnings.push(warning);
+            }
+            text
+        };
+        let memo = buf.iter().skip(31).take_while(|&b| *b != 0x1au8)
+            .cloned().collect::<Vec<u8>>();
+        let text_encoding = if options.detect_charset {
+            TextEncoding::detect(&buf[range(12, 19)], &memo)
+        } else {
+            TextEncoding::ShiftJis
+        };
+        debug!("text_encoding: {}", text_encoding);
+        let machine_code = decode_text(TextEncoding::ShiftJis, &buf[8..12], TextField::MachineCode);
+        let user_name = decode_text(text_encoding, &buf[range(12, 19)], TextField::UserName);
         debug!("machine_code: '{}', user_name: '{}'", machine_code, user_name);
 
-        let memo = &buf.iter().skip(31).take_while(|&b| *b != 0x1au8)
-            .cloned().collect::<Vec<u8>>();
         let header_offset = 31 + memo.len() as u32 + 1;
         debug!("header_offset: {}", header_offset);
+        if buf.len() < (header_offset + HEADER_SIZE) as usize {
+            return Err(Error::InvalidFormat("header truncated".into()));
+        }
         let mut header_buf = Cursor::new(buf[range(header_offset, HEADER_SIZE)].to_owned());
-        let (memo, _, _) = encoding.decode(&memo);
+        let memo = decode_text(text_encoding, &memo, TextField::Memo);
         debug!("memo: '{}'", memo);
 
         if header_buf.read_u8()? != 0 {
@@ -146,27 +438,85 @@ impl Decoder {
         let end_x = header_buf.read_u16::<LE>()?;
         let end_y = header_buf.read_u16::<LE>()?;
         debug!("x: {}, y: {}, end_x: {}, end_y: {}", x, y, end_x, end_y);
-        let pixel_unit = pixel_unit(color_mode);
+        let (width, height) = geometry(color_mode, x, y, end_x, end_y)?;
+        let is_200_line_mode = screen_mode & 1 != 0;
+        check_limits(&options, buf.len(), width, height, is_200_line_mode)?;
 
-        Ok(Decoder {
+        let num_colors = match color_mode {
+            ColorMode::Palette16 => 16,
+            ColorMode::Palette256 => 256,
+        };
+        let palette_start = (header_offset + HEADER_SIZE) as usize;
+        let palette_size = num_colors as usize * 3;
+        let mut grb_colors = buf[palette_start..].iter().take(palette_size).cloned().collect::<Vec<u8>>();
+        if grb_colors.len() < palette_size {
+            let warning = Warning::TruncatedPalette { expected: palette_size, actual: grb_colors.len() };
+            if !options.lenient {
+                return Err(Error::InvalidFormat(warning.to_string()));
+            }
+            warn!("{}", warning);
+            warnings.push(warning);
+            grb_colors.resize(palette_size, 0);
+        }
+        if let Some(colors) = &options.palette {
+            for (grb, &Rgb([r, g, b])) in grb_colors.chunks_mut(3).zip(colors) {
+                grb.copy_from_slice(&[g, r, b]);
+            }
+        }
+
+        let mut decoder = Decoder {
             info: ImageInfo {
                 machine_code,
-                user_name: user_name.to_string(),
-                memo: memo.to_string(),
+                user_name,
+                memo,
                 x,
                 y,
-                width: (((end_x / pixel_unit) - (x / pixel_unit)) + 1) * pixel_unit,
-                height: end_y - y + 1,
-                num_colors: match color_mode {
-                    ColorMode::Palette16 => 16,
-                    ColorMode::Palette256 => 256,
-                },
-                is_200_line_mode: screen_mode & 1 != 0,
+                end_x,
+                end_y,
+                width,
+                height,
+                num_colors,
+                is_200_line_mode,
+                text_encoding,
             },
             header_offset,
             color_mode,
+            palette: Palette::from_grb(grb_colors),
+            warnings,
+            timeout: options.timeout,
+            transform: options.transform,
+            line_doubling: options.line_doubling,
+            lenient: options.lenient,
             buf,
-        })
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            machine_code = %decoder.info.machine_code,
+            width = decoder.info.width,
+            height = decoder.info.height,
+            num_colors = decoder.info.num_colors,
+            is_200_line_mode = decoder.info.is_200_line_mode,
+            header_offset,
+            file_size = decoder.buf.len(),
+            "header parsed");
+
+        // every image has at least one flag A bit and, as the first unit cannot be a copy, one literal;
+        // sections out of order are left for decoding to reject, so that the header can still be read
+        if let Ok(sections) = decoder.declared_sections() {
+            for &(name, (_, size)) in &[("flag A", sections.flag_a), ("pixel", sections.pixel)] {
+                if size == 0 {
+                    return Err(Error::DegenerateImage(format!("{} section is empty for a {}x{} image",
+                                                              name, width, height)));
+                }
+            }
+        }
+        if options.lenient {
+            for warning in decoder.layout_warnings()? {
+                warn!("{}", warning);
+                decoder.warnings.push(warning);
+            }
+        }
+        Ok(decoder)
     }
 
     /// Gets metadata
@@ -174,107 +524,373 @@ impl Decoder {
         &self.info
     }
 
-    /// Decodes to RGB image buffer
+    /// Gets an owned copy of the metadata
+    pub fn to_info(&self) -> ImageInfo {
+        self.info.clone()
+    }
+
+    /// Consumes the decoder, releasing the file buffer and keeping only the metadata
+    pub fn into_info(self) -> ImageInfo {
+        self.info
+    }
+
+    /// Gets the non-fatal problems found in the input
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Gets an owned copy of the warnings
+    pub fn to_warnings(&self) -> Vec<Warning> {
+        self.warnings.clone()
+    }
+
+    /// Gets the raw bytes of the user name field, padding included
+    ///
+    /// `ImageInfo::user_name` drops a character cut off at the end of the field; these are the
+    /// bytes as stored.
+    pub fn raw_user_name(&self) -> &[u8] {
+        &self.buf[range(12, 19)]
+    }
+
+    /// Gets the palette
+    pub fn palette(&self) -> Vec<Rgb<u8>> {
+        self.palette.colors()
+    }
+
+    /// Decodes to RGB image buffer, applying the line doubling and transform set in `DecodeOptions`
     pub fn decode(&self) -> Result<RgbImage> {
-        let buf = &self.buf;
-        let mut header_buf = Cursor::new(buf[range(self.header_offset, HEADER_SIZE)].to_owned());
+        let img = if self.info.is_200_line_mode {
+            let mut img = RgbImage::new(u32::from(self.info.width), u32::from(self.info.height) * 2);
+            self.decode_onto(&mut img, 0, 0)?;
+            img
+        } else {
+            self.decode_indexed()?.to_rgb()
+        };
+        Ok(self.transform.apply(img))
+    }
+
+    /// Decodes straight into `target` with the top-left corner at (`dest_x`, `dest_y`)
+    ///
+    /// Rows are doubled in 200-line mode, as with `decode`, but the transform is not
+    /// applied. Fails without writing anything if the image does not fit in `target`.
+    pub fn decode_onto<I>(&self, target: &mut I, dest_x: u32, dest_y: u32) -> Result<()>
+        where I: GenericImage<Pixel = Rgb<u8>> {
+        let repeat = if self.info.is_200_line_mode { 2 } else { 1 };
+        let width = u32::from(self.info.width);
+        let height = u32::from(self.info.height) * repeat;
+        let (target_width, target_height) = target.dimensions();
+        let fits = |dest: u32, size: u32, target_size: u32| dest.checked_add(size).is_some_and(|end| end <= target_size);
+        if !fits(dest_x, width, target_width) || !fits(dest_y, height, target_height) {
+            return Err(other_err(format!("{}x{} image at ({}, {}) does not fit in the {}x{} target",
+                                         width, height, dest_x, dest_y, target_width, target_height)));
+        }
+        self.decode_rows(|y, row| {
+            for (x, &index) in row.iter().enumerate() {
+                let c = self.palette.rgb(index);
+                target.put_pixel(dest_x + x as u32, dest_y + y * repeat, c);
+                if repeat == 2 {
+                    target.put_pixel(dest_x + x as u32, dest_y + y * 2 + 1, self.line_doubling.second_row(c));
+                }
+            }
+        })
+    }
+
+    /// Decodes to palette indices, without 200-line doubling or the transform
+    pub fn decode_indexed(&self) -> Result<IndexedImage> {
+        let width = u32::from(self.info.width);
+        let height = u32::from(self.info.height);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        self.decode_rows(|_, row| pixels.extend_from_slice(row))?;
+        Ok(IndexedImage { width, height, pixels, palette: self.palette() })
+    }
+
+    /// Decodes to packed pixels, one `alpha << 24 | r << 16 | g << 8 | b` per pixel, row by row
+    ///
+    /// Pass 0 as `alpha` for 0RGB framebuffers such as minifb's, or 0xff for opaque ARGB.
+    /// Rows start `stride` pixels apart (the output width if `None`), with zeros in between.
+    /// Line doubling and the transform are applied as with `decode`.
+    pub fn decode_argb32(&self, alpha: u8, stride: Option<usize>) -> Result<Vec<u32>> {
+        let alpha = u32::from(alpha) << 24;
+        let pack = |Rgb([r, g, b]): Rgb<u8>| alpha | u32::from(r) << 16 | u32::from(g) << 8 | u32::from(b);
+        let repeat = if self.info.is_200_line_mode { 2 } else { 1 };
+        // Only a transform needs the intermediate RGB image
+        let transformed = if self.transform.is_identity() { None } else { Some(self.decode()?) };
+        let (width, height) = match &transformed {
+            Some(img) => (img.width() as usize, img.height() as usize),
+            None => (usize::from(self.info.width), usize::from(self.info.height) * repeat),
+        };
+        let stride = stride.unwrap_or(width);
+        if stride < width {
+            return Err(other_err(format!("stride {} is less than the width {}", stride, width)));
+        }
+
+        let mut out = vec![0; stride * height];
+        match transformed {
+            Some(img) => {
+                for (x, y, &c) in img.enumerate_pixels() {
+                    out[y as usize * stride + x as usize] = pack(c);
+                }
+            }
+            None => self.decode_rows(|y, row| {
+                let start = y as usize * repeat * stride;
+                for (x, &index) in row.iter().enumerate() {
+                    let c = self.palette.rgb(index);
+                    out[start + x] = pack(c);
+                    if repeat == 2 {
+                        out[start + stride + x] = pack(self.line_doubling.second_row(c));
+                    }
+                }
+            })?,
+        }
+        Ok(out)
+    }
+
+    /// Decodes, recording for each unit whether it was a literal or a copy and from where
+    ///
+    /// Units are in decoding order: left to right, top to bottom, in the stored image.
+    pub fn trace(&self) -> Result<Vec<Unit>> {
+        let num_units = self.info.width as usize / (pixel_unit(self.color_mode) as usize / 2) * self.info.height as usize;
+        let mut units = Vec::with_capacity(num_units);
+        self.decode_rows_traced(|_, _| (), |unit| units.push(unit))?;
+        Ok(units)
+    }
+
+    /// Counts the pixels using each palette entry, without materializing the image
+    pub fn color_usage(&self) -> Result<ColorUsage> {
+        let mut counts = vec![0u64; self.info.num_colors as usize];
+        self.decode_rows(|_, row| {
+            for &index in row {
+                counts[index as usize] += 1;
+            }
+        })?;
+        Ok(ColorUsage { counts })
+    }
+
+    /// Computes per-index and per-channel pixel counts, without materializing the image
+    ///
+    /// Counts are of stored pixels, so 200-line mode images are not doubled.
+    pub fn histogram(&self) -> Result<Histogram> {
+        Ok(Histogram::new(self.color_usage()?.counts, &self.palette()))
+    }
+
+    /// Gets the blocks appended after the image sections
+    pub fn trailer(&self) -> Result<Vec<TrailerBlock>> {
+        let end = self.sections()?.end().min(self.buf.len());
+        Ok(trailer::parse(&self.buf[end..], end))
+    }
+
+    /// Reads the section offsets and sizes declared in the header
+    fn layout(&self) -> Result<Layout> {
+        let mut header_buf = Cursor::new(&self.buf[range(self.header_offset, HEADER_SIZE)]);
         header_buf.seek(SeekFrom::Start(12))?;
 
-        let flag_a_offset = header_buf.read_u32::<LE>()?;
-        let flag_b_offset = header_buf.read_u32::<LE>()?;
-        let flag_b_size = header_buf.read_u32::<LE>()?;
-        let flag_a_size = flag_b_offset - flag_a_offset;
-        let pixel_offset = header_buf.read_u32::<LE>()?;
-        let pixel_size = header_buf.read_u32::<LE>()?;
+        let layout = Layout {
+            flag_a_offset: header_buf.read_u32::<LE>()?,
+            flag_b_offset: header_buf.read_u32::<LE>()?,
+            flag_b_size: header_buf.read_u32::<LE>()?,
+            pixel_offset: header_buf.read_u32::<LE>()?,
+            pixel_size: header_buf.read_u32::<LE>()?,
+        };
+        assert_eq!(header_buf.position() as u32, HEADER_SIZE);
+        Ok(layout)
+    }
+
+    /// The flag A bytes the image needs, one bit per flag byte of each line
+    fn flag_a_len(&self) -> u32 {
+        let bits = u32::from(self.info.width / pixel_unit(self.color_mode)) * u32::from(self.info.height);
+        bits.div_ceil(8)
+    }
+
+    /// Reads the section offsets and sizes from the header
+    ///
+    /// The flag A size is the distance to flag B, as the header has no field for it. Lenient
+    /// decoding takes the size the image needs instead, so each section is read from its own
+    /// offset whatever the layout.
+    fn declared_sections(&self) -> Result<Sections> {
+        let Layout { flag_a_offset, flag_b_offset, flag_b_size, pixel_offset, pixel_size } = self.layout()?;
+        let flag_a_size = match flag_b_offset.checked_sub(flag_a_offset) {
+            _ if self.lenient => self.flag_a_len(),
+            Some(size) => size,
+            None => return Err(Error::InvalidFormat("flag B offset precedes flag A offset".into())),
+        };
         debug!("flag_a_offset: {}, flag_b_offset: {}, flag_a_size: {}, flag_b_size: {}, pixel_offset: {}, pixel_size: {}",
                flag_a_offset, flag_b_offset, flag_a_size, flag_b_size, pixel_offset, pixel_size);
-        assert_eq!(header_buf.position() as u32, HEADER_SIZE);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(flag_a_offset, flag_a_size, flag_b_offset, flag_b_size, pixel_offset, pixel_size,
+                        "sections");
+
+        let absolute = |offset: u32| self.header_offset.saturating_add(offset);
+        Ok(Sections {
+            flag_a: (absolute(flag_a_offset), flag_a_size),
+            flag_b: (absolute(flag_b_offset), flag_b_size),
+            pixel: (absolute(pixel_offset), pixel_size),
+        })
+    }
+
+    /// `declared_sections`, cut off at the end of the file in lenient mode
+    fn sections(&self) -> Result<Sections> {
+        let mut sections = self.declared_sections()?;
+        if self.lenient {
+            let file_len = self.buf.len() as u32;
+            for (start, size) in [&mut sections.flag_a, &mut sections.flag_b, &mut sections.pixel] {
+                *size = (*size).min(file_len.saturating_sub(*start));
+            }
+        }
+        Ok(sections)
+    }
 
-        let palette = &buf[range(self.header_offset + HEADER_SIZE, u32::from(self.info.num_colors * 3))];
-        let flag_a = &buf[range(self.header_offset + flag_a_offset, flag_a_size)];
-        let flag_b = &buf[range(self.header_offset + flag_b_offset, flag_b_size)];
-        let pixels = &buf[range(self.header_offset + pixel_offset, pixel_size)];
-//        dbg!(flag_a.len(), flag_b.len(), pixels.len());
-//        dbg!(buf.len() - (self.header_offset + pixel_offset + pixel_size) as usize);
+    /// Describes gaps, overlaps and overruns in the section layout
+    fn layout_warnings(&self) -> Result<Vec<Warning>> {
+        let layout = self.layout()?;
+        let file_len = self.buf.len() as u32 - self.header_offset;
+        let palette_end = HEADER_SIZE + self.palette.len() as u32 * 3;
+        let mut declared = [
+            ("flag A", layout.flag_a_offset, self.flag_a_len()),
+            ("flag B", layout.flag_b_offset, layout.flag_b_size),
+            ("pixel", layout.pixel_offset, layout.pixel_size),
+        ];
+
+        let mut reasons = Vec::new();
+        declared.sort_by_key(|&(_, offset, _)| offset);
+        if declared.iter().map(|&(name, _, _)| name).ne(["flag A", "flag B", "pixel"].iter().cloned()) {
+            let order: Vec<&str> = declared.iter().map(|&(name, _, _)| name).collect();
+            reasons.push(format!("the sections are stored in the order {}", order.join(", ")));
+        }
+        let (mut previous, mut previous_end) = ("the palette".to_owned(), palette_end);
+        for &(name, offset, size) in &declared {
+            if offset > previous_end {
+                reasons.push(format!("{} unused bytes before the {} section", offset - previous_end, name));
+            } else if offset < previous_end {
+                reasons.push(format!("the {} section starts {} bytes inside {}", name, previous_end - offset, previous));
+            }
+            let end = offset.saturating_add(size);
+            if end > file_len {
+                reasons.push(format!("the {} section ends {} bytes past the end of the file", name, end - file_len));
+            }
+            previous = format!("the {} section", name);
+            previous_end = end;
+        }
+        Ok(reasons.into_iter().map(|reason| Warning::UnusualLayout { reason }).collect())
+    }
+
+    /// Walks the flag and pixel streams, passing each row of palette indices to `row_fn`.
+    ///
+    /// Only the rows reachable by copy vectors are kept in memory.
+    fn decode_rows<F: FnMut(u32, &[u8])>(&self, row_fn: F) -> Result<()> {
+        self.decode_rows_traced(row_fn, |_| ())
+    }
+
+    /// `decode_rows`, also passing each unit to `unit_fn` as it is decoded
+    fn decode_rows_traced<F, T>(&self, mut row_fn: F, mut unit_fn: T) -> Result<()>
+        where F: FnMut(u32, &[u8]), T: FnMut(Unit) {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("mag_decode", width = self.info.width, height = self.info.height).entered();
+        #[cfg(feature = "tracing")]
+        let started = std::time::Instant::now();
+
+        let deadline = self.timeout.map(|timeout| (Instant::now() + timeout, timeout));
+        let buf = &self.buf;
+        let sections = self.sections()?;
+        let flag_a = section(buf, "flag A", sections.flag_a.0, sections.flag_a.1)?;
+        let flag_b = section(buf, "flag B", sections.flag_b.0, sections.flag_b.1)?;
+        let pixels = section(buf, "pixel", sections.pixel.0, sections.pixel.1)?;
 
-        let mut img: RgbImage = ImageBuffer::new(u32::from(self.info.width), u32::from(self.info.height));
+        let width = self.info.width as usize;
         let pixel_unit = pixel_unit(self.color_mode);
         let num_x_units = self.info.width / pixel_unit;
+        let copy_pixels = pixel_unit as usize / 2;
 
         let mut flag_a_bits = BitVec::from_bytes(flag_a).into_iter();
-        let mut flag_b = Cursor::new(flag_b);
-        let mut pixels = Cursor::new(pixels);
-        let palette = Palette::new(palette);
+        let mut flag_b = flag_b.iter();
+        let mut pixels = pixels.iter();
         let mut line_flags = vec![0u8; num_x_units as usize];
-        let copy_vec = self.init_copy_vec();
+        let mut rows = vec![vec![0u8; width]; ROW_HISTORY];
 
-        let mut decode_nibble = |mut dst_x: u32, y: u32, flag: u8| -> u32 {
-            if flag == 0 {
-                match self.color_mode {
-                    ColorMode::Palette16 => {
-                        for _ in 0..2 {
-                            let pixel_byte = pixels.read_u8().unwrap();
-                            img.put_pixel(dst_x, y, palette.rgb(nibble_high(pixel_byte)));
-                            dst_x += 1;
-                            img.put_pixel(dst_x, y, palette.rgb(nibble_low(pixel_byte)));
-                            dst_x += 1;
-                        }
-                    }
-                    ColorMode::Palette256 => {
-                        for _ in 0..2 {
-                            let pixel_byte = pixels.read_u8().unwrap();
-                            img.put_pixel(dst_x, y, palette.rgb(pixel_byte));
-                            dst_x += 1;
-                        }
-                    }
+        for y in 0..u32::from(self.info.height) {
+            if let Some((deadline, timeout)) = deadline {
+                if Instant::now() > deadline {
+                    return Err(Error::TimedOut(timeout));
                 }
-                dst_x
-            } else {
-                dst_x + self.copy_pixel_unit(&mut img, dst_x, y, copy_vec[flag as usize])
             }
-        };
-
-        for y in 0..u32::from(self.info.height) {
             for flag in line_flags.iter_mut() {
                 if let Some(true) = flag_a_bits.next() {
-                    *flag ^= flag_b.read_u8()?;
+                    *flag ^= flag_b.next()
+                        .ok_or_else(|| Error::InvalidFormat(format!("flag B data exhausted at line {}", y)))?;
                 }
             }
 
-            let mut dst_x = 0;
-
+            let mut row = std::mem::take(&mut rows[y as usize % ROW_HISTORY]);
+            let mut x = 0;
             for &flag in line_flags.iter() {
-                dst_x = decode_nibble(dst_x, y, nibble_high(flag));
-                dst_x = decode_nibble(dst_x, y, nibble_low(flag));
+                for &nibble in &[nibble_high(flag), nibble_low(flag)] {
+                    let unit_x = x as u32;
+                    if nibble == 0 {
+                        unit_fn(Unit { x: unit_x, y, width: copy_pixels as u32, action: Action::Literal });
+                        for _ in 0..2 {
+                            let pixel_byte = *pixels.next()
+                                .ok_or_else(|| Error::InvalidFormat(format!("pixel data exhausted at line {}", y)))?;
+                            match self.color_mode {
+                                ColorMode::Palette16 => {
+                                    row[x] = nibble_high(pixel_byte);
+                                    row[x + 1] = nibble_low(pixel_byte);
+                                    x += 2;
+                                }
+                                ColorMode::Palette256 => {
+                                    row[x] = pixel_byte;
+                                    x += 1;
+                                }
+                            }
+                        }
+                    } else {
+                        let (dx, dy) = COPY_VECTORS[nibble as usize];
+                        let src_x = x.checked_sub(dx * copy_pixels)
+                            .filter(|_| dy <= y as usize)
+                            .ok_or_else(|| Error::InvalidFormat(format!(
+                                "copy source out of bounds at ({}, {})", x, y)))?;
+                        unit_fn(Unit {
+                            x: unit_x,
+                            y,
+                            width: copy_pixels as u32,
+                            action: Action::Copy { vector: nibble, dx: (dx * copy_pixels) as u32, dy: dy as u32 },
+                        });
+                        if dy == 0 {
+                            row.copy_within(src_x..src_x + copy_pixels, x);
+                        } else {
+                            let src = &rows[(y as usize - dy) % ROW_HISTORY];
+                            row[x..x + copy_pixels].copy_from_slice(&src[src_x..src_x + copy_pixels]);
+                        }
+                        x += copy_pixels;
+                    }
+                }
             }
-        }
 
-        if self.info.is_200_line_mode {
-            Ok(imageops::resize(&img, u32::from(self.info.width), u32::from(self.info.height) * 2,
-                                FilterType::Nearest))
-        } else {
-            Ok(img)
+            row_fn(y, &row);
+            rows[y as usize % ROW_HISTORY] = row;
         }
-    }
 
-    fn init_copy_vec(&self) -> Vec<(u32, u32)> {
-        let x = [0, 1, 2, 4, 0, 1, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0];
-        let y = [0, 0, 0, 0, 1, 1, 2, 2, 2, 4, 4, 4, 8, 8, 8, 16];
-        x.iter().zip(y.iter()).map(|(a, b)| (*a, *b)).collect()
+        #[cfg(feature = "tracing")]
+        tracing::debug!(elapsed_us = started.elapsed().as_micros() as u64, "decoded");
+        Ok(())
     }
+}
 
-    fn copy_pixel_unit(&self, img: &mut RgbImage, x: u32, y: u32, copy_from: (u32, u32)) -> u32 {
-        let copy_pixels = match self.color_mode {
-            ColorMode::Palette16 => 4,
-            ColorMode::Palette256 => 2,
-        };
-        let src_x = x - (copy_from.0 * copy_pixels);
-        let src_y = y - copy_from.1;
+/// Decodes the MAG file at `input` and saves it to `output`, in the format given by its extension.
+pub fn convert_file(input: impl AsRef<Path>, output: impl AsRef<Path>, options: &DecodeOptions) -> Result<()> {
+    let output = output.as_ref();
+    let format = OutputFormat::from_path(output)
+        .ok_or_else(|| other_err(format!("unknown output format for '{}'", output.display())))?;
+    let file = File::open(input)?;
+    let img = Decoder::with_options(BufReader::new(file), options.clone())?.decode()?;
+    format.save(&img, output)
+}
 
-        for i in 0..copy_pixels {
-            img.put_pixel(x + i, y, img[(src_x + i, src_y)]);
-        }
+/// Reads metadata of the MAG file at `path`.
+pub fn read_info(path: impl AsRef<Path>) -> Result<ImageInfo> {
+    let file = File::open(path)?;
+    Ok(Decoder::new(BufReader::new(file))?.into_info())
+}
 
-        copy_pixels
-    }
+/// Reads the author's memo of the MAG file at `path`.
+pub fn read_memo(path: impl AsRef<Path>) -> Result<String> {
+    Ok(read_info(path)?.memo)
 }
@@ -0,0 +1,239 @@
+//! Byte-exact model of a MAG file
+//!
+//! `MagFile` keeps every byte of the file it was parsed from: reserved header bytes,
+//! gaps between sections and data appended after them. Writing it back gives the
+//! same bytes, with only the edits made through its setters applied.
+//!
+//! # Examples
+//! ```no_run
+//! use mag_image_decoder::MagFile;
+//!
+//! let mut file = MagFile::read("SAMPLE.MAG").unwrap();
+//! file.set_memo("scanned from the original disk").unwrap();
+//! std::fs::write("SAMPLE.MAG", file.to_bytes()).unwrap();
+//! ```
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use encoding_rs::SHIFT_JIS;
+use image::Rgb;
+
+use crate::error::*;
+use crate::{geometry, pixel_unit, range, ColorMode, HEADER_SIZE, MAGIC_NUMBER};
+
+/// Magic number, machine code, user name and the byte before the memo
+const PREFIX_SIZE: usize = 31;
+const MACHINE_CODE: (u32, u32) = (8, 4);
+const USER_NAME: (u32, u32) = (12, 18);
+const MEMO_END: u8 = 0x1a;
+
+/// A MAG file as its raw parts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MagFile {
+    prefix: Vec<u8>,
+    /// The memo, without its 0x1A terminator
+    memo: Vec<u8>,
+    header: [u8; HEADER_SIZE as usize],
+    /// Everything after the header: the palette, the sections and whatever lies around them
+    body: Vec<u8>,
+}
+
+/// Encodes `s` as Shift_JIS, failing on characters it cannot represent
+fn encode_text(s: &str, field: &str) -> Result<Vec<u8>> {
+    let (bytes, _, had_unmappable) = SHIFT_JIS.encode(s);
+    if had_unmappable {
+        return Err(other_err(format!("{} '{}' cannot be encoded as Shift_JIS", field, s)));
+    }
+    Ok(bytes.into_owned())
+}
+
+impl MagFile {
+    /// Splits `bytes` into the parts of a MAG file
+    ///
+    /// Only the magic number, the memo terminator and a complete header are required;
+    /// everything else is kept as found, so damaged files can be edited too.
+    pub fn parse(bytes: &[u8]) -> Result<MagFile> {
+        if !bytes.starts_with(MAGIC_NUMBER) {
+            return Err(Error::InvalidFormat("Magic number mismatch".into()));
+        }
+        if bytes.len() < PREFIX_SIZE {
+            return Err(Error::InvalidFormat("header truncated".into()));
+        }
+        let memo_len = bytes[PREFIX_SIZE..].iter().position(|&b| b == MEMO_END)
+            .ok_or_else(|| Error::InvalidFormat("header truncated".into()))?;
+        let header_offset = PREFIX_SIZE + memo_len + 1;
+        let header_end = header_offset + HEADER_SIZE as usize;
+        if bytes.len() < header_end {
+            return Err(Error::InvalidFormat("header truncated".into()));
+        }
+        let mut header = [0; HEADER_SIZE as usize];
+        header.copy_from_slice(&bytes[header_offset..header_end]);
+        Ok(MagFile {
+            prefix: bytes[..PREFIX_SIZE].to_vec(),
+            memo: bytes[PREFIX_SIZE..header_offset - 1].to_vec(),
+            header,
+            body: bytes[header_end..].to_vec(),
+        })
+    }
+
+    /// Reads and parses the file at `path`
+    pub fn read(path: impl AsRef<Path>) -> Result<MagFile> {
+        MagFile::parse(&fs::read(path)?)
+    }
+
+    /// The file's bytes, with the edits applied
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.header_offset() + HEADER_SIZE as usize + self.body.len());
+        bytes.extend_from_slice(&self.prefix);
+        bytes.extend_from_slice(&self.memo);
+        bytes.push(MEMO_END);
+        bytes.extend_from_slice(&self.header);
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+
+    /// Writes the file's bytes to `w`
+    pub fn write<W: Write>(&self, mut w: W) -> Result<()> {
+        w.write_all(&self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Offset of the header from the start of the file
+    pub fn header_offset(&self) -> usize {
+        PREFIX_SIZE + self.memo.len() + 1
+    }
+
+    /// The raw machine code field
+    pub fn machine_code(&self) -> &[u8] {
+        &self.prefix[range(MACHINE_CODE.0, MACHINE_CODE.1)]
+    }
+
+    /// The raw user name field, padding included
+    pub fn user_name(&self) -> &[u8] {
+        &self.prefix[range(USER_NAME.0, USER_NAME.1)]
+    }
+
+    /// The raw memo, without its terminator
+    pub fn memo(&self) -> &[u8] {
+        &self.memo
+    }
+
+    /// The raw 32-byte header
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    fn color_mode(&self) -> ColorMode {
+        if self.header[3] & 0x80 != 0 { ColorMode::Palette256 } else { ColorMode::Palette16 }
+    }
+
+    fn header_u16(&self, at: usize) -> u16 {
+        u16::from_le_bytes([self.header[at], self.header[at + 1]])
+    }
+
+    fn header_u32(&self, at: usize) -> u32 {
+        u32::from_le_bytes([self.header[at], self.header[at + 1], self.header[at + 2], self.header[at + 3]])
+    }
+
+    /// Number of palette bytes the screen mode calls for
+    fn palette_size(&self) -> usize {
+        match self.color_mode() {
+            ColorMode::Palette16 => 16 * 3,
+            ColorMode::Palette256 => 256 * 3,
+        }
+    }
+
+    /// The palette entries stored in the file, fewer than the screen mode calls for if it is truncated
+    pub fn palette(&self) -> Vec<Rgb<u8>> {
+        self.body[..self.palette_size().min(self.body.len())].chunks_exact(3)
+            .map(|grb| Rgb([grb[1], grb[0], grb[2]]))
+            .collect()
+    }
+
+    /// Offset in `body` just past the last section, as declared in the header
+    ///
+    /// Flag A has no size field; it is taken to end at flag B, or where the image
+    /// needs it to when flag B comes first.
+    fn data_end(&self) -> usize {
+        let flag_a_offset = self.header_u32(12);
+        let flag_b_offset = self.header_u32(16);
+        let flag_a_size = flag_b_offset.checked_sub(flag_a_offset).unwrap_or_else(|| {
+            let color_mode = self.color_mode();
+            let (x, y, end_x, end_y) = (self.header_u16(4), self.header_u16(6), self.header_u16(8), self.header_u16(10));
+            geometry(color_mode, x, y, end_x, end_y).map_or(0, |(width, height)| {
+                (u32::from(width / pixel_unit(color_mode)) * u32::from(height)).div_ceil(8)
+            })
+        });
+        let ends = [
+            (flag_a_offset, flag_a_size),
+            (flag_b_offset, self.header_u32(20)),
+            (self.header_u32(24), self.header_u32(28)),
+        ];
+        let end = ends.iter().map(|&(offset, size)| u64::from(offset) + u64::from(size)).max().unwrap_or(0);
+        (end.saturating_sub(u64::from(HEADER_SIZE)) as usize).max(self.palette_size().min(self.body.len()))
+    }
+
+    /// The bytes after the last section
+    pub fn appended(&self) -> &[u8] {
+        &self.body[self.data_end().min(self.body.len())..]
+    }
+
+    /// Replaces the machine code, padding it with spaces to 4 bytes
+    pub fn set_machine_code(&mut self, machine_code: &str) -> Result<()> {
+        self.set_field(MACHINE_CODE, machine_code, "machine code")
+    }
+
+    /// Replaces the user name, padding it with spaces to 18 bytes
+    pub fn set_user_name(&mut self, user_name: &str) -> Result<()> {
+        self.set_field(USER_NAME, user_name, "user name")
+    }
+
+    fn set_field(&mut self, (start, size): (u32, u32), value: &str, field: &str) -> Result<()> {
+        let mut bytes = encode_text(value, field)?;
+        if bytes.len() > size as usize {
+            return Err(other_err(format!("{} '{}' is longer than {} bytes", field, value, size)));
+        }
+        bytes.resize(size as usize, b' ');
+        self.prefix[range(start, size)].copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Replaces the memo; the header and everything after it move along unchanged
+    pub fn set_memo(&mut self, memo: &str) -> Result<()> {
+        let bytes = encode_text(memo, "memo")?;
+        if bytes.contains(&MEMO_END) {
+            return Err(other_err("memo must not contain 0x1A"));
+        }
+        self.memo = bytes;
+        Ok(())
+    }
+
+    /// Sets the 200-line (rectangular pixel) flag of the screen mode
+    pub fn set_200_line_mode(&mut self, is_200_line_mode: bool) {
+        self.header[3] = self.header[3] & !1 | is_200_line_mode as u8;
+    }
+
+    /// Replaces the palette entry `index`, which must be stored in the file
+    pub fn set_palette_color(&mut self, index: usize, color: Rgb<u8>) -> Result<()> {
+        let num_colors = self.palette().len();
+        if index >= num_colors {
+            return Err(other_err(format!("index {} is outside the {}-entry palette", index, num_colors)));
+        }
+        let Rgb([r, g, b]) = color;
+        self.body[index * 3..index * 3 + 3].copy_from_slice(&[g, r, b]);
+        Ok(())
+    }
+
+    /// Replaces the bytes after the last section with `data`
+    pub fn set_appended(&mut self, data: &[u8]) -> Result<()> {
+        let end = self.data_end();
+        if end > self.body.len() {
+            return Err(other_err(format!("the sections end {} bytes past the end of the file", end - self.body.len())));
+        }
+        self.body.truncate(end);
+        self.body.extend_from_slice(data);
+        Ok(())
+    }
+}
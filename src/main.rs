@@ -1,54 +1,934 @@
-use mag_image_decoder::Decoder;
+use mag_image_decoder::{DecodeOptions, Decoder, LineDoubling, RetroImage, Rotation, Transform};
+use mag_image_decoder::filter::CrtFilter;
+use mag_image_decoder::indexed_png::write_indexed_png;
+use mag_image_decoder::output::OutputFormat;
+use mag_image_decoder::palette_file;
+use mag_image_decoder::output::png::ColorProfile;
+use mag_image_decoder::output::{aseprite, tga, tiff};
+use mag_image_decoder::remap::{PaletteMap, RemapTarget};
+use mag_image_decoder::resize::{Resize, ResizeFilter, ResizeMode};
+use mag_image_decoder::scale::Upscaler;
 use std::fs::File;
-use std::io::BufReader;
-use simple_logger;
-use log::info;
+use std::io::{self, BufWriter, Write};
+use log::{info, warn, LevelFilter};
+use image::{Rgb, RgbImage};
 use structopt::StructOpt;
-use std::path::PathBuf;
-use std::{fs, process};
+use structopt::clap::{AppSettings, ArgMatches, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::ffi::{OsStr, OsString};
+use std::{env, fs, process, thread};
+
+mod cmd;
+
+use crate::cmd::collision::{self, Collision};
+use crate::cmd::find::Filter;
+use crate::cmd::lang::Lang;
+use crate::cmd::source::{self, Item, SourceOptions};
+use crate::cmd::report::{self, LogFormat, Processed, Timing};
+use crate::cmd::slideshow::Slideshow;
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "magdecode", author = "", about = "\
-MAG image decoder")]
+MAG image decoder
+
+Without a subcommand, the arguments are those of `decode`.",
+    raw(global_settings = "&[AppSettings::AllArgsOverrideSelf]"))]
 struct Opt {
     // The number of occurrences of the `v/verbose` flag
-    /// Verbose mode (-v, -vv, -vvv, etc.)
-    #[structopt(short = "v", long = "verbose", parse(from_occurrences))]
+    /// Verbose mode (-v, -vv, -vvv, etc.), RUST_LOG overrides per module
+    #[structopt(short = "v", long = "verbose", parse(from_occurrences), raw(global = "true"))]
     verbose: u8,
 
+    /// Log format on stderr: text, or jsonl (adds one JSON record per processed file)
+    #[structopt(long = "log-format", name = "LOG_FORMAT", default_value = "text", raw(global = "true"))]
+    log_format: LogFormat,
+
+    /// Language of help and error messages: en, or ja (`ja` feature); defaults to the locale
+    #[structopt(long = "lang", name = "LANG", raw(global = "true"))]
+    #[allow(dead_code)] // read by `Lang::detect` before parsing, so that help and parse errors are translated
+    lang: Option<Lang>,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+/// Input files, shared by the subcommands that read MAG files, archives and directories
+#[derive(StructOpt, Debug)]
+struct InputArgs {
+    /// Also process the files listed in LIST, one per line ('-' reads the list from stdin)
+    #[structopt(long = "files-from", name = "LIST", parse(from_os_str))]
+    files_from: Option<PathBuf>,
+
+    /// With --files-from, entries are NUL-terminated (as from `find -print0`)
+    #[structopt(short = "0", long = "null", requires = "LIST")]
+    null: bool,
+
+    /// Process directories given as FILE recursively
+    #[structopt(short = "r", long = "recursive")]
+    recursive: bool,
+
+    /// Turn off --recursive (e.g. set in magdecode.toml)
+    #[structopt(long = "no-recursive", overrides_with = "recursive")]
+    #[allow(dead_code)] // only overrides --recursive
+    no_recursive: bool,
+
+    /// Files to process; .lzh and .zip archives are searched for MAG entries ('-' reads stdin)
+    #[structopt(name = "FILE", parse(from_os_str))]
+    files: Vec<PathBuf>,
+}
+
+/// How MAG files are parsed, shared by the subcommands that decode images
+#[derive(StructOpt, Debug)]
+struct ParseArgs {
+    /// Recover from damaged files where possible, with warnings
+    #[structopt(long = "lenient")]
+    lenient: bool,
+
+    /// Turn off --lenient (e.g. set in magdecode.toml)
+    #[structopt(long = "no-lenient", overrides_with = "lenient")]
+    #[allow(dead_code)] // only overrides --lenient
+    no_lenient: bool,
+
+    /// Try UTF-8 and EUC-JP for user names and memos that are not valid Shift_JIS
+    #[structopt(long = "detect-charset")]
+    detect_charset: bool,
+
+    /// Turn off --detect-charset (e.g. set in magdecode.toml)
+    #[structopt(long = "no-detect-charset", overrides_with = "detect_charset")]
+    #[allow(dead_code)] // only overrides --detect-charset
+    no_detect_charset: bool,
+
+    /// Reject images larger than WxH pixels
+    #[structopt(long = "max-dimensions", name = "WxH")]
+    max_dimensions: Option<Dimensions>,
+
+    /// Reject files needing more than BYTES of memory to decode
+    #[structopt(long = "max-memory", name = "BYTES")]
+    max_memory: Option<usize>,
+
+    /// Abort decoding a file after SECONDS of wall-clock time
+    #[structopt(long = "timeout", name = "SECONDS", parse(try_from_str = "parse_seconds"))]
+    timeout: Option<f64>,
+}
+
+/// Options of `decode`
+#[derive(StructOpt, Debug)]
+struct DecodeArgs {
     /// Specify the output directory
     #[structopt(short = "o", long = "outdir", name = "DIR", parse(from_os_str))]
     out_dir: Option<PathBuf>,
 
-    /// Files to process
-    #[structopt(name = "FILE", parse(from_os_str))]
-    files: Vec<PathBuf>,
+    /// Output image format: png, qoi, ppm, farbfeld, tga, tiff, aseprite, ico (avif when built with the `avif` feature)
+    #[structopt(short = "f", long = "format", name = "FORMAT", default_value = "png")]
+    format: OutputFormat,
+
+    /// Name output files after TEMPLATE, where {stem} is the input file name without its
+    /// extension, {name} the whole input file name and {ext} the extension of the output format
+    #[structopt(long = "name-template", name = "TEMPLATE", default_value = "{stem}.{ext}")]
+    name_template: NameTemplate,
+
+    /// Convert up to JOBS files at a time
+    #[structopt(short = "j", long = "jobs", name = "JOBS", default_value = "1", parse(try_from_str = "parse_jobs"))]
+    jobs: usize,
+
+    /// Embed the ICC profile file PROFILE in PNG output instead of tagging it as sRGB
+    #[structopt(long = "icc-profile", name = "PROFILE", parse(try_from_os_str = "read_icc_profile"),
+                conflicts_with = "indexed")]
+    icc_profile: Option<ColorProfile>,
+
+    /// Decode with the palette from PALETTE instead of the embedded one: a MAG file, JASC-PAL,
+    /// GIMP palette or Adobe Color Table; entries past its end keep the embedded colors
+    #[structopt(long = "palette-file", name = "PALETTE", parse(try_from_os_str = "read_palette_file"))]
+    palette_file: Option<PaletteFile>,
+
+    /// Upscale with a pixel-art-aware algorithm: scale2x, scale3x, hq2x
+    #[structopt(long = "upscale", name = "ALGORITHM")]
+    upscale: Option<Upscaler>,
+
+    /// Write an indexed PNG keeping the original palette indices and metadata (see `encode`),
+    /// a color-mapped TGA with `--format tga`, a palette TIFF with `--format tiff`, or an indexed
+    /// sprite with `--format aseprite`
+    #[structopt(long = "indexed")]
+    indexed: bool,
+
+    /// Turn off --indexed (e.g. set in magdecode.toml)
+    #[structopt(long = "no-indexed", overrides_with = "indexed")]
+    #[allow(dead_code)] // only overrides --indexed
+    no_indexed: bool,
+
+    /// Apply a CRT effect (scanlines, phosphor blur, aperture grille) after decoding
+    #[structopt(long = "crt")]
+    crt: bool,
+
+    /// Turn off --crt (e.g. set in magdecode.toml)
+    #[structopt(long = "no-crt", overrides_with = "crt")]
+    #[allow(dead_code)] // only overrides --crt
+    no_crt: bool,
+
+    /// Double 200-line images with scanlines of BRIGHTNESS (0 for black to 1) instead of repeated rows
+    #[structopt(long = "scanlines", name = "BRIGHTNESS", parse(try_from_str = "parse_brightness"))]
+    scanlines: Option<f32>,
+
+    /// Resize the output to WxH (after --upscale and --crt)
+    #[structopt(long = "resize", name = "SIZE")]
+    resize: Option<Dimensions>,
+
+    /// How --resize treats the aspect ratio: fit (within WxH) or fill (cover WxH, cropping the center)
+    #[structopt(long = "resize-mode", name = "MODE", default_value = "fit")]
+    resize_mode: ResizeMode,
+
+    /// Resampling filter for --resize: nearest, triangle, catmull-rom, gaussian, lanczos3
+    #[structopt(long = "filter", name = "FILTER", default_value = "lanczos3")]
+    filter: ResizeFilter,
+
+    /// Rotate the output clockwise by DEGREES (0, 90, 180 or 270)
+    #[structopt(long = "rotate", name = "DEGREES", default_value = "0")]
+    rotate: Rotation,
+
+    /// Mirror the output left to right (after rotating)
+    #[structopt(long = "flip-horizontal")]
+    flip_horizontal: bool,
+
+    /// Turn off --flip-horizontal (e.g. set in magdecode.toml)
+    #[structopt(long = "no-flip-horizontal", overrides_with = "flip_horizontal")]
+    #[allow(dead_code)] // only overrides --flip-horizontal
+    no_flip_horizontal: bool,
+
+    /// Mirror the output top to bottom (after rotating)
+    #[structopt(long = "flip-vertical")]
+    flip_vertical: bool,
+
+    /// Turn off --flip-vertical (e.g. set in magdecode.toml)
+    #[structopt(long = "no-flip-vertical", overrides_with = "flip_vertical")]
+    #[allow(dead_code)] // only overrides --flip-vertical
+    no_flip_vertical: bool,
+
+    /// Place the decoded image on the system clipboard instead of writing a file (`clipboard` feature)
+    #[structopt(long = "clipboard", raw(conflicts_with_all = "CLIPBOARD_CONFLICTS"))]
+    clipboard: bool,
+
+    /// When inputs map to the same output file: error (before converting anything), suffix
+    /// (NAME-1.EXT, ...), or overwrite
+    #[structopt(long = "on-collision", name = "POLICY", default_value = "error")]
+    on_collision: Collision,
+
+    /// Copy the modification time of each input file onto its output
+    #[structopt(long = "preserve-times")]
+    preserve_times: bool,
+
+    /// Turn off --preserve-times (e.g. set in magdecode.toml)
+    #[structopt(long = "no-preserve-times", overrides_with = "preserve_times")]
+    #[allow(dead_code)] // only overrides --preserve-times
+    no_preserve_times: bool,
+
+    /// Also write unrecognized data appended after the image to <output>.appended
+    #[structopt(long = "extract-appended")]
+    extract_appended: bool,
+
+    /// Deprecated: use `scan`, which this runs on the inputs instead of converting them
+    #[structopt(long = "list")]
+    list: bool,
+
+    /// Print decode/encode time, sizes and throughput per file and in total to stderr
+    #[structopt(long = "timing")]
+    timing: bool,
+
+    /// Turn off --timing (e.g. set in magdecode.toml)
+    #[structopt(long = "no-timing", overrides_with = "timing")]
+    #[allow(dead_code)] // only overrides --timing
+    no_timing: bool,
+
+    #[structopt(flatten)]
+    parse: ParseArgs,
+
+    #[structopt(flatten)]
+    input: InputArgs,
+}
+
+/// Arguments of `decode` that `--clipboard` conflicts with
+const CLIPBOARD_CONFLICTS: &[&str] = &["DIR", "indexed", "extract_appended", "preserve_times"];
+
+/// Arguments of `decode` and those they conflict with, so that defaults from `magdecode.toml`
+/// conflicting with the command line are skipped
+const CONFLICTS: &[(&str, &[&str])] = &[
+    ("clipboard", CLIPBOARD_CONFLICTS), ("PROFILE", &["indexed"]), ("indexed", &["PROFILE"]),
+];
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Converts MAG files to PNG or another format (the default subcommand)
+    #[structopt(name = "decode")]
+    Decode(DecodeArgs),
+
+    /// Prints the full header, warnings and appended data of each file
+    #[structopt(name = "info")]
+    Info {
+        /// Also print per-palette-entry pixel counts and unused entries
+        #[structopt(long = "color-usage")]
+        color_usage: bool,
+
+        #[structopt(flatten)]
+        parse: ParseArgs,
+
+        #[structopt(flatten)]
+        input: InputArgs,
+    },
+
+    /// Prints a one-line summary of each MAG file in the given files, directories and archives, without decoding
+    #[structopt(name = "scan", raw(alias = "\"identify\""))]
+    Scan {
+        /// Files, directories (searched recursively) and .lzh/.zip archives
+        #[structopt(name = "PATH", parse(from_os_str))]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Decodes each file without writing output, reporting errors and warnings
+    #[structopt(name = "check")]
+    Check {
+        #[structopt(flatten)]
+        parse: ParseArgs,
+
+        #[structopt(flatten)]
+        input: InputArgs,
+    },
+
+    /// Diagnoses undecodable files: checks each part in order and reports offsets and suggested fixes
+    #[structopt(name = "doctor")]
+    Doctor {
+        #[structopt(flatten)]
+        input: InputArgs,
+    },
+
+    /// Generates a static HTML gallery with thumbnails and metadata
+    #[structopt(name = "gallery")]
+    Gallery {
+        /// The directory to scan for MAG files (recursively)
+        #[structopt(name = "DIR", parse(from_os_str))]
+        dir: PathBuf,
+
+        /// The output directory of the site
+        #[structopt(short = "o", long = "outdir", name = "OUTDIR", parse(from_os_str))]
+        out_dir: PathBuf,
+    },
+
+    /// Encodes an indexed PNG written with `--indexed` back to MAG
+    #[structopt(name = "encode")]
+    Encode {
+        /// Input indexed PNG file
+        #[structopt(name = "INPUT", parse(from_os_str))]
+        input: PathBuf,
+
+        /// Output MAG file
+        #[structopt(short = "o", long = "output", name = "OUTPUT", parse(from_os_str))]
+        output: PathBuf,
+    },
+
+    /// Reports PSNR, SSIM and the maximum pixel difference of a decoded MAG file against a reference image
+    #[structopt(name = "compare")]
+    Compare {
+        /// MAG file to decode
+        #[structopt(name = "FILE", parse(from_os_str))]
+        file: PathBuf,
+
+        /// Reference image (PNG, BMP, ...) of the same size as the decoded output
+        #[structopt(name = "REFERENCE", parse(from_os_str))]
+        reference: PathBuf,
+    },
+
+    /// Renders a false-color map of literal (red) and copied units, shaded from cyan (near) to blue (far source)
+    #[structopt(name = "visualize")]
+    Visualize {
+        /// MAG file to analyze
+        #[structopt(name = "FILE", parse(from_os_str))]
+        file: PathBuf,
+
+        /// Output image, in the format given by its extension
+        #[structopt(short = "o", long = "output", name = "OUTPUT", parse(from_os_str))]
+        output: PathBuf,
+    },
+
+    /// Rewrites palette indices and entries, producing a new MAG file
+    #[structopt(name = "remap")]
+    Remap {
+        /// Index mapping, e.g. 3:5,5:3
+        #[structopt(short = "m", long = "map", name = "OLD:NEW,...")]
+        map: PaletteMap,
+
+        /// Rewrite pixel indices only, keeping the palette
+        #[structopt(long = "pixels-only", conflicts_with = "palette_only")]
+        pixels_only: bool,
+
+        /// Rewrite palette entries only, keeping the pixel indices
+        #[structopt(long = "palette-only")]
+        palette_only: bool,
+
+        /// Input MAG file
+        #[structopt(name = "INPUT", parse(from_os_str))]
+        input: PathBuf,
+
+        /// Output MAG file
+        #[structopt(short = "o", long = "output", name = "OUTPUT", parse(from_os_str))]
+        output: PathBuf,
+    },
+
+    /// Assembles MAG files into an animation, one frame per file: GIF (shared palette) or APNG
+    #[structopt(name = "animate")]
+    Animate {
+        /// Frames, in order; all must have the same size
+        #[structopt(name = "FILE", parse(from_os_str), required = true)]
+        files: Vec<PathBuf>,
+
+        /// Output file; .gif for GIF, .png or .apng for APNG
+        #[structopt(short = "o", long = "output", name = "OUTPUT", parse(from_os_str))]
+        output: PathBuf,
+
+        /// Time each frame is shown, in milliseconds
+        #[structopt(long = "delay", name = "MS", default_value = "100")]
+        delay: u32,
+    },
+
+    /// Writes a slideshow of the images as a Y4M video stream, e.g. for `ffmpeg -i - out.mp4`
+    #[structopt(name = "slideshow")]
+    Slideshow {
+        /// Write the stream to OUTPUT instead of stdout
+        #[structopt(short = "o", long = "output", name = "OUTPUT", parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Frame size; images are fitted within it on black, keeping their aspect ratio
+        #[structopt(long = "size", name = "WxH", default_value = "640x400")]
+        size: Dimensions,
+
+        /// Frames per second
+        #[structopt(long = "fps", default_value = "30")]
+        fps: u32,
+
+        /// How long each image is shown, in seconds
+        #[structopt(long = "duration", name = "SECONDS", parse(try_from_str = "parse_seconds"), default_value = "3")]
+        duration: f64,
+
+        /// Resampling filter for fitting: nearest, triangle, catmull-rom, gaussian, lanczos3
+        #[structopt(long = "filter", name = "FILTER", default_value = "lanczos3")]
+        filter: ResizeFilter,
+
+        #[structopt(flatten)]
+        input: InputArgs,
+    },
+
+    /// Writes a catalog of metadata and SHA-256 hashes of the MAG files in a directory
+    #[structopt(name = "catalog")]
+    Catalog {
+        /// The directory to scan for MAG files (recursively)
+        #[structopt(name = "DIR", parse(from_os_str))]
+        dir: PathBuf,
+
+        /// Write one CSV row per file to this path
+        #[structopt(long = "csv", name = "CSV", parse(from_os_str))]
+        csv: Option<PathBuf>,
+
+        /// Insert or update one row per file in this SQLite database (`sqlite` feature)
+        #[structopt(long = "sqlite", name = "DB", parse(from_os_str))]
+        sqlite: Option<PathBuf>,
+    },
+
+    /// Prints groups of visually similar MAG files in a directory, by perceptual hash
+    #[structopt(name = "similar")]
+    Similar {
+        /// The directory to scan for MAG files (recursively)
+        #[structopt(name = "DIR", parse(from_os_str))]
+        dir: PathBuf,
+
+        /// Maximum Hamming distance (0-64) between hashes of similar images
+        #[structopt(short = "t", long = "threshold", default_value = "10")]
+        threshold: u32,
+    },
+
+    /// Prints the paths of MAG files in a directory whose header metadata matches
+    #[structopt(name = "find")]
+    Find {
+        /// The directory to scan for MAG files (recursively)
+        #[structopt(name = "DIR", parse(from_os_str))]
+        dir: PathBuf,
+
+        /// Only files whose user name contains AUTHOR
+        #[structopt(long = "author", name = "AUTHOR")]
+        author: Option<String>,
+
+        /// Only files with this machine code, e.g. PC98 (case-insensitive)
+        #[structopt(long = "machine", name = "MACHINE")]
+        machine: Option<String>,
+
+        /// Only files whose memo contains TEXT
+        #[structopt(long = "memo-contains", name = "TEXT")]
+        memo_contains: Option<String>,
+    },
+
+    /// Serves a read-only web UI and JSON API for the MAG files in a directory
+    #[structopt(name = "serve")]
+    Serve {
+        /// The directory to scan for MAG files (recursively)
+        #[structopt(name = "DIR", parse(from_os_str))]
+        dir: PathBuf,
+
+        /// The address to listen on (use 0.0.0.0 to allow other hosts)
+        #[structopt(long = "bind", default_value = "127.0.0.1")]
+        bind: String,
+
+        /// The port to listen on
+        #[structopt(short = "p", long = "port", default_value = "8080")]
+        port: u16,
+    },
 }
 
 #[cfg(not(windows))]
-fn parse_args() -> Opt {
-    Opt::from_args()
+fn args() -> Vec<OsString> {
+    env::args_os().collect()
 }
 
 #[cfg(windows)]
-fn parse_args() -> Opt {
-    Opt::from_iter(wild::args())
+fn args() -> Vec<OsString> {
+    wild::args_os().collect()
+}
+
+/// Subcommand names; anything else in their place is taken as the arguments of `decode`
+const COMMANDS: &[&str] = &[
+    "decode", "info", "scan", "identify", "check", "doctor", "gallery", "encode", "compare", "remap", "animate",
+    "catalog", "similar", "slideshow", "find", "serve", "visualize", "help",
+];
+
+/// The index of the first argument that is not a global flag
+fn command_position(args: &[OsString]) -> usize {
+    let mut i = 1;
+    while let Some(arg) = args.get(i).and_then(|a| a.to_str()) {
+        match arg {
+            "--log-format" | "--lang" => i += 2,
+            _ if arg.starts_with("--log-format=") || arg.starts_with("--lang=") || arg == "--verbose" => i += 1,
+            _ if arg.len() > 1 && arg.starts_with('-') && arg[1..].bytes().all(|b| b == b'v') => i += 1,
+            _ => break,
+        }
+    }
+    i.min(args.len())
+}
+
+/// Parses the command line, inserting `decode` when no subcommand is given, and the
+/// defaults from `magdecode.toml` for the `decode` options not on the command line
+///
+/// Help and parse errors are printed in `lang` here, and exit the process.
+fn parse_args(mut args: Vec<OsString>, lang: Lang) -> Result<Opt, String> {
+    let at = command_position(&args);
+    let is_flag = |a: &OsStr| ["-h", "--help", "-V", "--version"].iter().any(|f| a == OsStr::new(f));
+    match args.get(at) {
+        Some(arg) if is_flag(arg) || COMMANDS.iter().any(|c| arg == OsStr::new(c)) => (),
+        _ => args.insert(at, "decode".into()),
+    }
+    let matches = get_matches(args.clone(), lang);
+    if let Some(decode) = matches.subcommand_matches("decode") {
+        let given = |arg: &str| decode.occurrences_of(arg) > 0 || decode.occurrences_of(format!("no_{}", arg)) > 0;
+        let skip = |arg: &str| given(arg) || CONFLICTS.iter()
+            .any(|(other, conflicts)| given(other) && conflicts.contains(&arg));
+        let defaults = cmd::config::default_args(&skip)?;
+        if !defaults.is_empty() {
+            args.splice(at + 1..at + 1, defaults);
+            return Ok(Opt::from_clap(&get_matches(args, lang)));
+        }
+    }
+    Ok(Opt::from_clap(&matches))
+}
+
+fn get_matches(args: Vec<OsString>, lang: Lang) -> ArgMatches<'static> {
+    let app = match lang {
+        Lang::En => Opt::clap(),
+        // Unwrapped, so that phrases are translated whole
+        Lang::Ja => Opt::clap().set_term_width(0),
+    };
+    match app.get_matches_from_safe(args) {
+        Ok(matches) => matches,
+        Err(e) if e.kind == ErrorKind::HelpDisplayed || e.kind == ErrorKind::VersionDisplayed => {
+            println!("{}", lang.translate(&e.message));
+            process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("{}", lang.translate(&e.message));
+            process::exit(1);
+        }
+    }
 }
 
 fn main() {
-    match run(parse_args()) {
+    let args = args();
+    let lang = Lang::detect(&args).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    });
+    match parse_args(args, lang).and_then(run) {
         Ok(_) => (),
         Err(e) => {
-            eprintln!("Error: {}", e);
+            eprintln!("{}", lang.translate(&format!("Error: {}", e)));
             process::exit(1);
         }
     }
 }
 
-fn run(opt: Opt) -> Result<(), String> {
-    if opt.verbose > 0 {
-        simple_logger::init().expect("logger init error");
+/// Sets the log level from the `-v` count; `RUST_LOG` directives (e.g. `mag_image_decoder=trace`) take precedence
+fn init_logger(verbose: u8, log_format: LogFormat) {
+    let level = match verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(level).parse_default_env().format_timestamp_millis();
+    if log_format == LogFormat::Jsonl {
+        builder.format(report::format_log_record);
+    }
+    builder.init();
+}
+
+fn copy_mtime(from: &Path, to: &Path) -> io::Result<()> {
+    let mtime = fs::metadata(from)?.modified()?;
+    fs::OpenOptions::new().write(true).open(to)?.set_modified(mtime)
+}
+
+/// `WxH` command line value
+#[derive(Copy, Clone, Debug)]
+struct Dimensions(u32, u32);
+
+impl FromStr for Dimensions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |v: &str| v.trim().parse::<u32>().map_err(|e| format!("'{}': {}", s, e));
+        let mut parts = s.splitn(2, ['x', 'X']);
+        match (parts.next(), parts.next()) {
+            (Some(w), Some(h)) => Ok(Dimensions(parse(w)?, parse(h)?)),
+            _ => Err(format!("'{}': expected WxH", s)),
+        }
+    }
+}
+
+/// A brightness from 0.0 to 1.0
+fn parse_brightness(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(brightness) if (0.0..=1.0).contains(&brightness) => Ok(brightness),
+        _ => Err(format!("invalid brightness '{}' (expected 0 to 1)", s)),
     }
+}
+
+/// A number of jobs, at least 1
+fn parse_jobs(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err(format!("invalid number of jobs '{}' (expected 1 or more)", s)),
+    }
+}
+
+/// A positive number of seconds
+fn parse_seconds(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(secs),
+        _ => Err(format!("invalid duration '{}' (expected a positive number of seconds)", s)),
+    }
+}
+
+/// Output file name with `{stem}`, `{name}` and `{ext}` placeholders
+#[derive(Clone, Debug)]
+struct NameTemplate(Vec<NamePart>);
+
+#[derive(Clone, Debug)]
+enum NamePart {
+    Text(String),
+    Stem,
+    Name,
+    Ext,
+}
+
+impl FromStr for NameTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(['/', '\\']) {
+            return Err(format!("'{}': the name template cannot contain directories", s));
+        }
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').map(|end| start + end)
+                .ok_or_else(|| format!("'{}': unclosed '{{'", s))?;
+            parts.push(NamePart::Text(rest[..start].to_owned()));
+            parts.push(match &rest[start + 1..end] {
+                "stem" => NamePart::Stem,
+                "name" => NamePart::Name,
+                "ext" => NamePart::Ext,
+                other => return Err(format!("'{}': unknown placeholder {{{}}} (expected {{stem}}, {{name}} or {{ext}})", s, other)),
+            });
+            rest = &rest[end + 1..];
+        }
+        parts.push(NamePart::Text(rest.to_owned()));
+        Ok(NameTemplate(parts))
+    }
+}
+
+impl NameTemplate {
+    /// The output file name for `input_file`
+    fn render(&self, input_file: &Path, ext: &str) -> OsString {
+        let mut name = OsString::new();
+        for part in &self.0 {
+            match part {
+                NamePart::Text(text) => name.push(text),
+                NamePart::Stem => name.push(input_file.file_stem().unwrap_or_default()),
+                NamePart::Name => name.push(input_file.file_name().unwrap_or_default()),
+                NamePart::Ext => name.push(ext),
+            }
+        }
+        name
+    }
+}
+
+/// Colors read from a `--palette-file`
+#[derive(Clone, Debug)]
+struct PaletteFile(Vec<Rgb<u8>>);
+
+fn read_palette_file(path: &OsStr) -> Result<PaletteFile, OsString> {
+    palette_file::read(path).map(PaletteFile)
+        .map_err(|e| format!("'{}': {}", Path::new(path).display(), e).into())
+}
+
+fn read_icc_profile(path: &OsStr) -> Result<ColorProfile, OsString> {
+    ColorProfile::from_icc_file(path)
+        .map_err(|e| format!("'{}': {}", Path::new(path).display(), e).into())
+}
+
+impl ParseArgs {
+    fn decode_options(&self) -> DecodeOptions {
+        let mut options = DecodeOptions::new().lenient(self.lenient).detect_charset(self.detect_charset);
+        if let Some(Dimensions(width, height)) = self.max_dimensions {
+            options = options.max_dimensions(width, height);
+        }
+        if let Some(bytes) = self.max_memory {
+            options = options.max_memory(bytes);
+        }
+        if let Some(secs) = self.timeout {
+            options = options.timeout(Duration::from_secs_f64(secs));
+        }
+        options
+    }
+}
+
+impl InputArgs {
+    /// Expands the input arguments (directories with `--recursive`, archives) into the MAG files in them
+    fn expand(&self) -> Result<Vec<Item>, String> {
+        let reads_stdin = |f: &PathBuf| f == Path::new(source::STDIO_PATH);
+        if self.files_from.as_ref().is_some_and(reads_stdin) && self.files.iter().any(reads_stdin) {
+            return Err("--files-from - and '-' input both read stdin".to_owned());
+        }
+        let listed = match &self.files_from {
+            Some(list) => cmd::read_file_list(list, self.null)?,
+            None => Vec::new(),
+        };
+        let options = SourceOptions { recursive: self.recursive };
+        let mut inputs = Vec::new();
+        for file in self.files.iter().chain(&listed) {
+            inputs.extend(source::open(file, &options)?.items()?);
+        }
+        if inputs.is_empty() {
+            return Err("No input file specified.".to_owned());
+        }
+        Ok(inputs)
+    }
+}
+
+fn decode_options(opt: &DecodeArgs) -> DecodeOptions {
+    let transform = Transform {
+        rotation: opt.rotate,
+        flip_horizontal: opt.flip_horizontal,
+        flip_vertical: opt.flip_vertical,
+    };
+    let line_doubling = match opt.scanlines {
+        Some(brightness) => LineDoubling::Scanlines { brightness },
+        None => LineDoubling::Repeat,
+    };
+    let options = opt.parse.decode_options().transform(transform).line_doubling(line_doubling);
+    match &opt.palette_file {
+        Some(PaletteFile(colors)) => options.palette(colors.clone()),
+        None => options,
+    }
+}
+
+fn postprocess(opt: &DecodeArgs, img: RgbImage) -> RgbImage {
+    let img = match opt.upscale {
+        Some(upscaler) => upscaler.apply(&img),
+        None => img,
+    };
+    let img = if opt.crt { CrtFilter::default().apply(&img) } else { img };
+    match opt.resize {
+        Some(Dimensions(width, height)) =>
+            Resize { width, height, mode: opt.resize_mode, filter: opt.filter }.apply(&img),
+        None => img,
+    }
+}
+
+/// With `--outdir`, mirrors the input's path relative to `base` under the output directory
+fn output_path(opt: &DecodeArgs, base: &Path, input_file: &Path) -> PathBuf {
+    let mut output_path = match &opt.out_dir {
+        Some(dir) => {
+            let input_file = cmd::normalize_path(input_file);
+            match input_file.strip_prefix(base) {
+                Ok(relative) => dir.join(relative),
+                Err(_) => dir.join(input_file.file_name().unwrap_or_default()),
+            }
+        }
+        None => input_file.to_owned(),
+    };
+    output_path.set_file_name(opt.name_template.render(input_file, opt.format.extension()));
+    output_path
+}
+
+/// Decodes and encodes to `w` in the selected output format, adding the time taken to `timing`
+fn write_image<W: Write>(opt: &DecodeArgs, decoder: &dyn RetroImage, mut w: W, timing: &mut Timing)
+                         -> mag_image_decoder::Result<()> {
+    let started = Instant::now();
+    let mut encoded = Vec::new();
+    if opt.indexed {
+        let image = decoder.decode_indexed()?;
+        timing.decode += started.elapsed();
+        let started = Instant::now();
+        match opt.format {
+            OutputFormat::Tga => {
+                // TGA has nowhere to record 200-line mode, so the rows are doubled as in RGB output
+                let image = if decoder.info().is_200_line_mode { image.double_rows() } else { image };
+                tga::write_indexed(&image, &mut encoded)?;
+            }
+            OutputFormat::Tiff => tiff::write_indexed(decoder.info(), &image, &mut encoded)?,
+            OutputFormat::Aseprite => aseprite::write_indexed(decoder.info(), &image, &mut encoded)?,
+            _ => write_indexed_png(decoder.info(), &image, &mut encoded)?,
+        }
+        w.write_all(&encoded)?;
+        w.flush()?;
+        timing.encode += started.elapsed();
+    } else {
+        let img = postprocess(opt, decoder.decode()?);
+        timing.decode += started.elapsed();
+        let started = Instant::now();
+        let profile = opt.icc_profile.as_ref().unwrap_or(&ColorProfile::Srgb);
+        opt.format.write_with_profile(&img, profile, &mut encoded)?;
+        w.write_all(&encoded)?;
+        w.flush()?;
+        timing.encode += started.elapsed();
+    }
+    timing.output_bytes += encoded.len() as u64;
+    Ok(())
+}
+
+/// Writes the trailing blocks that are not padding or a comment to `path`, if there are any
+fn extract_appended(decoder: &Decoder, path: &Path) -> Result<(), String> {
+    let trailer = decoder.trailer().map_err(|e| format!("{}", e))?;
+    let payload: Vec<u8> = trailer.iter()
+        .filter(|block| !block.is_recognized())
+        .flat_map(|block| block.data.iter().cloned())
+        .collect();
+    if payload.is_empty() {
+        return Ok(());
+    }
+    info!("appended data: {} bytes -> '{}'", payload.len(), path.display());
+    fs::write(path, &payload).map_err(|e| format!("'{}': failed to save: {}", path.display(), e))
+}
+
+/// Converts one input to `output_path` (stdout if `None`), recording what it produced in `processed`
+fn process_input(opt: &DecodeArgs, input: &Item, output_path: Option<&Path>, processed: &mut Processed)
+                 -> Result<(), String> {
+    info!("input_file: {}", input.name);
+    let started = Instant::now();
+    let (reader, size) = input.open()?;
+    let decoder = Decoder::with_options(reader, decode_options(opt)).map_err(|e| if input.is_stdin() {
+        format!("{}", e)
+    } else {
+        format!("'{}': {}", input.name, e)
+    })?;
+    processed.timing.decode += started.elapsed();
+    processed.timing.input_bytes = size.unwrap_or(0);
+    info!("{:?}", decoder.info());
+    processed.info = Some(decoder.to_info());
+    processed.warnings = decoder.to_warnings();
+
+    let output_path = match output_path {
+        Some(path) => path,
+        None => {
+            let stdout = io::stdout();
+            write_image(opt, &decoder, BufWriter::new(stdout.lock()), &mut processed.timing)
+                .map_err(|e| format!("failed to write: {}", e))?;
+            info!("ok");
+            return Ok(());
+        }
+    };
+    info!("output_path: '{}'", output_path.display());
+    if let Some(dir) = output_path.parent().filter(|d| !d.as_os_str().is_empty() && !d.exists()) {
+        info!("create directory: '{}'", dir.display());
+        fs::create_dir_all(dir).map_err(|e| format!("'{}': {}", dir.display(), e))?;
+    }
+    File::create(output_path).map_err(Into::into)
+        .and_then(|file| write_image(opt, &decoder, BufWriter::new(file), &mut processed.timing))
+        .map_err(|e| format!("'{}': failed to save: {}", output_path.display(), e))?;
+    if let Some(file) = input.file.as_ref().filter(|_| opt.preserve_times) {
+        copy_mtime(file, output_path)
+            .map_err(|e| format!("'{}': failed to set mtime: {}", output_path.display(), e))?;
+    }
+    if opt.extract_appended {
+        extract_appended(&decoder, &output_path.with_extension("appended"))?;
+    }
+    processed.output = Some(output_path.to_owned());
+    info!("ok");
+    Ok(())
+}
+
+/// Decodes `input` and places it on the clipboard, after the post-processing options
+fn copy_to_clipboard(opt: &DecodeArgs, input: &Item) -> Result<(), String> {
+    let (reader, _) = input.open()?;
+    let img = Decoder::with_options(reader, decode_options(opt))
+        .and_then(|decoder| decoder.decode())
+        .map_err(|e| format!("'{}': {}", input.name, e))?;
+    cmd::clipboard::copy(&postprocess(opt, img))
+}
+
+/// Converts every input, with the `decode` options
+fn decode(opt: &DecodeArgs, log_format: LogFormat) -> Result<(), String> {
+    if opt.list {
+        warn!("--list is deprecated, use `magdecode scan` instead");
+        let inputs = opt.input.expand()?;
+        if inputs.iter().any(Item::is_stdin) {
+            return Err("--list cannot be used with stdin input".to_owned());
+        }
+        return cmd::identify::list(&inputs);
+    }
+    let indexed_format = [OutputFormat::Png, OutputFormat::Tga, OutputFormat::Tiff, OutputFormat::Aseprite]
+        .contains(&opt.format);
+    if opt.indexed && (!indexed_format || opt.upscale.is_some() || opt.crt || opt.scanlines.is_some()
+        || opt.resize.is_some() || !decode_options(opt).transform.is_identity()) {
+        return Err("--indexed requires PNG, TGA, TIFF or Aseprite output without --upscale, --crt, --scanlines, --resize, --rotate or --flip-*".to_owned());
+    }
+
+    let inputs = opt.input.expand()?;
+    if opt.clipboard {
+        return match inputs.as_slice() {
+            [input] => copy_to_clipboard(opt, input),
+            _ => Err("--clipboard takes a single input".to_owned()),
+        };
+    }
+    if opt.extract_appended && inputs.iter().any(Item::is_stdin) {
+        return Err("--extract-appended cannot be used with stdin input".to_owned());
+    }
+    let files: Vec<_> = inputs.iter().filter(|f| !f.is_stdin()).map(|f| f.path.clone()).collect();
+    let base = cmd::common_base(&files);
+    info!("base directory: '{}'", base.display());
+
+    let names: Vec<_> = inputs.iter().map(|input| input.name.as_str()).collect();
+    let outputs = inputs.iter()
+        .map(|input| (!input.is_stdin()).then(|| output_path(opt, &base, &input.path)))
+        .collect();
+    let outputs = collision::resolve(&names, outputs, opt.on_collision)?;
 
     if let Some(dir) = &opt.out_dir {
         if !dir.exists() {
@@ -58,28 +938,97 @@ fn run(opt: Opt) -> Result<(), String> {
         }
     }
 
-    if opt.files.is_empty() {
-        Err("No input file specified.".to_owned())
-    } else {
-        for input_file in &opt.files {
-            info!("input_file: {}", input_file.display());
-            let reader = BufReader::new(File::open(input_file)
-                .map_err(|e| format!("'{}': {}", input_file.display(), e))?);
-            let decoder = Decoder::new(reader).map_err(|e| format!("{}", e))?;
-            let header = decoder.info();
-            info!("{:?}", header);
-
-            let mut output_path = match &opt.out_dir {
-                Some(dir) => dir.clone(),
-                None => PathBuf::new()
+    // Workers take the next input until all are taken or one fails; the first failure in input order is reported
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let total = Mutex::new(Timing::default());
+    let first_error = Mutex::new(None);
+    let work = || {
+        while !failed.load(Ordering::SeqCst) {
+            let i = next.fetch_add(1, Ordering::SeqCst);
+            let (input, output) = match (inputs.get(i), outputs.get(i)) {
+                (Some(input), Some(output)) => (input, output),
+                _ => break,
             };
-            output_path.push(input_file);
-            output_path.set_extension("png");
-            info!("output_path: '{}'", output_path.display());
-            let img = decoder.decode().map_err(|e| format!("{}", e))?;
-            img.save(output_path).map_err(|e| format!("failed to save: {}", e))?;
-            info!("ok");
+            let started = Instant::now();
+            let mut processed = Processed::default();
+            let result = process_input(opt, input, output.as_deref(), &mut processed);
+            // Held while reporting, so that the lines of concurrent jobs are not interleaved
+            let mut total = total.lock().unwrap();
+            if log_format == LogFormat::Jsonl {
+                report::print_file_record(&input.name, &processed, &result, started.elapsed());
+            }
+            if opt.timing {
+                eprintln!("timing: {}: {}", input.name, processed.timing);
+                *total += processed.timing;
+            }
+            if let Err(e) = result {
+                failed.store(true, Ordering::SeqCst);
+                let mut first_error = first_error.lock().unwrap();
+                if first_error.as_ref().is_none_or(|(at, _)| i < *at) {
+                    *first_error = Some((i, e));
+                }
+            }
+        }
+    };
+    thread::scope(|scope| {
+        for _ in 1..opt.jobs.min(inputs.len()) {
+            scope.spawn(work);
+        }
+        work();
+    });
+    if let Some((_, e)) = first_error.into_inner().unwrap() {
+        return Err(e);
+    }
+    if opt.timing && inputs.len() > 1 {
+        eprintln!("timing: total ({} files): {}", inputs.len(), total.into_inner().unwrap());
+    }
+    Ok(())
+}
+
+fn run(opt: Opt) -> Result<(), String> {
+    init_logger(opt.verbose, opt.log_format);
+
+    match &opt.command {
+        Command::Decode(args) => decode(args, opt.log_format),
+        Command::Info { color_usage, parse, input } =>
+            cmd::info::run(&input.expand()?, parse.decode_options(), *color_usage),
+        Command::Scan { paths } => {
+            let options = SourceOptions { recursive: true };
+            let mut items = Vec::new();
+            for path in paths {
+                items.extend(source::open(path, &options)?.items()?);
+            }
+            if items.iter().any(Item::is_stdin) {
+                return Err("scan cannot read stdin".to_owned());
+            }
+            cmd::identify::list(&items)
+        }
+        Command::Check { parse, input } => cmd::check::run(&input.expand()?, parse.decode_options()),
+        Command::Doctor { input } => cmd::doctor::run(&input.expand()?),
+        Command::Gallery { dir, out_dir } => cmd::gallery::run(dir, out_dir),
+        Command::Encode { input, output } => cmd::encode::run(input, output),
+        Command::Compare { file, reference } => cmd::compare::run(file, reference),
+        Command::Visualize { file, output } => cmd::visualize::run(file, output),
+        Command::Remap { map, pixels_only, palette_only, input, output } => {
+            let target = match (pixels_only, palette_only) {
+                (true, _) => RemapTarget::Pixels,
+                (_, true) => RemapTarget::Palette,
+                _ => RemapTarget::Both,
+            };
+            cmd::remap::run(input, output, map, target)
+        }
+        Command::Animate { files, output, delay } => cmd::animate::run(files, output, *delay),
+        Command::Slideshow { output, size: Dimensions(width, height), fps, duration, filter, input } => {
+            let slideshow = Slideshow { width: *width, height: *height, fps: *fps, duration: *duration, filter: *filter };
+            cmd::slideshow::run(&input.expand()?, &slideshow, output.as_deref())
+        }
+        Command::Catalog { dir, csv, sqlite } => cmd::catalog::run(dir, csv.as_deref(), sqlite.as_deref()),
+        Command::Similar { dir, threshold } => cmd::similar::run(dir, *threshold),
+        Command::Find { dir, author, machine, memo_contains } => {
+            let filter = Filter { author: author.clone(), machine: machine.clone(), memo_contains: memo_contains.clone() };
+            cmd::find::run(dir, &filter)
         }
-        Ok(())
+        Command::Serve { dir, bind, port } => cmd::serve::run(dir, bind, *port),
     }
 }
@@ -0,0 +1,116 @@
+//! Decoding options
+
+use std::time::Duration;
+
+use image::Rgb;
+
+use crate::transform::Transform;
+
+/// How `Decoder::decode` stretches 200-line images to their display height
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LineDoubling {
+    /// Repeats every row
+    #[default]
+    Repeat,
+    /// Follows every row with a dimmed copy, as 200-line modes looked on a 400-line CRT
+    Scanlines {
+        /// Brightness of the inserted rows, 0.0 (black) to 1.0 (same as `Repeat`)
+        brightness: f32,
+    },
+}
+
+impl LineDoubling {
+    /// The color of the inserted row below a pixel of color `c`
+    pub(crate) fn second_row(self, c: Rgb<u8>) -> Rgb<u8> {
+        match self {
+            LineDoubling::Repeat => c,
+            LineDoubling::Scanlines { brightness } => {
+                let brightness = brightness.clamp(0.0, 1.0);
+                Rgb([c[0], c[1], c[2]].map(|v| (f32::from(v) * brightness).round() as u8))
+            }
+        }
+    }
+}
+
+/// Options for `Decoder::with_options`
+///
+/// Fields may be added in minor releases; start from `DecodeOptions::new` and the builder methods.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// Recovers from damaged files where possible, recording a `Warning` instead of failing
+    pub lenient: bool,
+    /// Maximum width of the decoded image, in pixels
+    pub max_width: Option<u32>,
+    /// Maximum height of the decoded image (after 200-line doubling), in pixels
+    pub max_height: Option<u32>,
+    /// Maximum memory for the input buffer and decoded image buffers, in bytes
+    pub max_memory: Option<usize>,
+    /// Wall-clock limit for a single decode call, after which it fails with `Error::TimedOut`
+    pub timeout: Option<Duration>,
+    /// Rotation and flips applied to the output of `Decoder::decode`
+    pub transform: Transform,
+    /// Tries UTF-8 and EUC-JP for the user name and memo when they are not valid Shift_JIS
+    pub detect_charset: bool,
+    /// How 200-line images are doubled by `Decoder::decode` and `Decoder::decode_onto`
+    pub line_doubling: LineDoubling,
+    /// Colors replacing the embedded palette from index 0, e.g. from `palette_file::read`;
+    /// entries past its end keep the embedded colors
+    pub palette: Option<Vec<Rgb<u8>>>,
+}
+
+impl DecodeOptions {
+    /// Creates the default (strict) options
+    pub fn new() -> DecodeOptions {
+        DecodeOptions::default()
+    }
+
+    /// Sets lenient mode
+    pub fn lenient(mut self, lenient: bool) -> DecodeOptions {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Sets the maximum decoded image dimensions
+    pub fn max_dimensions(mut self, width: u32, height: u32) -> DecodeOptions {
+        self.max_width = Some(width);
+        self.max_height = Some(height);
+        self
+    }
+
+    /// Sets the maximum memory used for decoding
+    pub fn max_memory(mut self, bytes: usize) -> DecodeOptions {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    /// Sets the time limit for each decode call
+    pub fn timeout(mut self, timeout: Duration) -> DecodeOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the rotation and flips of decoded images
+    pub fn transform(mut self, transform: Transform) -> DecodeOptions {
+        self.transform = transform;
+        self
+    }
+
+    /// Sets charset detection for the user name and memo
+    pub fn detect_charset(mut self, detect_charset: bool) -> DecodeOptions {
+        self.detect_charset = detect_charset;
+        self
+    }
+
+    /// Sets how 200-line images are doubled
+    pub fn line_doubling(mut self, line_doubling: LineDoubling) -> DecodeOptions {
+        self.line_doubling = line_doubling;
+        self
+    }
+
+    /// Sets colors replacing the embedded palette
+    pub fn palette(mut self, colors: Vec<Rgb<u8>>) -> DecodeOptions {
+        self.palette = Some(colors);
+        self
+    }
+}
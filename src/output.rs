@@ -0,0 +1,149 @@
+//! Output image formats
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use image::RgbImage;
+
+use crate::error::*;
+use self::png::ColorProfile;
+
+pub mod aseprite;
+#[cfg(feature = "avif")]
+pub mod avif;
+pub mod farbfeld;
+pub mod ico;
+pub mod png;
+pub mod pnm;
+pub mod qoi;
+pub mod tga;
+pub mod tiff;
+pub mod y4m;
+
+/// Output image format
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// PNG
+    Png,
+    /// QOI
+    Qoi,
+    /// Binary PPM
+    Ppm,
+    /// farbfeld
+    Farbfeld,
+    /// Uncompressed 24-bit TGA
+    Tga,
+    /// Uncompressed 24-bit TIFF
+    Tiff,
+    /// Aseprite sprite, RGBA
+    Aseprite,
+    /// Windows icon, at the standard sizes
+    Ico,
+    /// Lossless AVIF (requires the `avif` feature)
+    #[cfg(feature = "avif")]
+    Avif,
+}
+
+const ALL_FORMATS: &[OutputFormat] = &[
+    OutputFormat::Png,
+    OutputFormat::Qoi,
+    OutputFormat::Ppm,
+    OutputFormat::Farbfeld,
+    OutputFormat::Tga,
+    OutputFormat::Tiff,
+    OutputFormat::Aseprite,
+    OutputFormat::Ico,
+    #[cfg(feature = "avif")]
+    OutputFormat::Avif,
+];
+
+impl OutputFormat {
+    /// Returns all formats supported by this build
+    pub fn all() -> &'static [OutputFormat] {
+        ALL_FORMATS
+    }
+
+    /// The format name, as accepted by `from_str`
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Qoi => "qoi",
+            OutputFormat::Ppm => "ppm",
+            OutputFormat::Farbfeld => "farbfeld",
+            OutputFormat::Tga => "tga",
+            OutputFormat::Tiff => "tiff",
+            OutputFormat::Aseprite => "aseprite",
+            OutputFormat::Ico => "ico",
+            #[cfg(feature = "avif")]
+            OutputFormat::Avif => "avif",
+        }
+    }
+
+    /// The file extension, without the leading dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Farbfeld => "ff",
+            OutputFormat::Tiff => "tif",
+            _ => self.name(),
+        }
+    }
+
+    /// Finds the format by the file extension of `path` (case-insensitive)
+    pub fn from_path(path: impl AsRef<Path>) -> Option<OutputFormat> {
+        let ext = path.as_ref().extension()?.to_str()?;
+        ALL_FORMATS.iter().cloned().find(|f| f.extension().eq_ignore_ascii_case(ext) || f.name().eq_ignore_ascii_case(ext))
+    }
+
+    /// Encodes `img` to the writer `w`, tagging PNG output as sRGB
+    pub fn write<W: Write>(self, img: &RgbImage, w: W) -> Result<()> {
+        self.write_with_profile(img, &ColorProfile::Srgb, w)
+    }
+
+    /// Encodes `img` to the writer `w`, tagging PNG output with `profile`
+    ///
+    /// The other formats have no place for a profile and are always sRGB.
+    pub fn write_with_profile<W: Write>(self, img: &RgbImage, profile: &ColorProfile, w: W) -> Result<()> {
+        match self {
+            OutputFormat::Png => self::png::write(img, profile, w),
+            OutputFormat::Qoi => qoi::write(img, w),
+            OutputFormat::Ppm => pnm::write(img, w),
+            OutputFormat::Farbfeld => farbfeld::write(img, w),
+            OutputFormat::Tga => tga::write(img, w),
+            OutputFormat::Tiff => tiff::write(img, w),
+            OutputFormat::Aseprite => aseprite::write(img, w),
+            OutputFormat::Ico => ico::write(img, w),
+            #[cfg(feature = "avif")]
+            OutputFormat::Avif => avif::write(img, w),
+        }
+    }
+
+    /// Encodes `img` to the file `path`
+    pub fn save(self, img: &RgbImage, path: impl AsRef<Path>) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(img, &mut w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ALL_FORMATS.iter().cloned()
+            .find(|f| f.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = ALL_FORMATS.iter().map(|f| f.name()).collect();
+                format!("unsupported format '{}' (expected one of: {})", s, names.join(", "))
+            })
+    }
+}
@@ -0,0 +1,194 @@
+//! Aseprite output
+//!
+//! [Aseprite file format](https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md)
+
+use std::io::Write;
+
+use byteorder::{LittleEndian as LE, WriteBytesExt};
+use image::RgbImage;
+
+use crate::error::*;
+use crate::indexed::IndexedImage;
+use crate::ImageInfo;
+
+const FILE_MAGIC: u16 = 0xa5e0;
+const FRAME_MAGIC: u16 = 0xf1fa;
+const HEADER_SIZE: usize = 128;
+const FRAME_HEADER_SIZE: usize = 16;
+/// The header's layer opacity field is valid
+const FLAG_LAYER_OPACITY: u32 = 1;
+
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_COLOR_PROFILE: u16 = 0x2007;
+const CHUNK_PALETTE: u16 = 0x2019;
+const CHUNK_USER_DATA: u16 = 0x2020;
+
+/// Visible, editable, movement locked, background: index 0 stays opaque
+const BACKGROUND_LAYER_FLAGS: u16 = 1 | 2 | 4 | 8;
+const CEL_RAW: u16 = 0;
+const COLOR_PROFILE_SRGB: u16 = 1;
+const USER_DATA_HAS_TEXT: u32 = 1;
+
+/// Writes a string with its 16-bit length, truncating it to fit
+fn string(out: &mut Vec<u8>, s: &str) -> Result<()> {
+    let mut end = s.len().min(0xffff);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    let s = &s[..end];
+    out.write_u16::<LE>(s.len() as u16)?;
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+fn chunk(out: &mut Vec<u8>, chunk_type: u16, data: &[u8]) -> Result<()> {
+    out.write_u32::<LE>(6 + data.len() as u32)?;
+    out.write_u16::<LE>(chunk_type)?;
+    out.extend_from_slice(data);
+    Ok(())
+}
+
+/// A one-frame, one-layer sprite
+struct Sprite<'a> {
+    width: u32,
+    height: u32,
+    /// 8 (indexed) or 32 (RGBA)
+    depth: u16,
+    /// The cel's pixels, in `depth`
+    pixels: &'a [u8],
+    palette: Option<&'a [[u8; 3]]>,
+    /// 2 for rectangular 200-line pixels
+    pixel_height: u8,
+    /// The sprite's user data text
+    memo: &'a str,
+}
+
+fn write_sprite<W: Write>(mut w: W, sprite: &Sprite) -> Result<()> {
+    let Sprite { width, height, depth, pixels, palette, pixel_height, memo } = *sprite;
+    if width > 0xffff || height > 0xffff {
+        return Err(other_err(format!("{}x{} is too large for Aseprite", width, height)));
+    }
+
+    let mut chunks = Vec::new();
+    let mut num_chunks = 0;
+    let mut profile = Vec::new();
+    profile.write_u16::<LE>(COLOR_PROFILE_SRGB)?;
+    profile.write_u16::<LE>(0)?;
+    profile.write_u32::<LE>(0)?; // gamma, unused without the fixed-gamma flag
+    profile.extend_from_slice(&[0; 8]);
+    chunk(&mut chunks, CHUNK_COLOR_PROFILE, &profile)?;
+    num_chunks += 1;
+
+    if let Some(palette) = palette {
+        let mut data = Vec::new();
+        data.write_u32::<LE>(palette.len() as u32)?;
+        data.write_u32::<LE>(0)?;
+        data.write_u32::<LE>(palette.len() as u32 - 1)?;
+        data.extend_from_slice(&[0; 8]);
+        for &[r, g, b] in palette {
+            data.write_u16::<LE>(0)?; // no name
+            data.extend_from_slice(&[r, g, b, 0xff]);
+        }
+        chunk(&mut chunks, CHUNK_PALETTE, &data)?;
+        num_chunks += 1;
+    }
+    if !memo.is_empty() {
+        // After the palette in the first frame, user data belongs to the sprite
+        let mut data = Vec::new();
+        data.write_u32::<LE>(USER_DATA_HAS_TEXT)?;
+        string(&mut data, memo)?;
+        chunk(&mut chunks, CHUNK_USER_DATA, &data)?;
+        num_chunks += 1;
+    }
+
+    let mut layer = Vec::new();
+    layer.write_u16::<LE>(BACKGROUND_LAYER_FLAGS)?;
+    layer.write_u16::<LE>(0)?; // normal layer
+    layer.write_u16::<LE>(0)?; // child level
+    layer.write_u32::<LE>(0)?; // default width and height, ignored
+    layer.write_u16::<LE>(0)?; // normal blend mode
+    layer.write_u8(0xff)?;
+    layer.extend_from_slice(&[0; 3]);
+    string(&mut layer, "Background")?;
+    chunk(&mut chunks, CHUNK_LAYER, &layer)?;
+    num_chunks += 1;
+
+    let mut cel = Vec::with_capacity(20 + pixels.len());
+    cel.write_u16::<LE>(0)?; // layer index
+    cel.write_i16::<LE>(0)?;
+    cel.write_i16::<LE>(0)?;
+    cel.write_u8(0xff)?;
+    cel.write_u16::<LE>(CEL_RAW)?;
+    cel.write_i16::<LE>(0)?; // z-index
+    cel.extend_from_slice(&[0; 5]);
+    cel.write_u16::<LE>(width as u16)?;
+    cel.write_u16::<LE>(height as u16)?;
+    cel.extend_from_slice(pixels);
+    chunk(&mut chunks, CHUNK_CEL, &cel)?;
+    num_chunks += 1;
+
+    let frame_size = FRAME_HEADER_SIZE + chunks.len();
+    let mut out = Vec::with_capacity(HEADER_SIZE + frame_size);
+    out.write_u32::<LE>((HEADER_SIZE + frame_size) as u32)?;
+    out.write_u16::<LE>(FILE_MAGIC)?;
+    out.write_u16::<LE>(1)?; // frames
+    out.write_u16::<LE>(width as u16)?;
+    out.write_u16::<LE>(height as u16)?;
+    out.write_u16::<LE>(depth)?;
+    out.write_u32::<LE>(FLAG_LAYER_OPACITY)?;
+    out.write_u16::<LE>(100)?; // speed, deprecated
+    out.write_u32::<LE>(0)?;
+    out.write_u32::<LE>(0)?;
+    out.write_u8(0)?; // transparent index, unused by background layers
+    out.extend_from_slice(&[0; 3]);
+    out.write_u16::<LE>(palette.map_or(0, |p| p.len() as u16))?; // 0 means 256
+    out.write_u8(1)?;
+    out.write_u8(pixel_height)?;
+    out.write_i16::<LE>(0)?;
+    out.write_i16::<LE>(0)?;
+    out.write_u16::<LE>(16)?;
+    out.write_u16::<LE>(16)?;
+    out.resize(HEADER_SIZE, 0);
+
+    out.write_u32::<LE>(frame_size as u32)?;
+    out.write_u16::<LE>(FRAME_MAGIC)?;
+    out.write_u16::<LE>(num_chunks)?;
+    out.write_u16::<LE>(100)?; // duration, in milliseconds
+    out.extend_from_slice(&[0; 2]);
+    out.write_u32::<LE>(u32::from(num_chunks))?;
+    out.extend_from_slice(&chunks);
+    w.write_all(&out)?;
+    Ok(())
+}
+
+/// Encodes `img` as an RGBA Aseprite sprite.
+pub fn write<W: Write>(img: &RgbImage, w: W) -> Result<()> {
+    let pixels: Vec<u8> = img.pixels().flat_map(|p| [p[0], p[1], p[2], 0xff]).collect();
+    write_sprite(w, &Sprite {
+        width: img.width(),
+        height: img.height(),
+        depth: 32,
+        pixels: &pixels,
+        palette: None,
+        pixel_height: 1,
+        memo: "",
+    })
+}
+
+/// Encodes `image` as an indexed Aseprite sprite with the exact palette
+///
+/// 200-line images keep their rows, with a 1:2 pixel ratio. The memo becomes the
+/// sprite's user data.
+pub fn write_indexed<W: Write>(info: &ImageInfo, image: &IndexedImage, w: W) -> Result<()> {
+    let palette: Vec<[u8; 3]> = image.palette.iter().map(|c| c.0).collect();
+    write_sprite(w, &Sprite {
+        width: image.width,
+        height: image.height,
+        depth: 8,
+        pixels: &image.pixels,
+        palette: Some(&palette),
+        pixel_height: if info.is_200_line_mode { 2 } else { 1 },
+        memo: &info.memo,
+    })
+}
@@ -0,0 +1,23 @@
+//! Lossless AVIF output
+
+use std::io::Write;
+
+use image::RgbImage;
+use ravif::{BitDepth, ColorModel, Encoder, Img, RGB8};
+
+use crate::error::*;
+
+/// Encodes `img` as lossless AVIF.
+///
+/// Quality 100 selects quantizer 0, and the RGB color model avoids the lossy YCbCr conversion.
+pub fn write<W: Write>(img: &RgbImage, mut w: W) -> Result<()> {
+    let pixels: Vec<RGB8> = img.pixels().map(|p| RGB8::new(p[0], p[1], p[2])).collect();
+    let encoded = Encoder::new()
+        .with_quality(100.)
+        .with_bit_depth(BitDepth::Eight)
+        .with_internal_color_model(ColorModel::RGB)
+        .encode_rgb(Img::new(&pixels[..], img.width() as usize, img.height() as usize))
+        .map_err(|e| Error::EncodingError(e.to_string()))?;
+    w.write_all(&encoded.avif_file)?;
+    Ok(())
+}
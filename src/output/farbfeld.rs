@@ -0,0 +1,26 @@
+//! farbfeld output
+//!
+//! [farbfeld specification](https://tools.suckless.org/farbfeld/)
+
+use std::io::Write;
+
+use byteorder::{BigEndian as BE, WriteBytesExt};
+use image::RgbImage;
+
+use crate::error::*;
+
+/// Encodes `img` as farbfeld (16-bit RGBA, fully opaque).
+pub fn write<W: Write>(img: &RgbImage, mut w: W) -> Result<()> {
+    w.write_all(b"farbfeld")?;
+    w.write_u32::<BE>(img.width())?;
+    w.write_u32::<BE>(img.height())?;
+    let mut out = Vec::with_capacity(img.width() as usize * img.height() as usize * 8);
+    for p in img.pixels() {
+        for &c in &p.0 {
+            out.write_u16::<BE>(u16::from(c) * 257)?;
+        }
+        out.write_u16::<BE>(0xffff)?;
+    }
+    w.write_all(&out)?;
+    Ok(())
+}
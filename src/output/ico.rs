@@ -0,0 +1,71 @@
+//! ICO output, at the standard icon sizes
+
+use std::io::Write;
+
+use byteorder::{LittleEndian as LE, WriteBytesExt};
+use image::RgbImage;
+use png::{BitDepth, ColorType};
+
+use super::png::{encoding_err, ColorProfile};
+use crate::error::*;
+use crate::resize::{Resize, ResizeFilter, ResizeMode};
+
+/// Icon sizes written, in pixels; those larger than the image are skipped, except the smallest
+pub const ICON_SIZES: &[u32] = &[16, 32, 48, 256];
+
+const ICONDIR_SIZE: u32 = 6;
+const ICONDIRENTRY_SIZE: u32 = 16;
+
+/// `img` fitted into a transparent `size` x `size` square, as RGBA PNG
+///
+/// Downscaling averages with a triangle filter, which keeps dithered areas flat
+/// without the ringing of sharper filters.
+fn icon_png(img: &RgbImage, size: u32) -> Result<Vec<u8>> {
+    let filter = if size >= img.width().max(img.height()) { ResizeFilter::Nearest } else { ResizeFilter::Triangle };
+    let fitted = Resize { width: size, height: size, mode: ResizeMode::Fit, filter }.apply(img);
+    let left = (size - fitted.width()) / 2;
+    let top = (size - fitted.height()) / 2;
+    let mut rgba = vec![0u8; size as usize * size as usize * 4];
+    for (x, y, p) in fitted.enumerate_pixels() {
+        let i = ((top + y) * size + left + x) as usize * 4;
+        rgba[i..i + 4].copy_from_slice(&[p[0], p[1], p[2], 0xff]);
+    }
+
+    let mut out = Vec::new();
+    let mut encoder = ColorProfile::Srgb.png_encoder(&mut out, size, size)?;
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(encoding_err)?;
+    writer.write_image_data(&rgba).map_err(encoding_err)?;
+    writer.finish().map_err(encoding_err)?;
+    Ok(out)
+}
+
+/// Encodes `img` as an ICO of PNG images at each of `ICON_SIZES` up to the image size.
+pub fn write<W: Write>(img: &RgbImage, mut w: W) -> Result<()> {
+    let longest = img.width().max(img.height());
+    let icons = ICON_SIZES.iter().enumerate()
+        .filter(|&(i, &size)| i == 0 || size <= longest)
+        .map(|(_, &size)| Ok((size, icon_png(img, size)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut out = Vec::new();
+    out.write_u16::<LE>(0)?;
+    out.write_u16::<LE>(1)?; // icon
+    out.write_u16::<LE>(icons.len() as u16)?;
+    let mut offset = ICONDIR_SIZE + ICONDIRENTRY_SIZE * icons.len() as u32;
+    for (size, png) in &icons {
+        let dimension = if *size >= 256 { 0 } else { *size as u8 }; // 0 means 256
+        out.extend_from_slice(&[dimension, dimension, 0, 0]);
+        out.write_u16::<LE>(1)?; // color planes
+        out.write_u16::<LE>(32)?; // bits per pixel
+        out.write_u32::<LE>(png.len() as u32)?;
+        out.write_u32::<LE>(offset)?;
+        offset += png.len() as u32;
+    }
+    for (_, png) in &icons {
+        out.extend_from_slice(png);
+    }
+    w.write_all(&out)?;
+    Ok(())
+}
@@ -0,0 +1,14 @@
+//! Binary PPM (P6) output
+
+use std::io::Write;
+
+use image::RgbImage;
+
+use crate::error::*;
+
+/// Encodes `img` as binary PPM (P6, maxval 255).
+pub fn write<W: Write>(img: &RgbImage, mut w: W) -> Result<()> {
+    write!(w, "P6\n{} {}\n255\n", img.width(), img.height())?;
+    w.write_all(img)?;
+    Ok(())
+}
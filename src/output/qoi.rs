@@ -0,0 +1,82 @@
+//! QOI output
+//!
+//! [QOI specification](https://qoiformat.org/qoi-specification.pdf)
+
+use std::io::Write;
+
+use byteorder::{BigEndian as BE, WriteBytesExt};
+use image::RgbImage;
+
+use crate::error::*;
+
+const QOI_OP_INDEX: u8 = 0x00;
+const QOI_OP_DIFF: u8 = 0x40;
+const QOI_OP_LUMA: u8 = 0x80;
+const QOI_OP_RUN: u8 = 0xc0;
+const QOI_OP_RGB: u8 = 0xfe;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+const MAX_RUN: u8 = 62;
+
+fn index_position([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+/// Encodes `img` as QOI (3 channels, sRGB).
+pub fn write<W: Write>(img: &RgbImage, mut w: W) -> Result<()> {
+    w.write_all(b"qoif")?;
+    w.write_u32::<BE>(img.width())?;
+    w.write_u32::<BE>(img.height())?;
+    w.write_all(&[3, 0])?;
+
+    let mut out = Vec::new();
+    // Slots start as transparent black, as in the decoder; a black pixel must not match them
+    let mut index = [[0u8; 4]; 64];
+    let mut prev = [0u8; 3];
+    let mut run = 0u8;
+
+    for p in img.pixels() {
+        let px = [p[0], p[1], p[2]];
+        if px == prev {
+            run += 1;
+            if run == MAX_RUN {
+                out.push(QOI_OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(QOI_OP_RUN | (run - 1));
+            run = 0;
+        }
+
+        let rgba = [px[0], px[1], px[2], 0xff];
+        let pos = index_position(rgba);
+        if index[pos] == rgba {
+            out.push(QOI_OP_INDEX | pos as u8);
+        } else {
+            index[pos] = rgba;
+            let dr = px[0].wrapping_sub(prev[0]) as i8;
+            let dg = px[1].wrapping_sub(prev[1]) as i8;
+            let db = px[2].wrapping_sub(prev[2]) as i8;
+            let dr_dg = dr.wrapping_sub(dg);
+            let db_dg = db.wrapping_sub(dg);
+
+            if (-2..=1).contains(&dr) && (-2..=1).contains(&dg) && (-2..=1).contains(&db) {
+                out.push(QOI_OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8);
+            } else if (-32..=31).contains(&dg) && (-8..=7).contains(&dr_dg) && (-8..=7).contains(&db_dg) {
+                out.push(QOI_OP_LUMA | (dg + 32) as u8);
+                out.push(((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8);
+            } else {
+                out.extend_from_slice(&[QOI_OP_RGB, px[0], px[1], px[2]]);
+            }
+        }
+        prev = px;
+    }
+    if run > 0 {
+        out.push(QOI_OP_RUN | (run - 1));
+    }
+
+    w.write_all(&out)?;
+    w.write_all(&END_MARKER)?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+//! TGA output
+//!
+//! [Truevision TGA specification](http://www.dca.fee.unicamp.br/~martino/disciplinas/ea978/tgaffs.pdf)
+
+use std::io::Write;
+
+use byteorder::{LittleEndian as LE, WriteBytesExt};
+use image::RgbImage;
+
+use crate::error::*;
+use crate::indexed::IndexedImage;
+
+const TYPE_COLOR_MAPPED: u8 = 1;
+const TYPE_TRUE_COLOR: u8 = 2;
+/// Image descriptor bit for top-to-bottom row order
+const TOP_LEFT_ORIGIN: u8 = 0x20;
+/// TGA 2.0 footer, with no extension area or developer directory
+const FOOTER: &[u8; 26] = b"\0\0\0\0\0\0\0\0TRUEVISION-XFILE.\0";
+
+fn write_header<W: Write>(w: &mut W, image_type: u8, color_map_len: u16, width: u32, height: u32, depth: u8)
+                          -> Result<()> {
+    if width > 0xffff || height > 0xffff {
+        return Err(other_err(format!("{}x{} is too large for TGA", width, height)));
+    }
+    w.write_u8(0)?; // no image ID
+    w.write_u8(if color_map_len > 0 { 1 } else { 0 })?;
+    w.write_u8(image_type)?;
+    w.write_u16::<LE>(0)?;
+    w.write_u16::<LE>(color_map_len)?;
+    w.write_u8(if color_map_len > 0 { 24 } else { 0 })?;
+    w.write_u16::<LE>(0)?;
+    w.write_u16::<LE>(0)?;
+    w.write_u16::<LE>(width as u16)?;
+    w.write_u16::<LE>(height as u16)?;
+    w.write_u8(depth)?;
+    w.write_u8(TOP_LEFT_ORIGIN)?;
+    Ok(())
+}
+
+/// Encodes `img` as uncompressed 24-bit TGA.
+pub fn write<W: Write>(img: &RgbImage, mut w: W) -> Result<()> {
+    write_header(&mut w, TYPE_TRUE_COLOR, 0, img.width(), img.height(), 24)?;
+    let mut out = Vec::with_capacity(img.width() as usize * img.height() as usize * 3 + FOOTER.len());
+    for p in img.pixels() {
+        let [r, g, b] = p.0;
+        out.extend_from_slice(&[b, g, r]);
+    }
+    out.extend_from_slice(FOOTER);
+    w.write_all(&out)?;
+    Ok(())
+}
+
+/// Encodes `image` as uncompressed color-mapped TGA, keeping the palette indices.
+pub fn write_indexed<W: Write>(image: &IndexedImage, mut w: W) -> Result<()> {
+    write_header(&mut w, TYPE_COLOR_MAPPED, image.palette.len() as u16, image.width, image.height, 8)?;
+    let mut out = Vec::with_capacity(image.palette.len() * 3 + image.pixels.len() + FOOTER.len());
+    for p in &image.palette {
+        let [r, g, b] = p.0;
+        out.extend_from_slice(&[b, g, r]);
+    }
+    out.extend_from_slice(&image.pixels);
+    out.extend_from_slice(FOOTER);
+    w.write_all(&out)?;
+    Ok(())
+}
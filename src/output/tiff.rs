@@ -0,0 +1,181 @@
+//! Baseline TIFF output
+//!
+//! [TIFF 6.0 specification](https://www.itu.int/itudoc/itu-t/com16/tiff-fx/docs/tiff6.pdf)
+
+use std::io::Write;
+
+use byteorder::{LittleEndian as LE, WriteBytesExt};
+use image::RgbImage;
+
+use crate::error::*;
+use crate::indexed::IndexedImage;
+use crate::ImageInfo;
+
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const IMAGE_DESCRIPTION: u16 = 270;
+const STRIP_OFFSETS: u16 = 273;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const ROWS_PER_STRIP: u16 = 278;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const X_RESOLUTION: u16 = 282;
+const Y_RESOLUTION: u16 = 283;
+const RESOLUTION_UNIT: u16 = 296;
+const ARTIST: u16 = 315;
+const HOST_COMPUTER: u16 = 316;
+const COLOR_MAP: u16 = 320;
+
+const PHOTOMETRIC_RGB: u16 = 2;
+const PHOTOMETRIC_PALETTE: u16 = 3;
+const RESOLUTION_UNIT_INCH: u16 = 2;
+const DPI: u32 = 72;
+
+/// Size of the header before the IFD
+const HEADER_LEN: u32 = 8;
+
+struct Field {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    /// The value, little-endian; fields longer than 4 bytes are written after the IFD
+    value: Vec<u8>,
+}
+
+fn shorts(tag: u16, values: &[u16]) -> Field {
+    let mut value = Vec::with_capacity(values.len() * 2);
+    for &v in values {
+        value.write_u16::<LE>(v).unwrap();
+    }
+    Field { tag, field_type: SHORT, count: values.len() as u32, value }
+}
+
+fn long(tag: u16, v: u32) -> Field {
+    Field { tag, field_type: LONG, count: 1, value: v.to_le_bytes().to_vec() }
+}
+
+fn rational(tag: u16, numerator: u32, denominator: u32) -> Field {
+    let mut value = numerator.to_le_bytes().to_vec();
+    value.extend_from_slice(&denominator.to_le_bytes());
+    Field { tag, field_type: RATIONAL, count: 1, value }
+}
+
+/// An ASCII field, or `None` for an empty string
+///
+/// The text is written as UTF-8, which readers in practice accept in ASCII fields.
+fn ascii(tag: u16, text: &str) -> Option<Field> {
+    if text.is_empty() {
+        return None;
+    }
+    let mut value = text.replace('\0', "").into_bytes();
+    value.push(0);
+    Some(Field { tag, field_type: ASCII, count: value.len() as u32, value })
+}
+
+/// Writes a little-endian TIFF of one strip, `data`, with `fields` plus the strip fields
+fn write_tiff<W: Write>(mut w: W, width: u32, height: u32, mut fields: Vec<Field>, data: &[u8]) -> Result<()> {
+    fields.push(long(IMAGE_WIDTH, width));
+    fields.push(long(IMAGE_LENGTH, height));
+    fields.push(shorts(COMPRESSION, &[1]));
+    fields.push(long(ROWS_PER_STRIP, height));
+    fields.push(long(STRIP_BYTE_COUNTS, data.len() as u32));
+    fields.push(long(STRIP_OFFSETS, 0));
+    fields.sort_by_key(|f| f.tag);
+
+    let ifd_len = 2 + fields.len() as u32 * 12 + 4;
+    let extra_len: u32 = fields.iter().filter(|f| f.value.len() > 4).map(|f| (f.value.len() as u32 + 1) & !1).sum();
+    let data_offset = HEADER_LEN + ifd_len + extra_len;
+    if u64::from(data_offset) + data.len() as u64 > u64::from(u32::MAX) {
+        return Err(other_err(format!("{}x{} is too large for TIFF", width, height)));
+    }
+    for f in fields.iter_mut().filter(|f| f.tag == STRIP_OFFSETS) {
+        f.value = data_offset.to_le_bytes().to_vec();
+    }
+
+    let mut out = Vec::with_capacity(data_offset as usize + data.len());
+    out.extend_from_slice(b"II");
+    out.write_u16::<LE>(42)?;
+    out.write_u32::<LE>(HEADER_LEN)?;
+    out.write_u16::<LE>(fields.len() as u16)?;
+    let mut extra = Vec::with_capacity(extra_len as usize);
+    for f in &fields {
+        out.write_u16::<LE>(f.tag)?;
+        out.write_u16::<LE>(f.field_type)?;
+        out.write_u32::<LE>(f.count)?;
+        if f.value.len() > 4 {
+            out.write_u32::<LE>(HEADER_LEN + ifd_len + extra.len() as u32)?;
+            extra.extend_from_slice(&f.value);
+            if extra.len() % 2 != 0 {
+                extra.push(0);
+            }
+        } else {
+            let mut inline = [0; 4];
+            inline[..f.value.len()].copy_from_slice(&f.value);
+            out.extend_from_slice(&inline);
+        }
+    }
+    out.write_u32::<LE>(0)?; // no next IFD
+    out.extend_from_slice(&extra);
+    out.extend_from_slice(data);
+    w.write_all(&out)?;
+    Ok(())
+}
+
+/// Encodes `img` as uncompressed 24-bit RGB TIFF.
+pub fn write<W: Write>(img: &RgbImage, w: W) -> Result<()> {
+    let fields = vec![
+        shorts(BITS_PER_SAMPLE, &[8, 8, 8]),
+        shorts(PHOTOMETRIC_INTERPRETATION, &[PHOTOMETRIC_RGB]),
+        shorts(SAMPLES_PER_PIXEL, &[3]),
+        rational(X_RESOLUTION, DPI, 1),
+        rational(Y_RESOLUTION, DPI, 1),
+        shorts(RESOLUTION_UNIT, &[RESOLUTION_UNIT_INCH]),
+    ];
+    write_tiff(w, img.width(), img.height(), fields, img)
+}
+
+/// Encodes `image` as uncompressed palette TIFF, keeping the palette indices
+///
+/// 16-color images are written with 4 bits per sample and 256-color ones with 8.
+/// The memo, user name and machine code go in the ImageDescription, Artist and
+/// HostComputer fields. 200-line images keep their rows and have half the
+/// vertical resolution instead.
+pub fn write_indexed<W: Write>(info: &ImageInfo, image: &IndexedImage, w: W) -> Result<()> {
+    let bits: u16 = if image.palette.len() <= 16 { 4 } else { 8 };
+    let mut color_map = vec![0u16; 3 << bits];
+    let entries = 1 << bits;
+    for (i, c) in image.palette.iter().enumerate().take(entries) {
+        for (channel, &v) in c.0.iter().enumerate() {
+            color_map[channel * entries + i] = u16::from(v) * 257;
+        }
+    }
+
+    let mut fields = vec![
+        shorts(BITS_PER_SAMPLE, &[bits]),
+        shorts(PHOTOMETRIC_INTERPRETATION, &[PHOTOMETRIC_PALETTE]),
+        shorts(SAMPLES_PER_PIXEL, &[1]),
+        rational(X_RESOLUTION, DPI, 1),
+        rational(Y_RESOLUTION, if info.is_200_line_mode { DPI / 2 } else { DPI }, 1),
+        shorts(RESOLUTION_UNIT, &[RESOLUTION_UNIT_INCH]),
+        shorts(COLOR_MAP, &color_map),
+    ];
+    fields.extend(ascii(IMAGE_DESCRIPTION, &info.memo));
+    fields.extend(ascii(ARTIST, info.user_name.trim_end()));
+    fields.extend(ascii(HOST_COMPUTER, info.machine_code.trim_end()));
+
+    let data: Vec<u8> = if bits == 4 {
+        image.pixels.chunks(image.width as usize)
+            .flat_map(|row| row.chunks(2).map(|p| (p[0] & 0x0f) << 4 | p.get(1).map_or(0, |&q| q & 0x0f)))
+            .collect()
+    } else {
+        image.pixels.clone()
+    };
+    write_tiff(w, image.width, image.height, fields, &data)
+}
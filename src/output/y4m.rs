@@ -0,0 +1,76 @@
+//! YUV4MPEG2 (Y4M) video output, for piping decoded images into video encoders
+//!
+//! [YUV4MPEG2](https://wiki.multimedia.cx/index.php/YUV4MPEG2)
+
+use std::io::Write;
+
+use image::RgbImage;
+
+use crate::error::*;
+
+/// BT.601 limited-range Y'CbCr of an sRGB pixel, as floats
+fn ycbcr([r, g, b]: [u8; 3]) -> (f32, f32, f32) {
+    let (r, g, b) = (f32::from(r) / 255.0, f32::from(g) / 255.0, f32::from(b) / 255.0);
+    let y = 16.0 + 65.481 * r + 128.553 * g + 24.966 * b;
+    let cb = 128.0 - 37.797 * r - 74.203 * g + 112.0 * b;
+    let cr = 128.0 + 112.0 * r - 93.786 * g - 18.214 * b;
+    (y, cb, cr)
+}
+
+/// A Y4M stream of 4:2:0 frames of one size, with square pixels
+pub struct Y4mWriter<W: Write> {
+    w: W,
+    width: u32,
+    height: u32,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Writes the stream header for `width` x `height` frames (both even) at `fps` frames per second
+    pub fn new(mut w: W, width: u32, height: u32, fps: u32) -> Result<Y4mWriter<W>> {
+        if width == 0 || height == 0 || !width.is_multiple_of(2) || !height.is_multiple_of(2) {
+            return Err(other_err(format!("{}x{}: Y4M 4:2:0 frames need even, non-zero dimensions", width, height)));
+        }
+        if fps == 0 {
+            return Err(other_err("the frame rate must be positive"));
+        }
+        writeln!(w, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg XYSCSS=420JPEG XCOLORRANGE=LIMITED",
+                 width, height, fps)?;
+        Ok(Y4mWriter { w, width, height })
+    }
+
+    /// Writes `img`, which must be the stream's size, as `count` identical frames
+    ///
+    /// With one image per slide, `count` is its duration in frames.
+    pub fn write_frames(&mut self, img: &RgbImage, count: u32) -> Result<()> {
+        if img.dimensions() != (self.width, self.height) {
+            return Err(other_err(format!("{}x{} frame in a {}x{} stream",
+                                         img.width(), img.height(), self.width, self.height)));
+        }
+        let (width, height) = (self.width as usize, self.height as usize);
+        let mut frame = Vec::with_capacity(6 + width * height * 3 / 2);
+        frame.extend_from_slice(b"FRAME\n");
+        let samples: Vec<_> = img.pixels().map(|p| ycbcr(p.0)).collect();
+        frame.extend(samples.iter().map(|&(y, _, _)| y.round() as u8));
+        for plane in 0..2 {
+            for cy in 0..height / 2 {
+                for cx in 0..width / 2 {
+                    let sum: f32 = [(0, 0), (1, 0), (0, 1), (1, 1)].iter()
+                        .map(|&(dx, dy)| samples[(cy * 2 + dy) * width + cx * 2 + dx])
+                        .map(|(_, cb, cr)| if plane == 0 { cb } else { cr })
+                        .sum();
+                    frame.push((sum / 4.0).round() as u8);
+                }
+            }
+        }
+        for _ in 0..count {
+            self.w.write_all(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer
+    pub fn into_inner(mut self) -> Result<W> {
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
@@ -0,0 +1,100 @@
+//! Palettes and nearest-color lookup
+
+use std::collections::HashMap;
+
+use image::{Rgb, RgbImage};
+
+use crate::IndexedImage;
+
+/// How `Palette::nearest_by` measures the difference between two colors
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ColorDistance {
+    /// Euclidean distance in RGB
+    #[default]
+    Euclidean,
+    /// Euclidean distance in RGB weighted by the mean red level ("redmean"), closer to perceived differences
+    Redmean,
+}
+
+impl ColorDistance {
+    /// The squared distance between `a` and `b`, scaled to compare other distances of the same metric
+    pub fn squared(self, a: Rgb<u8>, b: Rgb<u8>) -> u32 {
+        let d = |c: usize| (i32::from(a[c]) - i32::from(b[c])).pow(2) as u32;
+        match self {
+            ColorDistance::Euclidean => d(0) + d(1) + d(2),
+            ColorDistance::Redmean => {
+                let mean_r = (u32::from(a[0]) + u32::from(b[0])) / 2;
+                (512 + mean_r) * d(0) + 1024 * d(1) + (767 - mean_r) * d(2)
+            }
+        }
+    }
+}
+
+// TODO: 最初に並べ替えておく
+/// A palette of up to 256 colors
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    /// Colors in file order, G, R, B
+    grb_colors: Vec<u8>,
+}
+
+impl Palette {
+    /// Creates a palette of `colors`; entries past 256 are dropped
+    pub fn new(colors: &[Rgb<u8>]) -> Palette {
+        Palette { grb_colors: colors.iter().take(256).flat_map(|&Rgb([r, g, b])| vec![g, r, b]).collect() }
+    }
+
+    /// Creates a palette of GRB bytes, as stored in MAG files
+    pub(crate) fn from_grb(grb_colors: Vec<u8>) -> Palette {
+        Palette { grb_colors }
+    }
+
+    /// The number of colors
+    pub fn len(&self) -> usize {
+        self.grb_colors.len() / 3
+    }
+
+    /// Whether there are no colors
+    pub fn is_empty(&self) -> bool {
+        self.grb_colors.is_empty()
+    }
+
+    /// Gets the color at `index`
+    pub fn rgb(&self, index: u8) -> Rgb<u8> {
+        let index = index as usize * 3;
+        let g = self.grb_colors[index];
+        let r = self.grb_colors[index + 1];
+        let b = self.grb_colors[index + 2];
+        Rgb([r, g, b])
+    }
+
+    /// Gets all colors
+    pub fn colors(&self) -> Vec<Rgb<u8>> {
+        (0..self.len()).map(|i| self.rgb(i as u8)).collect()
+    }
+
+    /// The index of the color closest to `rgb` in Euclidean distance; ties go to the lowest index
+    ///
+    /// Panics if the palette is empty.
+    pub fn nearest(&self, rgb: Rgb<u8>) -> u8 {
+        self.nearest_by(rgb, ColorDistance::Euclidean)
+    }
+
+    /// The index of the color closest to `rgb` by `distance`; ties go to the lowest index
+    ///
+    /// Panics if the palette is empty.
+    pub fn nearest_by(&self, rgb: Rgb<u8>, distance: ColorDistance) -> u8 {
+        (0..self.len())
+            .min_by_key(|&i| distance.squared(self.rgb(i as u8), rgb))
+            .expect("nearest color in an empty palette") as u8
+    }
+
+    /// Maps each pixel of `img` to its nearest color, e.g. to encode an RGB image with this palette
+    pub fn quantize(&self, img: &RgbImage, distance: ColorDistance) -> IndexedImage {
+        let mut cache = HashMap::new();
+        let pixels = img.pixels()
+            .map(|&rgb| *cache.entry(rgb.0).or_insert_with(|| self.nearest_by(rgb, distance)))
+            .collect();
+        IndexedImage { width: img.width(), height: img.height(), pixels, palette: self.colors() }
+    }
+}
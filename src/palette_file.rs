@@ -0,0 +1,80 @@
+//! External palette files, for `DecodeOptions::palette`
+
+use std::fs;
+use std::path::Path;
+
+use image::Rgb;
+
+use crate::error::*;
+use crate::{DecodeOptions, Decoder, MAGIC_NUMBER};
+
+/// Size of an Adobe Color Table, with the optional color count and transparent index trailer
+const ACT_LEN: usize = 256 * 3;
+const ACT_TRAILER_LEN: usize = 4;
+
+fn parse_channel(s: &str, line: usize) -> Result<u8> {
+    s.parse().map_err(|_| Error::InvalidFormat(format!("line {}: invalid color component '{}'", line, s)))
+}
+
+/// Parses `r g b` lines, with 1-based line numbers for errors, up to `limit` colors
+fn parse_rgb_lines<'a>(lines: impl Iterator<Item = (usize, &'a str)>, limit: usize) -> Result<Vec<Rgb<u8>>> {
+    let mut colors = Vec::new();
+    for (i, line) in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().take(3).collect();
+        if fields.len() < 3 {
+            return Err(Error::InvalidFormat(format!("line {}: expected 'r g b'", i + 1)));
+        }
+        colors.push(Rgb([parse_channel(fields[0], i + 1)?, parse_channel(fields[1], i + 1)?,
+                         parse_channel(fields[2], i + 1)?]));
+        if colors.len() == limit {
+            break;
+        }
+    }
+    Ok(colors)
+}
+
+/// Parses a palette: a MAG file's palette, JASC-PAL, GIMP palette (`.gpl`) or Adobe Color Table (`.act`)
+///
+/// The format is recognized from the content.
+pub fn parse(bytes: &[u8]) -> Result<Vec<Rgb<u8>>> {
+    let colors = if bytes.starts_with(MAGIC_NUMBER) {
+        Decoder::with_options(bytes, DecodeOptions::new().lenient(true))?.palette()
+    } else if bytes.starts_with(b"JASC-PAL") || bytes.starts_with(b"GIMP Palette") {
+        let text = std::str::from_utf8(bytes).map_err(|_| Error::InvalidFormat("palette is not UTF-8".into()))?;
+        let mut lines = text.lines().enumerate().skip(1);
+        if bytes.starts_with(b"JASC-PAL") {
+            let (_, _version) = lines.next().ok_or_else(|| Error::InvalidFormat("JASC-PAL truncated".into()))?;
+            let (i, count) = lines.next().ok_or_else(|| Error::InvalidFormat("JASC-PAL truncated".into()))?;
+            let count: usize = count.trim().parse()
+                .map_err(|_| Error::InvalidFormat(format!("line {}: invalid color count '{}'", i + 1, count)))?;
+            parse_rgb_lines(lines, count)?
+        } else {
+            let lines = lines.filter(|(_, line)| !line.starts_with("Name:") && !line.starts_with("Columns:"));
+            parse_rgb_lines(lines, 256)?
+        }
+    } else if bytes.len() == ACT_LEN || bytes.len() == ACT_LEN + ACT_TRAILER_LEN {
+        let count = match bytes.get(ACT_LEN..ACT_LEN + 2) {
+            Some(&[hi, lo]) => match u16::from_be_bytes([hi, lo]) as usize {
+                count @ 1..=256 => count,
+                _ => 256,
+            },
+            _ => 256,
+        };
+        bytes.chunks(3).take(count).map(|c| Rgb([c[0], c[1], c[2]])).collect()
+    } else {
+        return Err(Error::InvalidFormat("not a MAG, JASC-PAL, GIMP or ACT palette".into()));
+    };
+    if colors.is_empty() {
+        return Err(Error::InvalidFormat("palette has no colors".into()));
+    }
+    Ok(colors)
+}
+
+/// Reads a palette file; see `parse`
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<Rgb<u8>>> {
+    parse(&fs::read(path)?)
+}
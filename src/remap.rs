@@ -0,0 +1,92 @@
+//! Palette remapping
+
+use std::str::FromStr;
+
+use crate::IndexedImage;
+use crate::error::*;
+
+/// What a `PaletteMap` rewrites
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RemapTarget {
+    /// Moves entries: pixel indices and palette entries both follow the map, so the image looks the same
+    Both,
+    /// Rewrites pixel indices only, e.g. pixels were saved with swapped indices
+    Pixels,
+    /// Rewrites palette entries only, e.g. the palette was saved in the wrong order
+    Palette,
+}
+
+/// A permutation of palette indices, as `old:new` pairs
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaletteMap {
+    table: Vec<u8>,
+}
+
+impl PaletteMap {
+    /// Creates a map from `(old, new)` pairs; the pairs must permute the indices they mention
+    pub fn new(pairs: &[(u8, u8)]) -> Result<PaletteMap> {
+        let mut table: Vec<u8> = (0..=255).collect();
+        let mut olds = [false; 256];
+        let mut news = [false; 256];
+        for &(old, new) in pairs {
+            if olds[old as usize] {
+                return Err(other_err(format!("index {} is mapped twice", old)));
+            }
+            if news[new as usize] {
+                return Err(other_err(format!("index {} is a target twice", new)));
+            }
+            olds[old as usize] = true;
+            news[new as usize] = true;
+            table[old as usize] = new;
+        }
+        if let Some(i) = (0..256).find(|&i| olds[i] != news[i]) {
+            return Err(other_err(format!("not a permutation: index {} is only on one side of the map", i)));
+        }
+        Ok(PaletteMap { table })
+    }
+
+    /// The new index of `old`
+    pub fn get(&self, old: u8) -> u8 {
+        self.table[old as usize]
+    }
+
+    /// Applies the map to `image`
+    pub fn apply(&self, image: &IndexedImage, target: RemapTarget) -> Result<IndexedImage> {
+        let num_colors = image.palette.len();
+        if let Some(i) = (0..256).find(|&i| self.table[i] as usize != i && (i >= num_colors || self.table[i] as usize >= num_colors)) {
+            return Err(other_err(format!("index {} is outside the {}-entry palette", i, num_colors)));
+        }
+
+        let mut out = image.clone();
+        if target != RemapTarget::Palette {
+            for p in out.pixels.iter_mut() {
+                *p = self.get(*p);
+            }
+        }
+        if target != RemapTarget::Pixels {
+            for (old, color) in image.palette.iter().enumerate() {
+                out.palette[self.get(old as u8) as usize] = *color;
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl FromStr for PaletteMap {
+    type Err = Error;
+
+    /// Parses `old:new,old:new,...`
+    fn from_str(s: &str) -> Result<Self> {
+        let pairs = s.split(',')
+            .map(|pair| {
+                let mut parts = pair.splitn(2, ':');
+                let parse = |v: Option<&str>| v.and_then(|v| v.trim().parse::<u8>().ok());
+                match (parse(parts.next()), parse(parts.next())) {
+                    (Some(old), Some(new)) => Ok((old, new)),
+                    _ => Err(other_err(format!("invalid mapping '{}', expected old:new", pair))),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        PaletteMap::new(&pairs)
+    }
+}
@@ -0,0 +1,128 @@
+//! Resizing to a target output size
+
+use std::fmt;
+use std::str::FromStr;
+
+use image::{FilterType, imageops, RgbImage};
+
+/// How the image is fitted to the target size
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ResizeMode {
+    /// Scale to fit within the target, keeping the aspect ratio
+    Fit,
+    /// Scale to cover the target, keeping the aspect ratio, then crop the center
+    Fill,
+}
+
+impl FromStr for ResizeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fit" => Ok(ResizeMode::Fit),
+            "fill" => Ok(ResizeMode::Fill),
+            _ => Err(format!("unsupported resize mode '{}' (expected one of: fit, fill)", s)),
+        }
+    }
+}
+
+impl fmt::Display for ResizeMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ResizeMode::Fit => "fit",
+            ResizeMode::Fill => "fill",
+        })
+    }
+}
+
+/// Resampling filter
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ResizeFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Gaussian,
+    Lanczos3,
+}
+
+const ALL_FILTERS: &[ResizeFilter] = &[
+    ResizeFilter::Nearest,
+    ResizeFilter::Triangle,
+    ResizeFilter::CatmullRom,
+    ResizeFilter::Gaussian,
+    ResizeFilter::Lanczos3,
+];
+
+impl ResizeFilter {
+    /// The filter name, as accepted by `from_str`
+    pub fn name(self) -> &'static str {
+        match self {
+            ResizeFilter::Nearest => "nearest",
+            ResizeFilter::Triangle => "triangle",
+            ResizeFilter::CatmullRom => "catmull-rom",
+            ResizeFilter::Gaussian => "gaussian",
+            ResizeFilter::Lanczos3 => "lanczos3",
+        }
+    }
+
+    fn filter_type(self) -> FilterType {
+        match self {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Gaussian => FilterType::Gaussian,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+impl FromStr for ResizeFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ALL_FILTERS.iter().cloned().find(|f| f.name() == s).ok_or_else(|| {
+            let names: Vec<_> = ALL_FILTERS.iter().map(|f| f.name()).collect();
+            format!("unsupported filter '{}' (expected one of: {})", s, names.join(", "))
+        })
+    }
+}
+
+impl fmt::Display for ResizeFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Target size, mode and filter
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Resize {
+    pub width: u32,
+    pub height: u32,
+    pub mode: ResizeMode,
+    pub filter: ResizeFilter,
+}
+
+impl Resize {
+    /// Returns the resized image
+    pub fn apply(&self, img: &RgbImage) -> RgbImage {
+        let (src_width, src_height) = img.dimensions();
+        let scale_x = f64::from(self.width) / f64::from(src_width);
+        let scale_y = f64::from(self.height) / f64::from(src_height);
+        let scale = match self.mode {
+            ResizeMode::Fit => scale_x.min(scale_y),
+            ResizeMode::Fill => scale_x.max(scale_y),
+        };
+        let width = ((f64::from(src_width) * scale).round() as u32).max(1);
+        let height = ((f64::from(src_height) * scale).round() as u32).max(1);
+        let mut resized = imageops::resize(img, width, height, self.filter.filter_type());
+        match self.mode {
+            ResizeMode::Fit => resized,
+            ResizeMode::Fill => {
+                let crop_width = self.width.min(width);
+                let crop_height = self.height.min(height);
+                imageops::crop(&mut resized, (width - crop_width) / 2, (height - crop_height) / 2,
+                               crop_width, crop_height).to_image()
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! A common interface to retro image decoders
+//!
+//! MAG is the only format decoded so far. Decoders for others (MAKI01, Pi, PIC)
+//! implement `RetroImage` too, so code written against it handles them all.
+
+use image::RgbImage;
+
+use crate::error::*;
+use crate::indexed::IndexedImage;
+use crate::{Decoder, ImageInfo};
+
+/// A parsed image, decoded on demand
+pub trait RetroImage {
+    /// The format name, e.g. `MAG`
+    fn format_name(&self) -> &'static str;
+
+    /// Gets metadata
+    fn info(&self) -> &ImageInfo;
+
+    /// Decodes to RGB, with non-square pixels already corrected
+    fn decode(&self) -> Result<RgbImage>;
+
+    /// Decodes to palette indices, as stored
+    fn decode_indexed(&self) -> Result<IndexedImage>;
+
+    /// The machine the image was made on, e.g. `PC98`, without padding
+    fn machine(&self) -> &str {
+        self.info().machine_code.trim_end()
+    }
+}
+
+impl RetroImage for Decoder {
+    fn format_name(&self) -> &'static str {
+        "MAG"
+    }
+
+    fn info(&self) -> &ImageInfo {
+        Decoder::info(self)
+    }
+
+    fn decode(&self) -> Result<RgbImage> {
+        Decoder::decode(self)
+    }
+
+    fn decode_indexed(&self) -> Result<IndexedImage> {
+        Decoder::decode_indexed(self)
+    }
+}
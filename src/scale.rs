@@ -0,0 +1,179 @@
+//! Pixel-art-aware upscalers
+
+use std::fmt;
+use std::str::FromStr;
+
+use image::{ImageBuffer, Rgb, RgbImage};
+
+/// Pixel-art-aware upscaling algorithm
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Upscaler {
+    /// Scale2x (AdvMAME2x), 2x
+    Scale2x,
+    /// Scale3x (AdvMAME3x), 3x
+    Scale3x,
+    /// hq2x-style interpolation, 2x
+    ///
+    /// This applies the per-corner YUV threshold blending of hq2x,
+    /// not the full 256-pattern lookup table of the reference implementation.
+    Hq2x,
+}
+
+const ALL_UPSCALERS: &[Upscaler] = &[Upscaler::Scale2x, Upscaler::Scale3x, Upscaler::Hq2x];
+
+impl Upscaler {
+    /// The algorithm name, as accepted by `from_str`
+    pub fn name(self) -> &'static str {
+        match self {
+            Upscaler::Scale2x => "scale2x",
+            Upscaler::Scale3x => "scale3x",
+            Upscaler::Hq2x => "hq2x",
+        }
+    }
+
+    /// The scale factor
+    pub fn factor(self) -> u32 {
+        match self {
+            Upscaler::Scale2x | Upscaler::Hq2x => 2,
+            Upscaler::Scale3x => 3,
+        }
+    }
+
+    /// Returns the upscaled image
+    pub fn apply(self, img: &RgbImage) -> RgbImage {
+        let n = self.factor();
+        let (width, height) = img.dimensions();
+        let mut out: RgbImage = ImageBuffer::new(width * n, height * n);
+        for y in 0..height {
+            for x in 0..width {
+                let block = neighbours(img, x, y);
+                let pixels = match self {
+                    Upscaler::Scale2x => scale2x(&block).to_vec(),
+                    Upscaler::Scale3x => scale3x(&block).to_vec(),
+                    Upscaler::Hq2x => hq2x(&block).to_vec(),
+                };
+                for (i, p) in pixels.into_iter().enumerate() {
+                    let i = i as u32;
+                    out.put_pixel(x * n + i % n, y * n + i / n, p);
+                }
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Upscaler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Upscaler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ALL_UPSCALERS.iter().cloned()
+            .find(|u| u.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let names: Vec<_> = ALL_UPSCALERS.iter().map(|u| u.name()).collect();
+                format!("unsupported upscaler '{}' (expected one of: {})", s, names.join(", "))
+            })
+    }
+}
+
+/// The 3x3 block around (x, y) in row-major order, edges clamped:
+/// ```text
+/// A B C
+/// D E F
+/// G H I
+/// ```
+fn neighbours(img: &RgbImage, x: u32, y: u32) -> [Rgb<u8>; 9] {
+    let (width, height) = img.dimensions();
+    let xs = [x.saturating_sub(1), x, (x + 1).min(width - 1)];
+    let ys = [y.saturating_sub(1), y, (y + 1).min(height - 1)];
+    let mut block = [Rgb([0, 0, 0]); 9];
+    for (j, &sy) in ys.iter().enumerate() {
+        for (i, &sx) in xs.iter().enumerate() {
+            block[j * 3 + i] = img[(sx, sy)];
+        }
+    }
+    block
+}
+
+fn scale2x(&[_, b, _, d, e, f, _, h, _]: &[Rgb<u8>; 9]) -> [Rgb<u8>; 4] {
+    if b != h && d != f {
+        [
+            if d == b { d } else { e },
+            if b == f { f } else { e },
+            if d == h { d } else { e },
+            if h == f { f } else { e },
+        ]
+    } else {
+        [e; 4]
+    }
+}
+
+fn scale3x(&[a, b, c, d, e, f, g, h, i]: &[Rgb<u8>; 9]) -> [Rgb<u8>; 9] {
+    if b != h && d != f {
+        [
+            if d == b { d } else { e },
+            if (d == b && e != c) || (b == f && e != a) { b } else { e },
+            if b == f { f } else { e },
+            if (d == b && e != g) || (d == h && e != a) { d } else { e },
+            e,
+            if (b == f && e != i) || (h == f && e != c) { f } else { e },
+            if d == h { d } else { e },
+            if (d == h && e != i) || (h == f && e != g) { h } else { e },
+            if h == f { f } else { e },
+        ]
+    } else {
+        [e; 9]
+    }
+}
+
+fn yuv(p: Rgb<u8>) -> [i32; 3] {
+    let [r, g, b] = [i32::from(p[0]), i32::from(p[1]), i32::from(p[2])];
+    [
+        (299 * r + 587 * g + 114 * b) / 1000,
+        (-169 * r - 331 * g + 500 * b) / 1000 + 128,
+        (500 * r - 419 * g - 81 * b) / 1000 + 128,
+    ]
+}
+
+/// The hqx color difference test (thresholds Y: 48, U: 7, V: 6)
+fn differs(p: Rgb<u8>, q: Rgb<u8>) -> bool {
+    let (p, q) = (yuv(p), yuv(q));
+    (p[0] - q[0]).abs() > 48 || (p[1] - q[1]).abs() > 7 || (p[2] - q[2]).abs() > 6
+}
+
+fn blend(colors: &[(Rgb<u8>, u32)]) -> Rgb<u8> {
+    let total: u32 = colors.iter().map(|&(_, w)| w).sum();
+    let mut out = [0u8; 3];
+    for (i, v) in out.iter_mut().enumerate() {
+        let sum: u32 = colors.iter().map(|&(p, w)| u32::from(p[i]) * w).sum();
+        *v = ((sum + total / 2) / total) as u8;
+    }
+    Rgb(out)
+}
+
+/// One output corner from the center `e`, its two orthogonal neighbours `p`, `q` and the diagonal `r`
+fn hq2x_corner(e: Rgb<u8>, p: Rgb<u8>, q: Rgb<u8>, r: Rgb<u8>) -> Rgb<u8> {
+    match (differs(e, p), differs(e, q)) {
+        (false, false) => blend(&[(e, 2), (p, 1), (q, 1)]),
+        // blend toward the neighbour on the same side of the edge
+        (true, false) => blend(&[(e, 3), (q, 1)]),
+        (false, true) => blend(&[(e, 3), (p, 1)]),
+        (true, true) if !differs(p, q) => blend(&[(e, 2), (p, 3), (q, 3)]),
+        (true, true) if differs(e, r) => blend(&[(e, 6), (p, 1), (q, 1)]),
+        (true, true) => e,
+    }
+}
+
+fn hq2x(&[a, b, c, d, e, f, g, h, i]: &[Rgb<u8>; 9]) -> [Rgb<u8>; 4] {
+    [
+        hq2x_corner(e, b, d, a),
+        hq2x_corner(e, b, f, c),
+        hq2x_corner(e, h, d, g),
+        hq2x_corner(e, h, f, i),
+    ]
+}
@@ -0,0 +1,58 @@
+//! Cheap format detection, for file-type dispatchers
+
+use std::io::{self, Read};
+
+use crate::{geometry, ColorMode, HEADER_SIZE, MAGIC_NUMBER};
+
+/// Bytes read by `sniff_reader`, enough for the header after a memo of typical length
+pub const SNIFF_LEN: usize = 4096;
+
+/// Offset of the memo, after the magic number, machine code and user name
+const MEMO_OFFSET: usize = 31;
+const MEMO_END: u8 = 0x1a;
+
+/// An image format recognized by `sniff`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FormatKind {
+    /// MAG (MAKI02)
+    Mag,
+}
+
+impl FormatKind {
+    /// The format name, e.g. `MAG`
+    pub fn name(self) -> &'static str {
+        match self {
+            FormatKind::Mag => "MAG",
+        }
+    }
+}
+
+/// Recognizes the format from the start of a file, without parsing it fully
+///
+/// `bytes` can be a prefix of the file. The MAG header is checked for a valid
+/// geometry when it is within `bytes`; otherwise the magic number decides.
+pub fn sniff(bytes: &[u8]) -> Option<FormatKind> {
+    if !bytes.starts_with(MAGIC_NUMBER) {
+        return None;
+    }
+    let header_offset = match bytes.iter().skip(MEMO_OFFSET).position(|&b| b == MEMO_END) {
+        Some(memo_len) => MEMO_OFFSET + memo_len + 1,
+        None => return Some(FormatKind::Mag),
+    };
+    let header = match bytes.get(header_offset..header_offset + HEADER_SIZE as usize) {
+        Some(header) => header,
+        None => return Some(FormatKind::Mag),
+    };
+    let color_mode = if header[3] & 0x80 != 0 { ColorMode::Palette256 } else { ColorMode::Palette16 };
+    let u16_at = |i: usize| u16::from_le_bytes([header[i], header[i + 1]]);
+    let plausible = header[0] == 0 && geometry(color_mode, u16_at(4), u16_at(6), u16_at(8), u16_at(10)).is_ok();
+    if plausible { Some(FormatKind::Mag) } else { None }
+}
+
+/// Reads up to `SNIFF_LEN` bytes from `reader` and recognizes them with `sniff`
+pub fn sniff_reader<R: Read>(reader: R) -> io::Result<Option<FormatKind>> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    reader.take(SNIFF_LEN as u64).read_to_end(&mut buf)?;
+    Ok(sniff(&buf))
+}
@@ -0,0 +1,248 @@
+//! Synthetic MAG files for tests and fuzz seeds (requires the `dev-tools` feature)
+//!
+//! Files are built from pseudo-random pixels, repeating as chosen by a `FlagPattern`,
+//! packed by the encoder, and can be damaged on purpose with `Corruption`s.
+//!
+//! # Examples
+//! ```
+//! use mag_image_decoder::Decoder;
+//! use mag_image_decoder::synth::{FlagPattern, SyntheticMag};
+//!
+//! let mag = SyntheticMag::new(64, 32).num_colors(256).pattern(FlagPattern::Random).build().unwrap();
+//! let decoded = Decoder::new(&mag.bytes[..]).unwrap().decode_indexed().unwrap();
+//! assert_eq!(decoded, mag.image);
+//! ```
+
+use byteorder::{LittleEndian as LE, WriteBytesExt};
+use image::Rgb;
+
+use crate::encoder::encode_streams;
+use crate::error::*;
+use crate::indexed::IndexedImage;
+use crate::{COPY_VECTORS, HEADER_SIZE, MAGIC_NUMBER};
+
+/// How the generated pixels repeat, and so which copies the encoder finds
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FlagPattern {
+    /// Only literal pixels
+    Literal,
+    /// The given copy vector (1-15) wherever its source is inside the image, literals elsewhere
+    Copy(u8),
+    /// Random nibbles, limited to copies whose source is inside the image
+    Random,
+}
+
+/// A header section, for `Corruption::SectionOffset`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Section {
+    FlagA,
+    FlagB,
+    Pixel,
+}
+
+/// Damage applied to the file after it is built
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Corruption {
+    /// Cuts the file to this many bytes
+    Truncate(usize),
+    /// Overwrites the magic number (what is left of it after truncation)
+    BadMagic,
+    /// XORs the byte at `offset` with `mask`
+    XorByte { offset: usize, mask: u8 },
+    /// Sets the header offset of a section, relative to the header
+    SectionOffset { section: Section, offset: u32 },
+}
+
+/// A generated file and the image it decodes to (before corruption)
+#[derive(Clone, Debug)]
+pub struct Synthetic {
+    pub bytes: Vec<u8>,
+    pub image: IndexedImage,
+}
+
+/// Settings of a synthetic MAG file
+#[derive(Clone, Debug)]
+pub struct SyntheticMag {
+    width: u16,
+    height: u16,
+    num_colors: u16,
+    is_200_line_mode: bool,
+    pattern: FlagPattern,
+    seed: u64,
+    corruptions: Vec<Corruption>,
+}
+
+/// xorshift64*, so the output is reproducible without a dependency
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn byte(&mut self) -> u8 {
+        (self.next() >> 56) as u8
+    }
+}
+
+impl SyntheticMag {
+    /// A `width` x `height`, 16-color, literal-only image
+    pub fn new(width: u16, height: u16) -> SyntheticMag {
+        SyntheticMag {
+            width,
+            height,
+            num_colors: 16,
+            is_200_line_mode: false,
+            pattern: FlagPattern::Literal,
+            seed: 1,
+            corruptions: Vec::new(),
+        }
+    }
+
+    /// Sets the number of colors, 16 or 256
+    pub fn num_colors(mut self, num_colors: u16) -> SyntheticMag {
+        self.num_colors = num_colors;
+        self
+    }
+
+    /// Sets 200-line mode
+    pub fn is_200_line_mode(mut self, is_200_line_mode: bool) -> SyntheticMag {
+        self.is_200_line_mode = is_200_line_mode;
+        self
+    }
+
+    /// Sets the flag pattern
+    pub fn pattern(mut self, pattern: FlagPattern) -> SyntheticMag {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Sets the seed of the pseudo-random palette, pixels and flags
+    pub fn seed(mut self, seed: u64) -> SyntheticMag {
+        self.seed = seed;
+        self
+    }
+
+    /// Adds damage applied after building, in order
+    pub fn corrupt(mut self, corruption: Corruption) -> SyntheticMag {
+        self.corruptions.push(corruption);
+        self
+    }
+
+    /// Builds the file
+    pub fn build(&self) -> Result<Synthetic> {
+        let pixel_unit = if self.num_colors == 256 { 4 } else { 8 };
+        if self.num_colors != 16 && self.num_colors != 256 {
+            return Err(other_err(format!("unsupported number of colors: {}", self.num_colors)));
+        }
+        if self.width == 0 || self.height == 0 || !self.width.is_multiple_of(pixel_unit) {
+            return Err(other_err(format!("{}x{} is not a positive multiple of the {}-pixel unit",
+                                         self.width, self.height, pixel_unit)));
+        }
+        if let FlagPattern::Copy(nibble) = self.pattern {
+            if nibble == 0 || nibble > 15 {
+                return Err(other_err(format!("copy vector {} is not in 1-15", nibble)));
+            }
+        }
+
+        let mut rng = Rng(self.seed.max(1));
+        let palette: Vec<u8> = (0..self.num_colors as usize * 3).map(|_| rng.byte()).collect();
+        let image = IndexedImage {
+            width: u32::from(self.width),
+            height: u32::from(self.height),
+            pixels: self.pixels(&mut rng, pixel_unit as usize),
+            palette: palette.chunks(3).map(|grb| Rgb([grb[1], grb[0], grb[2]])).collect(),
+        };
+        let (flag_a, flag_b, pixel_data) = encode_streams(&image, pixel_unit as usize);
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC_NUMBER);
+        bytes.extend_from_slice(b"SYNT");
+        bytes.extend_from_slice(b"synthetic          ");
+        bytes.extend_from_slice(b"generated\x1a");
+        let flag_a_offset = HEADER_SIZE + palette.len() as u32;
+        let flag_b_offset = flag_a_offset + flag_a.len() as u32;
+        let pixel_offset = flag_b_offset + flag_b.len() as u32;
+        let screen_mode = if self.num_colors == 256 { 0x80 } else { 0 } | self.is_200_line_mode as u8;
+        let header_start = bytes.len();
+        bytes.extend_from_slice(&[0, 0, 0, screen_mode]);
+        for v in &[0, 0, self.width - 1, self.height - 1] {
+            bytes.write_u16::<LE>(*v)?;
+        }
+        for v in &[flag_a_offset, flag_b_offset, flag_b.len() as u32, pixel_offset, pixel_data.len() as u32] {
+            bytes.write_u32::<LE>(*v)?;
+        }
+        bytes.extend_from_slice(&palette);
+        bytes.extend_from_slice(&flag_a);
+        bytes.extend_from_slice(&flag_b);
+        bytes.extend_from_slice(&pixel_data);
+
+        for corruption in &self.corruptions {
+            apply(&mut bytes, header_start, *corruption);
+        }
+
+        Ok(Synthetic { bytes, image })
+    }
+
+    /// Generates the pixels, copying units where the pattern asks for a copy from inside the image
+    fn pixels(&self, rng: &mut Rng, pixel_unit: usize) -> Vec<u8> {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let copy_pixels = pixel_unit / 2;
+        let mut pixels = vec![0u8; width * height];
+
+        for y in 0..height {
+            for x in (0..width).step_by(copy_pixels) {
+                let wanted = match self.pattern {
+                    FlagPattern::Literal => 0,
+                    FlagPattern::Copy(nibble) => nibble,
+                    FlagPattern::Random => rng.byte() % 16,
+                };
+                let (dx, dy) = COPY_VECTORS[wanted as usize];
+                if wanted != 0 && dy <= y && dx * copy_pixels <= x {
+                    let src = (y - dy) * width + x - dx * copy_pixels;
+                    pixels.copy_within(src..src + copy_pixels, y * width + x);
+                } else {
+                    for i in 0..2 {
+                        let b = rng.byte();
+                        if self.num_colors == 256 {
+                            pixels[y * width + x + i] = b;
+                        } else {
+                            pixels[y * width + x + 2 * i] = b >> 4;
+                            pixels[y * width + x + 2 * i + 1] = b & 0x0f;
+                        }
+                    }
+                }
+            }
+        }
+        pixels
+    }
+}
+
+fn apply(bytes: &mut Vec<u8>, header_start: usize, corruption: Corruption) {
+    match corruption {
+        Corruption::Truncate(len) => bytes.truncate(len),
+        Corruption::BadMagic => {
+            let len = bytes.len().min(4);
+            bytes[..len].copy_from_slice(&b"XXXX"[..len]);
+        }
+        Corruption::XorByte { offset, mask } => {
+            if let Some(b) = bytes.get_mut(offset) {
+                *b ^= mask;
+            }
+        }
+        Corruption::SectionOffset { section, offset } => {
+            let field = header_start + match section {
+                Section::FlagA => 12,
+                Section::FlagB => 16,
+                Section::Pixel => 24,
+            };
+            if let Some(field) = bytes.get_mut(field..field + 4) {
+                field.copy_from_slice(&offset.to_le_bytes());
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Per-unit record of how an image was decoded, for format research and visualization
+
+use image::{Rgb, RgbImage};
+
+/// How the pixels of a unit were produced
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Read from the pixel section
+    Literal,
+    /// Copied from earlier pixels
+    Copy {
+        /// The flag nibble, 1-15, selecting the copy vector
+        vector: u8,
+        /// How far left of the unit the source is, in pixels
+        dx: u32,
+        /// How far above the unit the source is, in lines
+        dy: u32,
+    },
+}
+
+/// One flag nibble's worth of pixels: 4 in 16-color images, 2 in 256-color ones
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Unit {
+    /// The x position of the leftmost pixel, in the stored (undoubled) image
+    pub x: u32,
+    /// The line
+    pub y: u32,
+    /// The number of pixels
+    pub width: u32,
+    pub action: Action,
+}
+
+/// Heatmap color of literal units
+pub const LITERAL_COLOR: Rgb<u8> = Rgb([230, 40, 40]);
+/// Heatmap color of the nearest copies; farther ones fade towards `FAR_COPY_COLOR`
+pub const NEAR_COPY_COLOR: Rgb<u8> = Rgb([120, 255, 220]);
+/// Heatmap color of copies from 32 or more pixels away
+pub const FAR_COPY_COLOR: Rgb<u8> = Rgb([20, 30, 150]);
+
+/// Distance at which copies get `FAR_COPY_COLOR`
+const FAR_DISTANCE: f64 = 32.0;
+
+fn copy_color(dx: u32, dy: u32) -> Rgb<u8> {
+    let distance = f64::from(dx).hypot(f64::from(dy)).max(1.0);
+    let t = (distance.ln() / FAR_DISTANCE.ln()).min(1.0);
+    let mut color = [0; 3];
+    for (c, (&near, &far)) in color.iter_mut().zip(NEAR_COPY_COLOR.0.iter().zip(&FAR_COPY_COLOR.0)) {
+        *c = (f64::from(near) + (f64::from(far) - f64::from(near)) * t).round() as u8;
+    }
+    Rgb(color)
+}
+
+/// Renders `units` of a `width`x`height` image as a false-color map: literals in
+/// `LITERAL_COLOR`, copies shaded by the distance to their source
+pub fn heatmap(units: &[Unit], width: u32, height: u32) -> RgbImage {
+    let mut img = RgbImage::new(width, height);
+    for unit in units {
+        let color = match unit.action {
+            Action::Literal => LITERAL_COLOR,
+            Action::Copy { dx, dy, .. } => copy_color(dx, dy),
+        };
+        for x in unit.x..(unit.x + unit.width).min(width) {
+            if unit.y < height {
+                img.put_pixel(x, unit.y, color);
+            }
+        }
+    }
+    img
+}
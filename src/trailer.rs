@@ -0,0 +1,120 @@
+//! Data appended after the image sections
+
+use encoding_rs::SHIFT_JIS;
+
+/// Kind of a block found after the image sections
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrailerKind {
+    /// `0x00` / `0x1A` filler, e.g. from transfers padding to a record size
+    Padding,
+    /// Shift_JIS text, e.g. an extended comment
+    Comment(String),
+    /// Another MAG image (`MAKI0`)
+    Mag,
+    /// Standard MIDI file (`MThd`)
+    Midi,
+    /// RIFF container such as WAV (`RIFF`)
+    Riff,
+    /// Anything else
+    Unknown,
+}
+
+/// A block of trailing data
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrailerBlock {
+    /// Offset from the start of the file
+    pub offset: usize,
+    pub kind: TrailerKind,
+    /// The raw bytes of the block
+    pub data: Vec<u8>,
+}
+
+impl TrailerBlock {
+    /// Whether the block is fully represented by its kind (padding or a comment)
+    pub fn is_recognized(&self) -> bool {
+        matches!(self.kind, TrailerKind::Padding | TrailerKind::Comment(_))
+    }
+}
+
+impl TrailerKind {
+    /// Short lowercase name of the kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrailerKind::Padding => "padding",
+            TrailerKind::Comment(_) => "comment",
+            TrailerKind::Mag => "mag",
+            TrailerKind::Midi => "midi",
+            TrailerKind::Riff => "riff",
+            TrailerKind::Unknown => "unknown",
+        }
+    }
+}
+
+const SIGNATURES: [(&[u8], TrailerKind); 3] = [
+    (b"MAKI0", TrailerKind::Mag),
+    (b"MThd", TrailerKind::Midi),
+    (b"RIFF", TrailerKind::Riff),
+];
+
+fn signature_at(data: &[u8]) -> Option<TrailerKind> {
+    SIGNATURES.iter().find(|(sig, _)| data.starts_with(sig)).map(|(_, kind)| kind.clone())
+}
+
+/// Length of the RIFF block at the start of `data`, if its size field fits
+fn riff_len(data: &[u8]) -> Option<usize> {
+    let size = u32::from_le_bytes([*data.get(4)?, *data.get(5)?, *data.get(6)?, *data.get(7)?]) as usize;
+    size.checked_add(8).filter(|&len| len <= data.len())
+}
+
+fn classify_untagged(data: &[u8]) -> TrailerKind {
+    let is_filler = |b: &u8| *b == 0x00 || *b == 0x1a;
+    let (first, last) = match (data.iter().position(|b| !is_filler(b)), data.iter().rposition(|b| !is_filler(b))) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return TrailerKind::Padding,
+    };
+    let text = &data[first..=last];
+    let is_text_byte = |b: &u8| *b >= 0x20 || *b == b'\t' || *b == b'\r' || *b == b'\n';
+    if text.iter().all(is_text_byte) {
+        if let Some(text) = SHIFT_JIS.decode_without_bom_handling_and_without_replacement(text) {
+            return TrailerKind::Comment(text.into_owned());
+        }
+    }
+    TrailerKind::Unknown
+}
+
+/// Splits `data`, found at `offset` in the file, into blocks at known signatures.
+pub fn parse(data: &[u8], offset: usize) -> Vec<TrailerBlock> {
+    let mut blocks = Vec::new();
+    let mut push = |start: usize, end: usize, kind: Option<TrailerKind>| {
+        if start < end {
+            let data = &data[start..end];
+            let kind = kind.unwrap_or_else(|| classify_untagged(data));
+            blocks.push(TrailerBlock { offset: offset + start, kind, data: data.to_vec() });
+        }
+    };
+
+    let mut start = 0;
+    let mut tagged = None;
+    let mut i = 0;
+    while i < data.len() {
+        match signature_at(&data[i..]) {
+            Some(kind) => {
+                push(start, i, tagged.take());
+                if kind == TrailerKind::Riff {
+                    if let Some(len) = riff_len(&data[i..]) {
+                        push(i, i + len, Some(kind));
+                        i += len;
+                        start = i;
+                        continue;
+                    }
+                }
+                start = i;
+                tagged = Some(kind);
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    push(start, data.len(), tagged);
+    blocks
+}
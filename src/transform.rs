@@ -0,0 +1,77 @@
+//! Rotation and flipping of decoded images
+
+use std::fmt;
+use std::str::FromStr;
+
+use image::{imageops, RgbImage};
+
+/// Clockwise rotation
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    /// The angle in degrees
+    pub fn degrees(self) -> u32 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Rotate90 => 90,
+            Rotation::Rotate180 => 180,
+            Rotation::Rotate270 => 270,
+        }
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(Rotation::None),
+            "90" => Ok(Rotation::Rotate90),
+            "180" => Ok(Rotation::Rotate180),
+            "270" => Ok(Rotation::Rotate270),
+            _ => Err(format!("unsupported rotation '{}' (expected one of: 0, 90, 180, 270)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.degrees())
+    }
+}
+
+/// Rotation followed by optional flips, applied by `Decoder::decode`
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Transform {
+    pub rotation: Rotation,
+    /// Mirrors left and right, after rotating
+    pub flip_horizontal: bool,
+    /// Mirrors top and bottom, after rotating
+    pub flip_vertical: bool,
+}
+
+impl Transform {
+    /// Whether the transform leaves images unchanged
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::default()
+    }
+
+    /// Returns the transformed image
+    pub fn apply(&self, img: RgbImage) -> RgbImage {
+        let img = match self.rotation {
+            Rotation::None => img,
+            Rotation::Rotate90 => imageops::rotate90(&img),
+            Rotation::Rotate180 => imageops::rotate180(&img),
+            Rotation::Rotate270 => imageops::rotate270(&img),
+        };
+        let img = if self.flip_horizontal { imageops::flip_horizontal(&img) } else { img };
+        if self.flip_vertical { imageops::flip_vertical(&img) } else { img }
+    }
+}
@@ -0,0 +1,60 @@
+//! Non-fatal problems found while decoding
+
+use std::fmt;
+
+use crate::charset::TextEncoding;
+
+/// A recoverable problem in the input, reported by `Decoder::warnings`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    /// The file ends inside the palette; missing entries are black
+    TruncatedPalette {
+        /// Expected palette size in bytes
+        expected: usize,
+        /// Bytes actually present
+        actual: usize,
+    },
+    /// Invalid bytes in a text field were replaced with U+FFFD, so the text may be mangled
+    MalformedText {
+        /// The affected field
+        field: TextField,
+        /// The encoding the field was decoded with
+        encoding: TextEncoding,
+    },
+    /// The sections are not laid out back to back after the palette; in lenient mode each is
+    /// read from its declared offset
+    UnusualLayout {
+        /// What is unusual, e.g. a gap or an overlap
+        reason: String,
+    },
+}
+
+/// A text field of the header
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextField {
+    MachineCode,
+    UserName,
+    Memo,
+}
+
+impl fmt::Display for TextField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            TextField::MachineCode => "machine code",
+            TextField::UserName => "user name",
+            TextField::Memo => "memo",
+        })
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::TruncatedPalette { expected, actual } =>
+                write!(f, "palette truncated ({} of {} bytes), missing entries are black", actual, expected),
+            Warning::MalformedText { field, encoding } =>
+                write!(f, "{} is not valid {}, invalid bytes were replaced", field, encoding),
+            Warning::UnusualLayout { reason } => write!(f, "unusual section layout: {}", reason),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::convert::TryInto;
+
+use mag_image_decoder::ImageInfo;
+use mag_image_decoder::output::aseprite;
+
+mod common;
+
+fn u16_at(b: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes(b[i..i + 2].try_into().unwrap())
+}
+
+fn u32_at(b: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes(b[i..i + 4].try_into().unwrap())
+}
+
+/// The (type, data) of each chunk in the first frame
+fn chunks(file: &[u8]) -> Vec<(u16, &[u8])> {
+    let frame = 128;
+    assert_eq!(u16_at(file, frame + 4), 0xf1fa);
+    let mut chunks = Vec::new();
+    let mut at = frame + 16;
+    for _ in 0..u32_at(file, frame + 12) {
+        let size = u32_at(file, at) as usize;
+        chunks.push((u16_at(file, at + 4), &file[at + 6..at + size]));
+        at += size;
+    }
+    assert_eq!(at, file.len());
+    chunks
+}
+
+#[test]
+fn indexed_sprite_keeps_palette_and_indices() {
+    let info = ImageInfo::builder(16).memo("タイトル").size(4, 2).is_200_line_mode(true).build().unwrap();
+    let image = common::indexed(4, 2, vec![0, 1, 2, 3, 15, 14, 13, 12]);
+    let mut buf = Vec::new();
+    aseprite::write_indexed(&info, &image, &mut buf).unwrap();
+
+    assert_eq!(u32_at(&buf, 0) as usize, buf.len());
+    assert_eq!(u16_at(&buf, 4), 0xa5e0);
+    assert_eq!((u16_at(&buf, 8), u16_at(&buf, 10), u16_at(&buf, 12)), (4, 2, 8));
+    assert_eq!(u16_at(&buf, 32), 16);
+    assert_eq!((buf[34], buf[35]), (1, 2));
+
+    let chunks = chunks(&buf);
+    let (_, palette) = chunks.iter().find(|(t, _)| *t == 0x2019).unwrap();
+    assert_eq!(u32_at(palette, 0), 16);
+    assert_eq!(&palette[20 + 6 + 2..20 + 6 + 6], &[16, 0xef, 1, 0xff]);
+    let (_, user_data) = chunks.iter().find(|(t, _)| *t == 0x2020).unwrap();
+    assert_eq!(&user_data[6..], "タイトル".as_bytes());
+    let (_, cel) = chunks.last().unwrap();
+    assert_eq!(chunks.last().unwrap().0, 0x2005);
+    assert_eq!((u16_at(cel, 7), u16_at(cel, 16), u16_at(cel, 18)), (0, 4, 2));
+    assert_eq!(&cel[20..], &image.pixels[..]);
+}
@@ -0,0 +1,28 @@
+//! Fixtures shared by the output format tests
+#![allow(dead_code)] // each test crate uses some of them
+
+use image::{ImageFormat, Rgb, RgbImage};
+use mag_image_decoder::IndexedImage;
+use mag_image_decoder::output::OutputFormat;
+
+/// 16 colors whose channels all differ between entries
+pub fn palette16() -> Vec<Rgb<u8>> {
+    (0..16).map(|i| Rgb([i * 16, 0xff - i * 16, i])).collect()
+}
+
+/// An image over `palette16`
+pub fn indexed(width: u32, height: u32, pixels: Vec<u8>) -> IndexedImage {
+    IndexedImage { width, height, pixels, palette: palette16() }
+}
+
+/// A small image with a different color in every pixel
+pub fn gradient() -> RgbImage {
+    RgbImage::from_fn(5, 3, |x, y| Rgb([x as u8 * 40, y as u8 * 80, 0x7f]))
+}
+
+/// Writes `img` as `format` and reads it back with the `image` crate
+pub fn round_trip(img: &RgbImage, format: OutputFormat, image_format: ImageFormat) -> RgbImage {
+    let mut buf = Vec::new();
+    format.write(img, &mut buf).unwrap();
+    image::load_from_memory_with_format(&buf, image_format).unwrap().to_rgb()
+}
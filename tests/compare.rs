@@ -0,0 +1,26 @@
+use image::{Rgb, RgbImage};
+use mag_image_decoder::analysis::compare;
+
+fn gradient(offset: u8) -> RgbImage {
+    RgbImage::from_fn(32, 16, |x, y| Rgb([(x * 6) as u8 + offset, (y * 12) as u8 + offset, 100 + offset]))
+}
+
+#[test]
+fn identical_images_are_a_perfect_match() {
+    let c = compare(&gradient(0), &gradient(0)).unwrap();
+    assert_eq!((c.psnr, c.max_difference), (f64::INFINITY, 0));
+    assert!((c.ssim - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn uniform_offset_gives_the_expected_psnr() {
+    let c = compare(&gradient(5), &gradient(0)).unwrap();
+    assert!((c.psnr - 10.0 * (255.0f64 * 255.0 / 25.0).log10()).abs() < 1e-9);
+    assert_eq!(c.max_difference, 5);
+    assert!(c.ssim > 0.9 && c.ssim < 1.0);
+}
+
+#[test]
+fn size_mismatch_is_an_error() {
+    assert!(compare(&gradient(0), &RgbImage::new(16, 16)).is_err());
+}
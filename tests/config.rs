@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+use mag_image_decoder::synth::SyntheticMag;
+
+const CONFIG: &str = r#"
+outdir = "out"
+format = "tga"
+jobs = 2
+indexed = true
+name-template = "{stem}-converted.{ext}"
+"#;
+
+/// A fresh working directory with `magdecode.toml` and two MAG files
+fn workdir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("magdecode-config-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("magdecode.toml"), CONFIG).unwrap();
+    for file in &["A.MAG", "B.MAG"] {
+        fs::write(dir.join(file), SyntheticMag::new(16, 8).build().unwrap().bytes).unwrap();
+    }
+    dir
+}
+
+fn magdecode(dir: &PathBuf, args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_magdecode"))
+        .args(args)
+        .current_dir(dir)
+        .env("XDG_CONFIG_HOME", dir.join("no-config"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+/// Byte 1 of a TGA header is 1 when there is a color map
+fn is_color_mapped(tga: &[u8]) -> bool {
+    tga[1] == 1
+}
+
+#[test]
+fn config_file_sets_the_decode_defaults() {
+    let dir = workdir("defaults");
+    magdecode(&dir, &["A.MAG", "B.MAG"]);
+    for file in &["A-converted.tga", "B-converted.tga"] {
+        assert!(is_color_mapped(&fs::read(dir.join("out").join(file)).unwrap()));
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn command_line_options_take_precedence() {
+    let dir = workdir("precedence");
+    magdecode(&dir, &["--outdir", "other", "--no-indexed", "--name-template", "{name}.{ext}", "A.MAG"]);
+    assert!(!dir.join("out").exists());
+    assert!(!is_color_mapped(&fs::read(dir.join("other").join("A.MAG.tga")).unwrap()));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,33 @@
+use mag_image_decoder::{Decoder, Error};
+
+/// A 16-color file with the given header rectangle and no image data after it
+fn header_only(x: u16, y: u16, end_x: u16, end_y: u16) -> Vec<u8> {
+    let mut bytes = b"MAKI02  PC98".to_vec();
+    bytes.extend_from_slice(&[b' '; 19]);
+    bytes.push(0x1a);
+    bytes.extend_from_slice(&[0, 0, 0, 0]);
+    for v in &[x, y, end_x, end_y] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[0; 20]);
+    bytes
+}
+
+fn geometry_error(x: u16, y: u16, end_x: u16, end_y: u16) -> Option<String> {
+    match Decoder::new(&header_only(x, y, end_x, end_y)[..]) {
+        Err(Error::InvalidGeometry { reason, .. }) => Some(reason),
+        _ => None,
+    }
+}
+
+#[test]
+fn inverted_rectangles_are_rejected() {
+    assert_eq!(geometry_error(8, 0, 7, 0).as_deref(), Some("end_x < x"));
+    assert_eq!(geometry_error(0, 1, 7, 0).as_deref(), Some("end_y < y"));
+}
+
+#[test]
+fn full_height_rectangle_does_not_wrap_to_zero() {
+    assert_eq!(geometry_error(0, 0, 7, 65535).as_deref(), Some("height exceeds 65535"));
+    assert_eq!(geometry_error(0, 1, 7, 65535), None);
+}
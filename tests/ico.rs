@@ -0,0 +1,18 @@
+use image::{GenericImageView, ImageFormat, Rgb, RgbImage};
+use mag_image_decoder::output::OutputFormat;
+
+#[test]
+fn ico_holds_standard_sizes_up_to_the_image_size() {
+    let img = RgbImage::from_pixel(64, 40, Rgb([0x20, 0x80, 0xc0]));
+    let mut buf = Vec::new();
+    OutputFormat::Ico.write(&img, &mut buf).unwrap();
+    assert_eq!(&buf[..6], &[0, 0, 1, 0, 3, 0]);
+    let sizes: Vec<u8> = (0..3).map(|i| buf[6 + i * 16]).collect();
+    assert_eq!(sizes, vec![16, 32, 48]);
+
+    let icon = image::load_from_memory_with_format(&buf, ImageFormat::ICO).unwrap();
+    assert_eq!(icon.dimensions(), (48, 48));
+    let icon = icon.to_rgba();
+    assert_eq!(icon.get_pixel(0, 0).0[3], 0);
+    assert_eq!(icon.get_pixel(24, 24).0, [0x20, 0x80, 0xc0, 0xff]);
+}
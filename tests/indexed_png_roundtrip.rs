@@ -0,0 +1,80 @@
+use std::io::Cursor;
+
+use image::Rgb;
+use mag_image_decoder::{Decoder, Encoder, ImageInfo, IndexedImage};
+use mag_image_decoder::indexed_png::{read_indexed_png, write_indexed_png};
+
+/// A deterministic image mixing flat areas, repeated blocks and noise, so that both literals and copies occur
+fn sample_image(width: u32, height: u32, num_colors: u16) -> IndexedImage {
+    let mut seed = 12345u32;
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let index = match (x / 16 + y / 8) % 3 {
+                0 => 1,
+                1 => (x / 4 + y) as u16 % num_colors,
+                _ => (seed >> 16) as u16 % num_colors,
+            };
+            pixels.push(index as u8);
+        }
+    }
+    let palette = (0..num_colors)
+        .map(|i| Rgb([(i * 17) as u8, (i * 71) as u8, (i * 113) as u8]))
+        .collect();
+    IndexedImage { width, height, pixels, palette }
+}
+
+fn sample_info(width: u32, height: u32, num_colors: u16, is_200_line_mode: bool) -> ImageInfo {
+    ImageInfo::builder(num_colors)
+        .machine_code("PC98")
+        .user_name("テスト太郎        ")
+        .memo("タイトル\r\nsecond line")
+        .rect(10, 2, 8 + width as u16 - 3, 2 + height as u16 - 1)
+        .is_200_line_mode(is_200_line_mode)
+        .build()
+        .unwrap()
+}
+
+fn encode_mag(info: &ImageInfo, image: &IndexedImage) -> Vec<u8> {
+    let mut mag = Vec::new();
+    Encoder::new(&mut mag).encode(info, image).unwrap();
+    mag
+}
+
+fn assert_round_trip(num_colors: u16, is_200_line_mode: bool) {
+    let (width, height) = (96, 40);
+    let original = sample_image(width, height, num_colors);
+    let mag = encode_mag(&sample_info(width, height, num_colors, is_200_line_mode), &original);
+
+    let decoder = Decoder::new(Cursor::new(&mag)).unwrap();
+    let decoded = decoder.decode_indexed().unwrap();
+    assert_eq!(decoded, original);
+
+    let mut png = Vec::new();
+    write_indexed_png(decoder.info(), &decoded, &mut png).unwrap();
+    let (info, restored) = read_indexed_png(Cursor::new(&png)).unwrap();
+    assert_eq!(&info, decoder.info());
+    assert_eq!(restored, decoded);
+
+    let mag2 = encode_mag(&info, &restored);
+    let decoder2 = Decoder::new(Cursor::new(&mag2)).unwrap();
+    assert_eq!(decoder2.info(), decoder.info());
+    assert_eq!(decoder2.decode_indexed().unwrap(), original);
+    assert_eq!(decoder2.decode().unwrap().into_raw(), decoder.decode().unwrap().into_raw());
+}
+
+#[test]
+fn round_trip_16_colors() {
+    assert_round_trip(16, false);
+}
+
+#[test]
+fn round_trip_256_colors() {
+    assert_round_trip(256, false);
+}
+
+#[test]
+fn round_trip_200_line_mode() {
+    assert_round_trip(16, true);
+}
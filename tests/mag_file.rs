@@ -0,0 +1,59 @@
+use image::Rgb;
+use mag_image_decoder::{Decoder, MagFile};
+use mag_image_decoder::synth::{FlagPattern, Synthetic, SyntheticMag};
+
+const APPENDED: &[u8] = b"\x1aextended comment\x00\x00";
+
+/// A file with set reserved header bytes and data after the sections
+fn unusual_file() -> (Synthetic, Vec<u8>) {
+    let synthetic = SyntheticMag::new(32, 8).pattern(FlagPattern::Random).build().unwrap();
+    let mut bytes = synthetic.bytes.clone();
+    let header_offset = 31 + bytes[31..].iter().position(|&b| b == 0x1a).unwrap() + 1;
+    bytes[header_offset + 1] = 0x12;
+    bytes[header_offset + 2] = 0x34;
+    bytes.extend_from_slice(APPENDED);
+    (synthetic, bytes)
+}
+
+#[test]
+fn unedited_file_is_written_back_byte_for_byte() {
+    let (_, bytes) = unusual_file();
+    let file = MagFile::parse(&bytes).unwrap();
+    assert_eq!(file.appended(), APPENDED);
+    assert_eq!(file.to_bytes(), bytes);
+}
+
+#[test]
+fn edits_leave_the_rest_of_the_file_untouched() {
+    let (synthetic, bytes) = unusual_file();
+    let mut file = MagFile::parse(&bytes).unwrap();
+    let body = bytes[file.header_offset()..].to_vec();
+    file.set_user_name("新しい名前").unwrap();
+    file.set_memo("a much longer memo than the one it replaces").unwrap();
+    file.set_palette_color(1, Rgb([1, 2, 3])).unwrap();
+    assert!(file.set_machine_code("TOO LONG").is_err());
+
+    let edited = file.to_bytes();
+    let changed_palette = file.header_offset() + 32 + 3..file.header_offset() + 32 + 6;
+    assert_eq!(&edited[file.header_offset()..changed_palette.start], &body[..35]);
+    assert_eq!(&edited[changed_palette.end..], &body[38..]);
+
+    let decoder = Decoder::new(&edited[..]).unwrap();
+    assert_eq!(decoder.info().user_name.trim_end(), "新しい名前");
+    assert_eq!(decoder.info().memo, "a much longer memo than the one it replaces");
+    assert_eq!(decoder.palette()[1], Rgb([1, 2, 3]));
+    assert_eq!(decoder.decode_indexed().unwrap().pixels, synthetic.image.pixels);
+    assert_eq!(decoder.trailer().unwrap().iter().flat_map(|block| block.data.clone()).collect::<Vec<u8>>(), APPENDED);
+}
+
+#[test]
+fn appended_data_can_be_replaced() {
+    let (synthetic, bytes) = unusual_file();
+    let mut file = MagFile::parse(&bytes).unwrap();
+    file.set_appended(&[]).unwrap();
+    let mut expected = synthetic.bytes;
+    let header_offset = file.header_offset();
+    expected[header_offset + 1] = 0x12;
+    expected[header_offset + 2] = 0x34;
+    assert_eq!(file.to_bytes(), expected);
+}
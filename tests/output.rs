@@ -0,0 +1,19 @@
+use image::ImageFormat;
+use mag_image_decoder::output::OutputFormat;
+
+mod common;
+
+#[test]
+fn rgb_output_round_trips_through_image() {
+    let img = common::gradient();
+    let formats = [
+        (OutputFormat::Png, ImageFormat::PNG),
+        (OutputFormat::Ppm, ImageFormat::PNM),
+        (OutputFormat::Tga, ImageFormat::TGA),
+        (OutputFormat::Tiff, ImageFormat::TIFF),
+    ];
+    for &(format, image_format) in &formats {
+        let decoded = common::round_trip(&img, format, image_format);
+        assert_eq!(*decoded, *img, "{:?}", format);
+    }
+}
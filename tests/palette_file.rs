@@ -0,0 +1,55 @@
+use image::{Rgb, RgbImage};
+use mag_image_decoder::{palette_file, ColorDistance, DecodeOptions, Decoder, Palette};
+use mag_image_decoder::synth::SyntheticMag;
+
+#[test]
+fn palette_formats_are_recognized() {
+    let red_green = vec![Rgb([255, 0, 0]), Rgb([0, 255, 0])];
+    assert_eq!(palette_file::parse(b"JASC-PAL\r\n0100\r\n2\r\n255 0 0\r\n0 255 0\r\n9 9 9\r\n").unwrap(), red_green);
+    assert_eq!(palette_file::parse(b"GIMP Palette\nName: test\nColumns: 2\n# comment\n255   0   0 Red\n  0 255   0\tGreen\n")
+                   .unwrap(), red_green);
+
+    let mut act = vec![0; 768];
+    act[..6].copy_from_slice(&[255, 0, 0, 0, 255, 0]);
+    assert_eq!(palette_file::parse(&act).unwrap().len(), 256);
+    act.extend_from_slice(&[0, 2, 0xff, 0xff]);
+    assert_eq!(palette_file::parse(&act).unwrap(), red_green);
+
+    let mag = SyntheticMag::new(64, 40).build().unwrap();
+    let embedded = Decoder::new(&mag.bytes[..]).unwrap().palette();
+    assert_eq!(palette_file::parse(&mag.bytes).unwrap(), embedded);
+
+    assert!(palette_file::parse(b"JASC-PAL\n0100\n1\n256 0 0\n").is_err());
+    assert!(palette_file::parse(b"not a palette").is_err());
+}
+
+#[test]
+fn palette_option_replaces_leading_entries() {
+    let mag = SyntheticMag::new(64, 40).build().unwrap();
+    let embedded = Decoder::new(&mag.bytes[..]).unwrap().palette();
+    let options = DecodeOptions::new().palette(vec![Rgb([1, 2, 3]), Rgb([4, 5, 6])]);
+    let decoder = Decoder::with_options(&mag.bytes[..], options).unwrap();
+    let palette = decoder.palette();
+    assert_eq!(&palette[..2], &[Rgb([1, 2, 3]), Rgb([4, 5, 6])]);
+    assert_eq!(&palette[2..], &embedded[2..]);
+
+    let indexed = decoder.decode_indexed().unwrap();
+    assert_eq!(decoder.decode().unwrap().into_raw(), indexed.to_rgb().into_raw());
+}
+
+#[test]
+fn nearest_color_depends_on_the_metric() {
+    let palette = Palette::new(&[Rgb([0, 0, 0]), Rgb([255, 0, 0]), Rgb([0, 0, 160]), Rgb([0, 100, 0])]);
+    assert_eq!(palette.len(), 4);
+    assert_eq!(palette.nearest(Rgb([200, 10, 10])), 1);
+    assert_eq!(palette.nearest(Rgb([0, 0, 0])), 0);
+    // Closer to blue in RGB, but green differences weigh more than blue ones with redmean
+    assert_eq!(palette.nearest(Rgb([0, 80, 100])), 2);
+    assert_eq!(palette.nearest_by(Rgb([0, 80, 100]), ColorDistance::Redmean), 3);
+
+    let mut img = RgbImage::from_pixel(2, 1, Rgb([250, 5, 0]));
+    img.put_pixel(1, 0, Rgb([10, 10, 150]));
+    let indexed = palette.quantize(&img, ColorDistance::Redmean);
+    assert_eq!(indexed.pixels, vec![1, 2]);
+    assert_eq!(indexed.palette, palette.colors());
+}
@@ -0,0 +1,34 @@
+use std::io::Cursor;
+
+use image::{Rgb, RgbImage};
+use mag_image_decoder::output::OutputFormat;
+use mag_image_decoder::output::png::ColorProfile;
+
+fn encode(profile: &ColorProfile) -> png::Info<'static> {
+    let img = RgbImage::from_pixel(8, 4, Rgb([0x11, 0x99, 0xff]));
+    let mut buf = Vec::new();
+    OutputFormat::Png.write_with_profile(&img, profile, &mut buf).unwrap();
+    let reader = png::Decoder::new(Cursor::new(buf)).read_info().unwrap();
+    reader.info().to_owned()
+}
+
+#[test]
+fn png_is_tagged_as_srgb_by_default() {
+    let info = encode(&ColorProfile::default());
+    assert!(info.srgb.is_some());
+    assert_eq!(info.source_gamma.map(|g| g.into_scaled()), Some(45455));
+    assert!(info.source_chromaticities.is_some());
+    assert!(info.icc_profile.is_none());
+}
+
+#[test]
+fn png_embeds_icc_profile() {
+    let mut profile = vec![0u8; 200];
+    profile[36..40].copy_from_slice(b"acsp");
+    let info = encode(&ColorProfile::Icc(profile.clone()));
+    assert!(info.srgb.is_none());
+    assert_eq!(info.icc_profile.as_deref(), Some(&profile[..]));
+
+    let info = encode(&ColorProfile::Untagged);
+    assert!(info.srgb.is_none() && info.icc_profile.is_none());
+}
@@ -0,0 +1,53 @@
+use mag_image_decoder::Decoder;
+use mag_image_decoder::indexed_png::write_indexed_png;
+use mag_image_decoder::output::OutputFormat;
+use mag_image_decoder::synth::{FlagPattern, SyntheticMag};
+use sha2::{Digest, Sha256};
+
+fn mag() -> Vec<u8> {
+    SyntheticMag::new(64, 32).num_colors(256).pattern(FlagPattern::Random).build().unwrap().bytes
+}
+
+fn rgb_png() -> Vec<u8> {
+    let img = Decoder::new(&mag()[..]).unwrap().decode().unwrap();
+    let mut png = Vec::new();
+    OutputFormat::Png.write(&img, &mut png).unwrap();
+    png
+}
+
+fn indexed_png() -> Vec<u8> {
+    let decoder = Decoder::new(&mag()[..]).unwrap();
+    let mut png = Vec::new();
+    write_indexed_png(decoder.info(), &decoder.decode_indexed().unwrap(), &mut png).unwrap();
+    png
+}
+
+fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn chunk_types(png: &[u8]) -> Vec<String> {
+    let mut types = Vec::new();
+    let mut at = 8;
+    while at + 8 <= png.len() {
+        let len = u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]) as usize;
+        types.push(String::from_utf8_lossy(&png[at + 4..at + 8]).into_owned());
+        at += 12 + len;
+    }
+    types
+}
+
+#[test]
+fn png_chunks_are_fixed_and_untimestamped() {
+    assert_eq!(chunk_types(&rgb_png()), ["IHDR", "sRGB", "gAMA", "cHRM", "IDAT", "IEND"]);
+    let indexed = chunk_types(&indexed_png());
+    assert!(!indexed.iter().any(|t| t == "tIME"));
+    assert_eq!(indexed.iter().filter(|t| *t == "iTXt").count(), 9);
+}
+
+/// Pins the exact bytes, so that an encoder or dependency change breaking checksums is noticed
+#[test]
+fn png_output_matches_the_pinned_checksum() {
+    assert_eq!(sha256(&rgb_png()), "3a233c8634d723fe24cd2d8de4aa1e614531f5c4299ec95768dd77520523aa43");
+    assert_eq!(sha256(&indexed_png()), "cdd0d5668911f10418cce3312b998382223ba347358427ea72404bd4c5b92603");
+}
@@ -0,0 +1,73 @@
+use image::{Rgb, RgbImage};
+use mag_image_decoder::output::OutputFormat;
+
+/// A QOI decoder written from the specification, independent of the encoder
+fn decode_qoi(data: &[u8]) -> (u32, u32, Vec<[u8; 4]>) {
+    assert_eq!(&data[..4], b"qoif");
+    let width = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    let height = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
+    let num_pixels = (width * height) as usize;
+    let mut index = [[0u8; 4]; 64];
+    let mut px = [0, 0, 0, 255];
+    let mut pixels = Vec::with_capacity(num_pixels);
+    let mut at = 14;
+    while pixels.len() < num_pixels {
+        let b = data[at];
+        at += 1;
+        match b {
+            0xfe => {
+                px[..3].copy_from_slice(&data[at..at + 3]);
+                at += 3;
+            }
+            0xff => {
+                px.copy_from_slice(&data[at..at + 4]);
+                at += 4;
+            }
+            _ => match b >> 6 {
+                0 => px = index[b as usize],
+                1 => {
+                    px[0] = px[0].wrapping_add((b >> 4 & 3).wrapping_sub(2));
+                    px[1] = px[1].wrapping_add((b >> 2 & 3).wrapping_sub(2));
+                    px[2] = px[2].wrapping_add((b & 3).wrapping_sub(2));
+                }
+                2 => {
+                    let dg = (b & 0x3f).wrapping_sub(32);
+                    let next = data[at];
+                    at += 1;
+                    px[0] = px[0].wrapping_add(dg.wrapping_add(next >> 4).wrapping_sub(8));
+                    px[1] = px[1].wrapping_add(dg);
+                    px[2] = px[2].wrapping_add(dg.wrapping_add(next & 0xf).wrapping_sub(8));
+                }
+                _ => {
+                    for _ in 0..(b & 0x3f) {
+                        pixels.push(px);
+                    }
+                }
+            },
+        }
+        let [r, g, b, a] = px;
+        index[(r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64] = px;
+        pixels.push(px);
+    }
+    assert_eq!(&data[at..], &[0, 0, 0, 0, 0, 0, 0, 1]);
+    (width, height, pixels)
+}
+
+#[test]
+fn qoi_output_decodes_with_a_reference_decoder() {
+    let colors = [[255, 0, 0], [0, 0, 0], [1, 1, 1], [255, 0, 0], [1, 1, 1], [0, 0, 0], [0, 0, 0], [90, 200, 30]];
+    let mut img = RgbImage::from_fn(8, 12, |x, y| Rgb(colors[((x + y * 3) % 8) as usize]));
+    // a run longer than one QOI_OP_RUN can hold
+    for x in 0..8 {
+        for y in 4..12 {
+            img.put_pixel(x, y, Rgb([0, 0, 0]));
+        }
+    }
+    let mut qoi = Vec::new();
+    OutputFormat::Qoi.write(&img, &mut qoi).unwrap();
+
+    let (width, height, pixels) = decode_qoi(&qoi);
+    assert_eq!((width, height), img.dimensions());
+    let expected: Vec<[u8; 4]> = img.pixels().map(|p| [p[0], p[1], p[2], 255]).collect();
+    assert_eq!(pixels, expected);
+}
@@ -0,0 +1,23 @@
+use image::{Rgb, RgbImage};
+use mag_image_decoder::scale::Upscaler;
+
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+
+#[test]
+fn hq2x_smooths_a_diagonal_edge_without_fringes() {
+    // white above the diagonal, black on and below it
+    let img = RgbImage::from_fn(4, 4, |x, y| if x > y { WHITE } else { BLACK });
+    let out = Upscaler::Hq2x.apply(&img);
+    assert_eq!(out.dimensions(), (8, 8));
+
+    // (1, 1) is black with white above and right: only the corner between them is blended
+    assert_eq!(out[(2, 2)], BLACK);
+    assert_eq!(out[(2, 3)], BLACK);
+    assert_eq!(out[(3, 3)], BLACK);
+    let Rgb([r, g, b]) = out[(3, 2)];
+    assert!(r == g && g == b && r > 128 && r < 255, "{:?}", out[(3, 2)]);
+
+    // pixels away from the edge are untouched
+    assert!([(0, 7), (1, 6), (7, 0), (6, 1)].iter().all(|&(x, y)| out[(x, y)] == img[(x / 2, y / 2)]));
+}
@@ -0,0 +1,278 @@
+use image::{imageops, Rgb, RgbImage};
+use mag_image_decoder::{DecodeOptions, Decoder, Error, FormatKind, LineDoubling, RetroImage, Rotation, TextEncoding,
+                         TextField, Transform, Warning};
+use mag_image_decoder::synth::{Corruption, FlagPattern, Section, SyntheticMag};
+use mag_image_decoder::trace::{self, Action};
+
+fn decode(bytes: &[u8]) -> mag_image_decoder::Result<mag_image_decoder::IndexedImage> {
+    Decoder::new(bytes)?.decode_indexed()
+}
+
+#[test]
+fn generated_images_decode_to_the_expected_pixels() {
+    for &num_colors in &[16, 256] {
+        for pattern in (1..16).map(FlagPattern::Copy).chain(vec![FlagPattern::Literal, FlagPattern::Random]) {
+            let mag = SyntheticMag::new(64, 40).num_colors(num_colors).pattern(pattern).seed(7).build().unwrap();
+            assert_eq!(decode(&mag.bytes).unwrap(), mag.image, "{} colors, {:?}", num_colors, pattern);
+        }
+    }
+}
+
+#[test]
+fn generated_header_matches_settings() {
+    let mag = SyntheticMag::new(32, 10).num_colors(256).is_200_line_mode(true).build().unwrap();
+    let decoder = Decoder::new(&mag.bytes[..]).unwrap();
+    let info = decoder.info();
+    assert_eq!((info.width, info.height, info.num_colors, info.is_200_line_mode), (32, 10, 256, true));
+    assert_eq!(decoder.decode().unwrap().dimensions(), (32, 20));
+}
+
+#[test]
+fn corrupted_files_fail_without_panicking() {
+    let base = SyntheticMag::new(64, 40).pattern(FlagPattern::Random);
+    let len = base.build().unwrap().bytes.len();
+    let corruptions = vec![
+        Corruption::BadMagic,
+        Corruption::Truncate(20),
+        Corruption::Truncate(60),
+        Corruption::Truncate(len - 1),
+        Corruption::SectionOffset { section: Section::Pixel, offset: 1_000_000 },
+        Corruption::SectionOffset { section: Section::FlagB, offset: 0 },
+    ];
+    for corruption in corruptions {
+        let mag = base.clone().corrupt(corruption).build().unwrap();
+        assert!(decode(&mag.bytes).is_err(), "{:?}", corruption);
+    }
+    for offset in 0..len {
+        let mag = base.clone().corrupt(Corruption::XorByte { offset, mask: 0xff }).build().unwrap();
+        let _ = decode(&mag.bytes);
+    }
+}
+
+#[test]
+fn bad_magic_after_truncation_overwrites_what_is_left() {
+    let mag = SyntheticMag::new(64, 40).corrupt(Corruption::Truncate(2)).corrupt(Corruption::BadMagic).build().unwrap();
+    assert_eq!(mag.bytes, b"XX");
+}
+
+#[test]
+fn invalid_settings_are_rejected() {
+    assert!(SyntheticMag::new(60, 40).build().is_err());
+    assert!(SyntheticMag::new(64, 0).build().is_err());
+    assert!(SyntheticMag::new(64, 40).num_colors(8).build().is_err());
+    assert!(matches!(SyntheticMag::new(64, 40).pattern(FlagPattern::Copy(16)).build(), Err(Error::OtherError(_))));
+}
+
+#[test]
+fn degenerate_headers_are_rejected() {
+    let mag = SyntheticMag::new(64, 40).build().unwrap();
+    let header = mag.bytes.iter().position(|&b| b == 0x1a).unwrap() + 1;
+    let patched = |offset: usize, value: &[u8]| {
+        let mut bytes = mag.bytes.clone();
+        bytes[header + offset..header + offset + value.len()].copy_from_slice(value);
+        Decoder::new(&bytes[..]).err()
+    };
+
+    let flag_a_offset = &mag.bytes[header + 12..header + 16];
+    assert!(matches!(patched(16, flag_a_offset), Some(Error::DegenerateImage(_))));
+    assert!(matches!(patched(28, &[0; 4]), Some(Error::DegenerateImage(_))));
+    assert!(matches!(patched(10, &[0xff, 0xff]), Some(Error::InvalidGeometry { .. })));
+}
+
+#[test]
+fn misordered_sections_only_fail_decoding() {
+    let mut bytes = SyntheticMag::new(64, 40).build().unwrap().bytes;
+    let header = bytes.iter().position(|&b| b == 0x1a).unwrap() + 1;
+    bytes[header + 16..header + 20].copy_from_slice(&[0; 4]);
+    let decoder = Decoder::new(&bytes[..]).unwrap();
+    assert_eq!((decoder.info().width, decoder.info().height), (64, 40));
+    assert!(matches!(decoder.decode_indexed(), Err(Error::InvalidFormat(_))));
+}
+
+#[test]
+fn memo_charset_is_detected_when_enabled() {
+    let mag = SyntheticMag::new(64, 40).build().unwrap();
+    let memo_start = mag.bytes.windows(9).position(|w| w == b"generated").unwrap();
+    let with_memo = |memo: &[u8]| {
+        let mut bytes = mag.bytes[..memo_start].to_vec();
+        bytes.extend_from_slice(memo);
+        bytes.extend_from_slice(&mag.bytes[memo_start + 9..]);
+        bytes
+    };
+    let memo_of = |bytes: &[u8], detect| {
+        let decoder = Decoder::with_options(bytes, DecodeOptions::new().detect_charset(detect)).unwrap();
+        (decoder.info().memo.clone(), decoder.info().text_encoding, decoder.warnings().len())
+    };
+
+    // "タイトル" in EUC-JP, and UTF-8 that is not valid Shift_JIS
+    let euc_jp = with_memo(b"\xa5\xbf\xa5\xa4\xa5\xc8\xa5\xeb");
+    let utf8 = with_memo("640×400".as_bytes());
+    assert_eq!(memo_of(&euc_jp, true), ("タイトル".to_owned(), TextEncoding::EucJp, 0));
+    assert_eq!(memo_of(&utf8, true), ("640×400".to_owned(), TextEncoding::Utf8, 0));
+    assert_eq!(memo_of(&utf8, false).1, TextEncoding::ShiftJis);
+    assert_eq!(memo_of(&utf8, false).2, 1);
+    let warning = Decoder::new(&utf8[..]).unwrap().warnings()[0].clone();
+    assert_eq!(warning, Warning::MalformedText { field: TextField::Memo, encoding: TextEncoding::ShiftJis });
+    assert_eq!(warning.to_string(), "memo is not valid Shift_JIS, invalid bytes were replaced");
+    assert_eq!(memo_of(&mag.bytes, true), ("generated".to_owned(), TextEncoding::ShiftJis, 0));
+}
+
+#[test]
+fn truncated_user_name_does_not_affect_detection() {
+    // "ｱ" and the lead byte of a two-byte character cut off by the field width in Shift_JIS,
+    // which would pass as one complete character in EUC-JP
+    let mut bytes = SyntheticMag::new(64, 40).build().unwrap().bytes;
+    bytes[12..31].copy_from_slice(b"ABCDEFGHIJKLMNOPQ\xb1\xe0");
+    let decoder = Decoder::with_options(&bytes[..], DecodeOptions::new().detect_charset(true)).unwrap();
+    assert_eq!(decoder.info().text_encoding, TextEncoding::ShiftJis);
+    assert_eq!(decoder.info().user_name, "ABCDEFGHIJKLMNOPQｱ");
+    assert!(decoder.warnings().is_empty());
+}
+
+#[test]
+fn lenient_mode_reads_sections_from_their_own_offsets() {
+    let mag = SyntheticMag::new(64, 40).pattern(FlagPattern::Random).seed(3).build().unwrap();
+    let header = mag.bytes.iter().position(|&b| b == 0x1a).unwrap() + 1;
+    let field = |i: usize| {
+        let b = &mag.bytes[header + i..];
+        u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize
+    };
+    let (flag_a, flag_b, flag_b_size, pixel) = (field(12), field(16), field(20), field(24));
+
+    // Flag B stored before flag A, with 3 bytes of padding before the pixel section
+    let mut bytes = mag.bytes[..header + flag_a].to_vec();
+    bytes.extend_from_slice(&mag.bytes[header + flag_b..header + flag_b + flag_b_size]);
+    bytes.extend_from_slice(&mag.bytes[header + flag_a..header + flag_b]);
+    bytes.extend_from_slice(&[0; 3]);
+    bytes.extend_from_slice(&mag.bytes[header + pixel..]);
+    let set = |bytes: &mut Vec<u8>, i: usize, v: usize| bytes[header + i..header + i + 4].copy_from_slice(&(v as u32).to_le_bytes());
+    set(&mut bytes, 12, flag_a + flag_b_size);
+    set(&mut bytes, 16, flag_a);
+    set(&mut bytes, 24, pixel + 3);
+
+    assert!(decode(&bytes).is_err());
+    let decoder = Decoder::with_options(&bytes[..], DecodeOptions::new().lenient(true)).unwrap();
+    assert_eq!(decoder.decode_indexed().unwrap(), mag.image);
+    let warnings = decoder.warnings().iter().map(|w| w.to_string()).collect::<Vec<_>>();
+    assert!(warnings.iter().any(|w| w.ends_with("in the order flag B, flag A, pixel")), "{:?}", warnings);
+    assert!(warnings.iter().any(|w| w.ends_with("3 unused bytes before the pixel section")), "{:?}", warnings);
+    assert_eq!(warnings.len(), 2, "{:?}", warnings);
+
+    let standard = Decoder::with_options(&mag.bytes[..], DecodeOptions::new().lenient(true)).unwrap();
+    assert!(standard.warnings().is_empty());
+}
+
+#[test]
+fn user_name_cut_mid_character_is_trimmed() {
+    let mut mag = SyntheticMag::new(64, 40).build().unwrap();
+    // "ＡＢＣＤＥＦＧＨ" and the lead byte of "Ｉ" fill the 17 name bytes before the padding
+    let mut name: Vec<u8> = (0x60..0x68).flat_map(|trail| vec![0x82, trail]).collect();
+    name.extend_from_slice(&[0x82, b' ', b' ']);
+    mag.bytes[12..31].copy_from_slice(&name);
+
+    let decoder = Decoder::new(&mag.bytes[..]).unwrap();
+    assert_eq!(decoder.info().user_name, "ＡＢＣＤＥＦＧＨ  ");
+    assert_eq!(decoder.raw_user_name(), &name[..]);
+    assert!(decoder.warnings().is_empty());
+}
+
+#[test]
+fn scanlines_dim_the_inserted_rows() {
+    let mag = SyntheticMag::new(32, 10).is_200_line_mode(true).pattern(FlagPattern::Random).build().unwrap();
+    let options = DecodeOptions::new().line_doubling(LineDoubling::Scanlines { brightness: 0.5 });
+    let img = Decoder::with_options(&mag.bytes[..], options).unwrap().decode().unwrap();
+    let stored = mag.image.to_rgb();
+    assert_eq!(img.dimensions(), (32, 20));
+    for (x, y, &p) in stored.enumerate_pixels() {
+        assert_eq!(img[(x, y * 2)], p);
+        assert_eq!(img[(x, y * 2 + 1)].0, p.0.map(|v| (f32::from(v) / 2.0).round() as u8));
+    }
+}
+
+#[test]
+fn decode_onto_matches_decode() {
+    for &is_200_line_mode in &[false, true] {
+        let mag = SyntheticMag::new(32, 10).is_200_line_mode(is_200_line_mode).pattern(FlagPattern::Random).build().unwrap();
+        let decoder = Decoder::new(&mag.bytes[..]).unwrap();
+        let expected = decoder.decode().unwrap();
+        let mut canvas = RgbImage::from_pixel(40, 30, Rgb([1, 2, 3]));
+        decoder.decode_onto(&mut canvas, 5, 7).unwrap();
+        let blitted = imageops::crop(&mut canvas, 5, 7, expected.width(), expected.height()).to_image();
+        assert_eq!(blitted.into_raw(), expected.into_raw());
+        assert_eq!(canvas.get_pixel(4, 7), &Rgb([1, 2, 3]));
+
+        let mut small = RgbImage::new(36, 16);
+        assert!(decoder.decode_onto(&mut small, 5, 0).is_err());
+        assert!(small.pixels().all(|p| p.0 == [0, 0, 0]));
+    }
+}
+
+#[test]
+fn decode_argb32_packs_the_decoded_pixels() {
+    let mag = SyntheticMag::new(32, 10).is_200_line_mode(true).pattern(FlagPattern::Random).build().unwrap();
+    let rotated = DecodeOptions::new().transform(Transform { rotation: Rotation::Rotate90, ..Transform::default() });
+    for options in [DecodeOptions::new(), rotated] {
+        let decoder = Decoder::with_options(&mag.bytes[..], options).unwrap();
+        let expected = decoder.decode().unwrap();
+        let stride = expected.width() as usize + 3;
+        let packed = decoder.decode_argb32(0xff, Some(stride)).unwrap();
+        assert_eq!(packed.len(), stride * expected.height() as usize);
+        for (x, y, p) in expected.enumerate_pixels() {
+            let [r, g, b] = p.0;
+            assert_eq!(packed[y as usize * stride + x as usize], u32::from_be_bytes([0xff, r, g, b]));
+        }
+        assert_eq!(packed[stride - 1], 0);
+        assert!(decoder.decode_argb32(0, Some(stride - 4)).is_err());
+    }
+}
+
+#[test]
+fn decoder_works_as_retro_image() {
+    let mag = SyntheticMag::new(32, 10).is_200_line_mode(true).pattern(FlagPattern::Random).build().unwrap();
+    let decoder = Decoder::new(&mag.bytes[..]).unwrap();
+    let image: &dyn RetroImage = &decoder;
+    assert_eq!(image.format_name(), "MAG");
+    assert_eq!(image.info(), decoder.info());
+    assert_eq!(image.decode_indexed().unwrap(), mag.image);
+    assert_eq!(image.decode().unwrap().into_raw(), decoder.decode().unwrap().into_raw());
+    assert_eq!(image.machine(), decoder.info().machine_code.trim_end());
+}
+
+#[test]
+fn sniff_checks_magic_and_header() {
+    let mag = SyntheticMag::new(64, 40).build().unwrap();
+    assert_eq!(mag_image_decoder::sniff(&mag.bytes), Some(FormatKind::Mag));
+    assert_eq!(mag_image_decoder::sniff(&mag.bytes[..20]), Some(FormatKind::Mag));
+    assert_eq!(mag_image_decoder::sniff(b"\x89PNG\r\n\x1a\n"), None);
+
+    let bad_magic = SyntheticMag::new(64, 40).corrupt(Corruption::BadMagic).build().unwrap();
+    assert_eq!(mag_image_decoder::sniff(&bad_magic.bytes), None);
+    let header = mag.bytes.iter().position(|&b| b == 0x1a).unwrap() + 1;
+    let mut bad_geometry = mag.bytes.clone();
+    bad_geometry[header + 4..header + 6].copy_from_slice(&[0xff, 0xff]);
+    assert_eq!(mag_image_decoder::sniff(&bad_geometry), None);
+}
+
+#[test]
+fn trace_accounts_for_every_pixel() {
+    for &num_colors in &[16, 256] {
+        let literal = SyntheticMag::new(64, 8).num_colors(num_colors).pattern(FlagPattern::Literal).build().unwrap();
+        let units = Decoder::new(&literal.bytes[..]).unwrap().trace().unwrap();
+        let unit_width = if num_colors == 16 { 4 } else { 2 };
+        assert_eq!(units.len(), 64 / unit_width * 8);
+        assert!(units.iter().all(|u| u.action == Action::Literal && u.width == unit_width as u32));
+        assert!(trace::heatmap(&units, 64, 8).pixels().all(|p| *p == trace::LITERAL_COLOR));
+
+        let mag = SyntheticMag::new(64, 40).num_colors(num_colors).pattern(FlagPattern::Random).seed(3).build().unwrap();
+        let units = Decoder::new(&mag.bytes[..]).unwrap().trace().unwrap();
+        assert!(units.iter().any(|u| u.action != Action::Literal));
+        for unit in units {
+            if let Action::Copy { dx, dy, .. } = unit.action {
+                for i in 0..unit.width {
+                    let (x, y) = (unit.x + i, unit.y);
+                    assert_eq!(mag.image.index(x, y), mag.image.index(x - dx, y - dy), "{:?}", unit);
+                }
+            }
+        }
+    }
+}
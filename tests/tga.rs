@@ -0,0 +1,23 @@
+use mag_image_decoder::output::OutputFormat;
+use mag_image_decoder::output::tga;
+
+mod common;
+
+#[test]
+fn tga_is_stored_top_down_with_a_footer() {
+    let mut buf = Vec::new();
+    OutputFormat::Tga.write(&common::gradient(), &mut buf).unwrap();
+    assert_eq!((buf[2], buf[16], buf[17] & 0x20), (2, 24, 0x20));
+    assert_eq!(&buf[18..21], &[0x7f, 0, 0]);
+    assert!(buf.ends_with(b"TRUEVISION-XFILE.\0"));
+}
+
+#[test]
+fn indexed_tga_keeps_palette_indices() {
+    let image = common::indexed(4, 2, vec![0, 1, 2, 3, 3, 2, 1, 0]);
+    let mut buf = Vec::new();
+    tga::write_indexed(&image, &mut buf).unwrap();
+    assert_eq!((buf[1], buf[2], buf[16]), (1, 1, 8));
+    assert_eq!(&buf[18 + 16 * 3..18 + 16 * 3 + 8], &image.pixels[..]);
+    assert_eq!(&buf[18 + 3..18 + 6], &[1, 0xef, 0x10]);
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use mag_image_decoder::ImageInfo;
+use mag_image_decoder::output::tiff;
+
+mod common;
+
+/// Reads the first IFD of a little-endian TIFF as tag -> value bytes
+fn read_ifd(tiff: &[u8]) -> HashMap<u16, Vec<u8>> {
+    let u16_at = |i: usize| u16::from_le_bytes(tiff[i..i + 2].try_into().unwrap());
+    let u32_at = |i: usize| u32::from_le_bytes(tiff[i..i + 4].try_into().unwrap());
+    assert_eq!(&tiff[..4], b"II*\0");
+    let ifd = u32_at(4) as usize;
+    (0..u16_at(ifd) as usize)
+        .map(|n| {
+            let entry = ifd + 2 + n * 12;
+            let size = match u16_at(entry + 2) { 2 => 1, 3 => 2, 4 => 4, 5 => 8, t => panic!("type {}", t) };
+            let len = size * u32_at(entry + 4) as usize;
+            let start = if len > 4 { u32_at(entry + 8) as usize } else { entry + 8 };
+            (u16_at(entry), tiff[start..start + len].to_vec())
+        })
+        .collect()
+}
+
+#[test]
+fn palette_tiff_keeps_indices_and_metadata() {
+    let info = ImageInfo::builder(16).user_name("作者").memo("タイトル").size(3, 2).build().unwrap();
+    let image = common::indexed(3, 2, vec![0, 1, 15, 2, 3, 4]);
+    let mut buf = Vec::new();
+    tiff::write_indexed(&info, &image, &mut buf).unwrap();
+    let ifd = read_ifd(&buf);
+
+    assert_eq!(ifd[&258], [4, 0]);
+    assert_eq!(ifd[&262], [3, 0]);
+    assert_eq!(ifd[&270], "タイトル\0".as_bytes());
+    assert_eq!(ifd[&315], "作者\0".as_bytes());
+    let color_map = &ifd[&320];
+    assert_eq!(color_map.len(), 3 * 16 * 2);
+    assert_eq!(&color_map[2..4], &(16u16 * 257).to_le_bytes());
+    assert_eq!(&color_map[32 + 2..32 + 4], &(0xefu16 * 257).to_le_bytes());
+    let offset = u32::from_le_bytes(ifd[&273][..].try_into().unwrap()) as usize;
+    assert_eq!(&buf[offset..], &[0x01, 0xf0, 0x23, 0x40]);
+}
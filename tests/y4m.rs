@@ -0,0 +1,23 @@
+use image::{Rgb, RgbImage};
+use mag_image_decoder::output::y4m::Y4mWriter;
+
+#[test]
+fn y4m_repeats_frames_in_limited_range() {
+    let mut img = RgbImage::from_pixel(4, 2, Rgb([0, 0, 0]));
+    img.put_pixel(3, 1, Rgb([0xff, 0xff, 0xff]));
+    let mut writer = Y4mWriter::new(Vec::new(), 4, 2, 30).unwrap();
+    writer.write_frames(&img, 2).unwrap();
+    let buf = writer.into_inner().unwrap();
+
+    let header_len = buf.iter().position(|&b| b == b'\n').unwrap() + 1;
+    assert!(buf.starts_with(b"YUV4MPEG2 W4 H2 F30:1 Ip A1:1 C420jpeg"));
+    let frame_len = 6 + 4 * 2 + 2 * 2;
+    assert_eq!(buf.len(), header_len + frame_len * 2);
+    let frame = &buf[header_len..header_len + frame_len];
+    assert_eq!(&frame[..6], b"FRAME\n");
+    assert_eq!(&frame[6..14], &[16, 16, 16, 16, 16, 16, 16, 235]);
+    assert_eq!(&frame[14..], &[128, 128, 128, 128]);
+    assert_eq!(frame, &buf[header_len + frame_len..]);
+
+    assert!(Y4mWriter::new(Vec::new(), 3, 2, 30).is_err());
+}